@@ -0,0 +1,30 @@
+-- Tables read and written by the postgres backend, see src/repository/postgres_repository.rs
+CREATE EXTENSION IF NOT EXISTS postgis;
+
+CREATE SCHEMA IF NOT EXISTS geoxidated;
+
+CREATE TABLE IF NOT EXISTS geoxidated.features_collection (
+    id BIGSERIAL PRIMARY KEY,
+    label TEXT NOT NULL,
+    properties JSONB NOT NULL DEFAULT '{}'
+);
+
+CREATE TABLE IF NOT EXISTS geoxidated.feature (
+    id BIGSERIAL PRIMARY KEY,
+    properties JSONB NOT NULL DEFAULT '{}',
+    geometry geometry(Geometry, 4326) NOT NULL
+);
+
+-- a feature can belong to several collections
+CREATE TABLE IF NOT EXISTS geoxidated.features_in_collection (
+    feature_id BIGINT NOT NULL REFERENCES geoxidated.feature(id) ON DELETE CASCADE,
+    collection_id BIGINT NOT NULL REFERENCES geoxidated.features_collection(id) ON DELETE CASCADE,
+    CONSTRAINT features_in_collection_unique UNIQUE (feature_id, collection_id)
+);
+
+CREATE TABLE IF NOT EXISTS geoxidated.collection_filter (
+    collection_id BIGINT NOT NULL REFERENCES geoxidated.features_collection(id) ON DELETE CASCADE,
+    name TEXT NOT NULL,
+    filter JSONB NOT NULL,
+    PRIMARY KEY (collection_id, name)
+);
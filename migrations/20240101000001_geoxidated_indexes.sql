@@ -0,0 +1,9 @@
+-- bbox and spatial filters
+CREATE INDEX IF NOT EXISTS feature_geometry_idx ON geoxidated.feature USING GIST (geometry);
+
+-- property filters and collection search
+CREATE INDEX IF NOT EXISTS feature_properties_idx ON geoxidated.feature USING GIN (properties);
+CREATE INDEX IF NOT EXISTS features_collection_properties_idx ON geoxidated.features_collection USING GIN (properties);
+
+-- listing the features of a collection, the unique constraint already covers lookups by feature
+CREATE INDEX IF NOT EXISTS features_in_collection_collection_idx ON geoxidated.features_in_collection (collection_id, feature_id);
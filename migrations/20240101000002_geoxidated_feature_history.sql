@@ -0,0 +1,91 @@
+-- Every revision of the features, written by triggers so no backend write can skip it.
+-- create, update and delete rows hold the feature as it was left, attach and detach rows the collection.
+CREATE TABLE IF NOT EXISTS geoxidated.feature_history (
+    revision BIGSERIAL PRIMARY KEY,
+    feature_id BIGINT NOT NULL,
+    collection_id BIGINT,
+    operation TEXT NOT NULL CHECK (operation IN ('create', 'update', 'delete', 'attach', 'detach')),
+    properties JSONB,
+    geometry geometry(Geometry, 4326),
+    changed_by TEXT,
+    changed_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+-- the history of a feature and the membership of a collection at an instant
+CREATE INDEX IF NOT EXISTS feature_history_feature_idx ON geoxidated.feature_history (feature_id, revision);
+CREATE INDEX IF NOT EXISTS feature_history_collection_idx ON geoxidated.feature_history (collection_id, feature_id, revision);
+
+-- the editor is set per transaction with set_config('geoxidated.editor', ..., true)
+CREATE OR REPLACE FUNCTION geoxidated.record_feature_revision() RETURNS trigger AS $$
+BEGIN
+    IF TG_OP = 'DELETE' THEN
+        INSERT INTO geoxidated.feature_history(feature_id, operation, properties, geometry, changed_by)
+        SELECT id, 'delete', properties, geometry, NULLIF(current_setting('geoxidated.editor', true), '')
+        FROM   old_rows
+        ORDER BY id;
+    ELSE
+        INSERT INTO geoxidated.feature_history(feature_id, operation, properties, geometry, changed_by)
+        SELECT id, CASE TG_OP WHEN 'INSERT' THEN 'create' ELSE 'update' END, properties, geometry,
+               NULLIF(current_setting('geoxidated.editor', true), '')
+        FROM   new_rows
+        ORDER BY id;
+    END IF;
+    RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE OR REPLACE FUNCTION geoxidated.record_feature_membership() RETURNS trigger AS $$
+BEGIN
+    IF TG_OP = 'DELETE' THEN
+        INSERT INTO geoxidated.feature_history(feature_id, collection_id, operation, changed_by)
+        SELECT feature_id, collection_id, 'detach', NULLIF(current_setting('geoxidated.editor', true), '')
+        FROM   old_rows
+        ORDER BY feature_id;
+    ELSE
+        INSERT INTO geoxidated.feature_history(feature_id, collection_id, operation, changed_by)
+        SELECT feature_id, collection_id, 'attach', NULLIF(current_setting('geoxidated.editor', true), '')
+        FROM   new_rows
+        ORDER BY feature_id;
+    END IF;
+    RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
+
+-- statement triggers, a bulk insert or an import records its revisions with one insert
+DROP TRIGGER IF EXISTS feature_created ON geoxidated.feature;
+CREATE TRIGGER feature_created AFTER INSERT ON geoxidated.feature
+    REFERENCING NEW TABLE AS new_rows
+    FOR EACH STATEMENT EXECUTE FUNCTION geoxidated.record_feature_revision();
+
+DROP TRIGGER IF EXISTS feature_updated ON geoxidated.feature;
+CREATE TRIGGER feature_updated AFTER UPDATE ON geoxidated.feature
+    REFERENCING NEW TABLE AS new_rows
+    FOR EACH STATEMENT EXECUTE FUNCTION geoxidated.record_feature_revision();
+
+DROP TRIGGER IF EXISTS feature_deleted ON geoxidated.feature;
+CREATE TRIGGER feature_deleted AFTER DELETE ON geoxidated.feature
+    REFERENCING OLD TABLE AS old_rows
+    FOR EACH STATEMENT EXECUTE FUNCTION geoxidated.record_feature_revision();
+
+DROP TRIGGER IF EXISTS feature_attached ON geoxidated.features_in_collection;
+CREATE TRIGGER feature_attached AFTER INSERT ON geoxidated.features_in_collection
+    REFERENCING NEW TABLE AS new_rows
+    FOR EACH STATEMENT EXECUTE FUNCTION geoxidated.record_feature_membership();
+
+DROP TRIGGER IF EXISTS feature_detached ON geoxidated.features_in_collection;
+CREATE TRIGGER feature_detached AFTER DELETE ON geoxidated.features_in_collection
+    REFERENCING OLD TABLE AS old_rows
+    FOR EACH STATEMENT EXECUTE FUNCTION geoxidated.record_feature_membership();
+
+-- features stored before the history existed start with the state they had when it was created
+DO $$
+BEGIN
+    IF NOT EXISTS (SELECT 1 FROM geoxidated.feature_history) THEN
+        INSERT INTO geoxidated.feature_history(feature_id, operation, properties, geometry)
+        SELECT id, 'create', properties, geometry FROM geoxidated.feature ORDER BY id;
+
+        INSERT INTO geoxidated.feature_history(feature_id, collection_id, operation)
+        SELECT feature_id, collection_id, 'attach' FROM geoxidated.features_in_collection ORDER BY feature_id;
+    END IF;
+END;
+$$;
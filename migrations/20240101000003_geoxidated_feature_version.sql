@@ -0,0 +1,2 @@
+-- optimistic concurrency, every update of a feature moves its version on
+ALTER TABLE geoxidated.feature ADD COLUMN IF NOT EXISTS version BIGINT NOT NULL DEFAULT 1;
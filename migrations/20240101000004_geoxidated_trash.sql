@@ -0,0 +1,53 @@
+-- Soft delete, trashed features and collections keep their rows until they are purged
+ALTER TABLE geoxidated.feature ADD COLUMN IF NOT EXISTS deleted_at TIMESTAMPTZ;
+ALTER TABLE geoxidated.features_collection ADD COLUMN IF NOT EXISTS deleted_at TIMESTAMPTZ;
+
+CREATE INDEX IF NOT EXISTS feature_trash_idx ON geoxidated.feature (deleted_at) WHERE deleted_at IS NOT NULL;
+CREATE INDEX IF NOT EXISTS features_collection_trash_idx ON geoxidated.features_collection (deleted_at) WHERE deleted_at IS NOT NULL;
+
+-- what the reads see, a link only counts while both its feature and its collection are out of the trash
+CREATE OR REPLACE VIEW geoxidated.live_features_collection AS
+    SELECT id, label, properties FROM geoxidated.features_collection WHERE deleted_at IS NULL;
+
+CREATE OR REPLACE VIEW geoxidated.live_features_in_collection AS
+    SELECT fi.feature_id, fi.collection_id
+    FROM   geoxidated.features_in_collection fi
+    WHERE  EXISTS (SELECT 1 FROM geoxidated.feature fa WHERE fa.id = fi.feature_id AND fa.deleted_at IS NULL)
+    AND    EXISTS (SELECT 1 FROM geoxidated.features_collection c WHERE c.id = fi.collection_id AND c.deleted_at IS NULL);
+
+-- trashing and restoring are updates of deleted_at, the rows removed by a purge are gone for good
+ALTER TABLE geoxidated.feature_history DROP CONSTRAINT IF EXISTS feature_history_operation_check;
+ALTER TABLE geoxidated.feature_history ADD CONSTRAINT feature_history_operation_check
+    CHECK (operation IN ('create', 'update', 'delete', 'restore', 'purge', 'attach', 'detach'));
+
+CREATE OR REPLACE FUNCTION geoxidated.record_feature_revision() RETURNS trigger AS $$
+BEGIN
+    IF TG_OP = 'DELETE' THEN
+        INSERT INTO geoxidated.feature_history(feature_id, operation, properties, geometry, changed_by)
+        SELECT id, 'purge', properties, geometry, NULLIF(current_setting('geoxidated.editor', true), '')
+        FROM   old_rows
+        ORDER BY id;
+    ELSIF TG_OP = 'UPDATE' THEN
+        INSERT INTO geoxidated.feature_history(feature_id, operation, properties, geometry, changed_by)
+        SELECT new_rows.id,
+               CASE WHEN new_rows.deleted_at IS NOT NULL THEN 'delete'
+                    WHEN old_rows.deleted_at IS NOT NULL THEN 'restore'
+                    ELSE 'update' END,
+               new_rows.properties, new_rows.geometry, NULLIF(current_setting('geoxidated.editor', true), '')
+        FROM   new_rows
+        INNER JOIN old_rows ON old_rows.id = new_rows.id
+        ORDER BY new_rows.id;
+    ELSE
+        INSERT INTO geoxidated.feature_history(feature_id, operation, properties, geometry, changed_by)
+        SELECT id, 'create', properties, geometry, NULLIF(current_setting('geoxidated.editor', true), '')
+        FROM   new_rows
+        ORDER BY id;
+    END IF;
+    RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
+
+DROP TRIGGER IF EXISTS feature_updated ON geoxidated.feature;
+CREATE TRIGGER feature_updated AFTER UPDATE ON geoxidated.feature
+    REFERENCING OLD TABLE AS old_rows NEW TABLE AS new_rows
+    FOR EACH STATEMENT EXECUTE FUNCTION geoxidated.record_feature_revision();
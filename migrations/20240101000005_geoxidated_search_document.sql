@@ -0,0 +1,6 @@
+-- The searchable text of a feature, kept up to date by Postgres so searches read it from the GIN index
+-- instead of building it for every row. Collections with another search_language build theirs per row.
+ALTER TABLE geoxidated.feature ADD COLUMN IF NOT EXISTS search_document TSVECTOR
+    GENERATED ALWAYS AS (jsonb_to_tsvector('simple'::regconfig, properties, '["string"]')) STORED;
+
+CREATE INDEX IF NOT EXISTS feature_search_document_idx ON geoxidated.feature USING GIN (search_document);
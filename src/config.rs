@@ -32,18 +32,70 @@ use serde::Deserialize;
 
 #[derive(Deserialize)]
 pub struct Configuration {
-    database: Database
+    database: Option<Database>,
+    storage: Option<StorageConfiguration>,
+    trash: Option<TrashConfiguration>
 }
 
+/// `migrate` runs the embedded migrations at startup, on unless set to `false`.
 #[derive(Deserialize)]
 struct Database {
     db_password: String,
     db_user: String,
+    migrate: Option<bool>
+}
+
+/// `backend` is `postgres` (the default), `sqlite`, `geopackage` or `memory`,
+/// `path` is the database file of the sqlite and geopackage backends.
+#[derive(Deserialize)]
+struct StorageConfiguration {
+    backend: String,
+    path: Option<String>
+}
+
+/// `retention_days` is how long deleted features and collections stay in the trash, 30 unless set,
+/// `admin_token` enables the purge for requests sending it as a bearer token.
+#[derive(Deserialize)]
+struct TrashConfiguration {
+    retention_days: Option<i64>,
+    admin_token: Option<String>
+}
+
+/// What the purge of the trash is allowed to remove and who may start it.
+pub struct TrashPolicy {
+    pub retention_days: i64,
+    pub admin_token: Option<String>
 }
 
 impl Configuration {
     pub fn get_database_url(&self) -> String {
-        self.database.get_url()
+        match &self.database {
+            Some(database) => database.get_url(),
+            None => panic!("The [database] section is required by the postgres backend")
+        }
+    }
+
+    pub fn get_migrate(&self) -> bool {
+        self.database.as_ref().and_then(|database| database.migrate).unwrap_or(true)
+    }
+
+    pub fn get_storage_backend(&self) -> &str {
+        self.storage.as_ref().map(|storage| storage.backend.as_str()).unwrap_or("postgres")
+    }
+
+    pub fn get_storage_path(&self) -> &str {
+        self.storage.as_ref().and_then(|storage| storage.path.as_deref()).unwrap_or("geoxidated.db")
+    }
+
+    pub fn get_trash_policy(&self) -> TrashPolicy {
+        let admin_token = env::var("ADMIN_TOKEN").ok()
+            .or_else(|| self.trash.as_ref().and_then(|trash| trash.admin_token.clone()))
+            .filter(|token| !token.is_empty());
+
+        TrashPolicy {
+            retention_days: self.trash.as_ref().and_then(|trash| trash.retention_days).unwrap_or(30),
+            admin_token
+        }
     }
 }
 
@@ -28,4 +28,9 @@
 
 pub mod feature_data;
 pub mod feature_collection_data;
-pub mod filter_data;
\ No newline at end of file
+pub mod filter_data;
+pub mod stored_filter_data;
+pub mod search_hit_data;
+pub mod geopackage_data;
+pub mod feature_revision_data;
+pub mod trash_data;
\ No newline at end of file
@@ -26,7 +26,7 @@
     DEALINGS IN THE SOFTWARE.
  */
 
-use sqlx::{postgres::PgRow, Row, Error};
+use sqlx::{postgres::PgRow, sqlite::SqliteRow, Row, Error};
 
 use crate::model::{id::Id, feature_collection::FeatureCollection, value::ObjectValue, json::Json};
 
@@ -47,4 +47,19 @@ impl From<&PgRow> for FeatureCollection {
 
         FeatureCollection::new(id, label, vec![], ObjectValue::from(Json::new(properties_str)))
     }
-}
\ No newline at end of file
+}
+
+impl From<&SqliteRow> for FeatureCollection {
+    fn from(row: &SqliteRow) -> Self {
+
+        let id: i64 = match row.try_get(0) {
+            Ok(id) => id,
+            Err(err) => panic!("Unexpected value for id {}", err)
+        };
+
+        let label: String = row.try_get(1).unwrap_or("".to_string());
+        let properties_str: String = row.try_get(2).unwrap_or("{}".to_string());
+
+        FeatureCollection::new(Id::from(id), label, vec![], ObjectValue::from(Json::new(properties_str)))
+    }
+}
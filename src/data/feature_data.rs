@@ -26,7 +26,7 @@
     DEALINGS IN THE SOFTWARE.
  */
 
-use sqlx::{postgres::PgRow, Row, Error};
+use sqlx::{postgres::PgRow, sqlite::SqliteRow, Row, Error};
 
 use crate::model::{feature::Feature, id::Id, geometry::Geometry, json::Json, value::ObjectValue};
 
@@ -45,6 +45,25 @@ impl From<&PgRow> for Feature {
         let properties_str: String = row.try_get(1).unwrap_or("{}".to_string());
         let geometry_str: String = row.try_get(2).unwrap_or("".to_string());
 
-        Feature::new(id, Geometry::from(Json::new(geometry_str)), ObjectValue::from(Json::new(properties_str)))
+        let mut feature = Feature::new(id, Geometry::from(Json::new(geometry_str)), ObjectValue::from(Json::new(properties_str)));
+        feature.version = row.try_get(3).ok();
+        feature
     }
-}
\ No newline at end of file
+}
+
+impl From<&SqliteRow> for Feature {
+    fn from(row: &SqliteRow) -> Self {
+
+        let id: i64 = match row.try_get(0) {
+            Ok(id) => id,
+            Err(err) => panic!("Unexpected value for id {}", err)
+        };
+
+        let properties_str: String = row.try_get(1).unwrap_or("{}".to_string());
+        let geometry_str: String = row.try_get(2).unwrap_or("".to_string());
+
+        let mut feature = Feature::new(Id::from(id), Geometry::from(Json::new(geometry_str)), ObjectValue::from(Json::new(properties_str)));
+        feature.version = row.try_get(3).ok();
+        feature
+    }
+}
@@ -26,37 +26,342 @@
     DEALINGS IN THE SOFTWARE.
  */
 
-use crate::model::filter::{Filter, Expression, Equals, Element};
+use sqlx::{QueryBuilder, Postgres};
 
-pub trait IntoSQLQuery {
-    fn into_sql_query(&self) -> String;
+use crate::model::filter::{Filter, Expression, Equals, NotEquals, LessThan, LessThanOrEquals, GreaterThan,
+    GreaterThanOrEquals, IsNull, Element, ElementType, Like, ILike, StartsWith, And, Or, Not,
+    GeometryOperand, Intersects, Within, Contains, Disjoint, DWithin, ContainsValue, ContainedBy, AnyOf,
+    ArrayLength, LengthComparison};
+
+/// Compiles a filter into a SQL condition, the fields and values are always sent as
+/// bind parameters so nothing coming from the request is spliced into the SQL text.
+pub trait ToSQLQuery {
+    fn to_sql_query(&self, query: &mut QueryBuilder<'_, Postgres>);
+}
+
+impl ToSQLQuery for Filter {
+    fn to_sql_query(&self, query: &mut QueryBuilder<'_, Postgres>) {
+        self.expressions.to_sql_query(query)
+    }
+}
+
+impl ToSQLQuery for Expression {
+    fn to_sql_query(&self, query: &mut QueryBuilder<'_, Postgres>) {
+        match self {
+            Expression::Equals(exp) => exp.to_sql_query(query),
+            Expression::NotEquals(exp) => exp.to_sql_query(query),
+            Expression::LessThan(exp) => exp.to_sql_query(query),
+            Expression::LessThanOrEquals(exp) => exp.to_sql_query(query),
+            Expression::GreaterThan(exp) => exp.to_sql_query(query),
+            Expression::GreaterThanOrEquals(exp) => exp.to_sql_query(query),
+            Expression::IsNull(exp) => exp.to_sql_query(query),
+            Expression::Like(exp) => exp.to_sql_query(query),
+            Expression::ILike(exp) => exp.to_sql_query(query),
+            Expression::StartsWith(exp) => exp.to_sql_query(query),
+            Expression::Intersects(exp) => exp.to_sql_query(query),
+            Expression::Within(exp) => exp.to_sql_query(query),
+            Expression::Contains(exp) => exp.to_sql_query(query),
+            Expression::Disjoint(exp) => exp.to_sql_query(query),
+            Expression::DWithin(exp) => exp.to_sql_query(query),
+            Expression::ContainsValue(exp) => exp.to_sql_query(query),
+            Expression::ContainedBy(exp) => exp.to_sql_query(query),
+            Expression::AnyOf(exp) => exp.to_sql_query(query),
+            Expression::ArrayLength(exp) => exp.to_sql_query(query),
+            Expression::And(exp) => exp.to_sql_query(query),
+            Expression::Or(exp) => exp.to_sql_query(query),
+            Expression::Not(exp) => exp.to_sql_query(query),
+        }
+    }
+}
+
+impl ToSQLQuery for Equals {
+    fn to_sql_query(&self, query: &mut QueryBuilder<'_, Postgres>) {
+        comparison(query, &self.field, "=", &self.value)
+    }
+}
+
+impl ToSQLQuery for NotEquals {
+    fn to_sql_query(&self, query: &mut QueryBuilder<'_, Postgres>) {
+        comparison(query, &self.field, "<>", &self.value)
+    }
+}
+
+impl ToSQLQuery for LessThan {
+    fn to_sql_query(&self, query: &mut QueryBuilder<'_, Postgres>) {
+        comparison(query, &self.field, "<", &self.value)
+    }
+}
+
+impl ToSQLQuery for LessThanOrEquals {
+    fn to_sql_query(&self, query: &mut QueryBuilder<'_, Postgres>) {
+        comparison(query, &self.field, "<=", &self.value)
+    }
+}
+
+impl ToSQLQuery for GreaterThan {
+    fn to_sql_query(&self, query: &mut QueryBuilder<'_, Postgres>) {
+        comparison(query, &self.field, ">", &self.value)
+    }
+}
+
+impl ToSQLQuery for GreaterThanOrEquals {
+    fn to_sql_query(&self, query: &mut QueryBuilder<'_, Postgres>) {
+        comparison(query, &self.field, ">=", &self.value)
+    }
+}
+
+impl ToSQLQuery for IsNull {
+    fn to_sql_query(&self, query: &mut QueryBuilder<'_, Postgres>) {
+        property_text(query, &self.field);
+        query.push(" IS NULL");
+    }
+}
+
+impl ToSQLQuery for Like {
+    fn to_sql_query(&self, query: &mut QueryBuilder<'_, Postgres>) {
+        like(query, &self.field, "LIKE", like_pattern(&self.pattern))
+    }
+}
+
+impl ToSQLQuery for ILike {
+    fn to_sql_query(&self, query: &mut QueryBuilder<'_, Postgres>) {
+        like(query, &self.field, "ILIKE", like_pattern(&self.pattern))
+    }
+}
+
+impl ToSQLQuery for StartsWith {
+    fn to_sql_query(&self, query: &mut QueryBuilder<'_, Postgres>) {
+        like(query, &self.field, "LIKE", format!("{}%", escape_like(&self.value)))
+    }
+}
+
+impl ToSQLQuery for Intersects {
+    fn to_sql_query(&self, query: &mut QueryBuilder<'_, Postgres>) {
+        spatial(query, "ST_Intersects", &self.geometry)
+    }
+}
+
+impl ToSQLQuery for Within {
+    fn to_sql_query(&self, query: &mut QueryBuilder<'_, Postgres>) {
+        spatial(query, "ST_Within", &self.geometry)
+    }
+}
+
+impl ToSQLQuery for Contains {
+    fn to_sql_query(&self, query: &mut QueryBuilder<'_, Postgres>) {
+        spatial(query, "ST_Contains", &self.geometry)
+    }
+}
+
+impl ToSQLQuery for Disjoint {
+    fn to_sql_query(&self, query: &mut QueryBuilder<'_, Postgres>) {
+        spatial(query, "ST_Disjoint", &self.geometry)
+    }
+}
+
+impl ToSQLQuery for DWithin {
+    fn to_sql_query(&self, query: &mut QueryBuilder<'_, Postgres>) {
+        query.push("ST_DWithin(geometry, ");
+        self.geometry.to_sql_query(query);
+        query.push(", ").push_bind(self.distance).push(")");
+    }
+}
+
+impl ToSQLQuery for GeometryOperand {
+    fn to_sql_query(&self, query: &mut QueryBuilder<'_, Postgres>) {
+        query.push("ST_SetSRID(ST_GeomFromGeoJSON(").push_bind(self.0.clone()).push("), 4326)");
+    }
 }
 
-impl IntoSQLQuery for Filter {
-    fn into_sql_query(&self) -> String {
+impl ToSQLQuery for ContainsValue {
+    fn to_sql_query(&self, query: &mut QueryBuilder<'_, Postgres>) {
+        property_json(query, &self.field);
+        query.push(" @> ");
+        json_value(query, self.value.to_json());
+    }
+}
+
+impl ToSQLQuery for ContainedBy {
+    fn to_sql_query(&self, query: &mut QueryBuilder<'_, Postgres>) {
+        property_json(query, &self.field);
+        query.push(" <@ ");
+        json_value(query, self.value.to_json());
+    }
+}
+
+//one containment check per value, so the GIN index on properties can still be used
+impl ToSQLQuery for AnyOf {
+    fn to_sql_query(&self, query: &mut QueryBuilder<'_, Postgres>) {
+        if self.values.is_empty() {
+            query.push("FALSE");
+            return;
+        }
 
-        let sql_query = match &self.expressions {
-            crate::model::filter::Expression::Equals(exp) => exp.into_sql_query(),
-            crate::model::filter::Expression::NotEquals(exp) => todo!(),
-            crate::model::filter::Expression::And(exp) => todo!(),
-            crate::model::filter::Expression::Or(exp) => todo!(),
+        for (index, value) in self.values.iter().enumerate() {
+            if index > 0 {
+                query.push(" OR ");
+            }
+
+            property_json(query, &self.field);
+            query.push(" @> ");
+            json_value(query, format!("[{}]", value.to_json()));
+        }
+    }
+}
+
+impl ToSQLQuery for ArrayLength {
+    fn to_sql_query(&self, query: &mut QueryBuilder<'_, Postgres>) {
+        let operator = match self.comparison {
+            LengthComparison::Equals => "=",
+            LengthComparison::NotEquals => "<>",
+            LengthComparison::LessThan => "<",
+            LengthComparison::LessThanOrEquals => "<=",
+            LengthComparison::GreaterThan => ">",
+            LengthComparison::GreaterThanOrEquals => ">=",
         };
 
-        format!("{}", sql_query)
+        query.push("CASE WHEN jsonb_typeof(");
+        property_json(query, &self.field);
+        query.push(") = 'array' THEN jsonb_array_length(");
+        property_json(query, &self.field);
+        query.push(format!(") END {operator} ")).push_bind(self.length);
+    }
+}
+
+impl ToSQLQuery for And {
+    fn to_sql_query(&self, query: &mut QueryBuilder<'_, Postgres>) {
+        join_expressions(query, &self.0, " AND ")
+    }
+}
+
+impl ToSQLQuery for Or {
+    fn to_sql_query(&self, query: &mut QueryBuilder<'_, Postgres>) {
+        join_expressions(query, &self.0, " OR ")
     }
 }
 
-impl IntoSQLQuery for Equals {
-    fn into_sql_query(&self) -> String {
-        format!("{} = {}", self.field, self.value.into_sql_query())
+impl ToSQLQuery for Not {
+    fn to_sql_query(&self, query: &mut QueryBuilder<'_, Postgres>) {
+        query.push("NOT (");
+        self.0.to_sql_query(query);
+        query.push(")");
     }
 }
 
-impl IntoSQLQuery for Element {
-    fn into_sql_query(&self) -> String {
+//numbers are compared as numeric so that 10 and 10.0 are the same value
+impl ToSQLQuery for Element {
+    fn to_sql_query(&self, query: &mut QueryBuilder<'_, Postgres>) {
         match self.e_type {
-            crate::model::filter::ElementType::String => format!(r#"'{}'"#, self.value),
-            crate::model::filter::ElementType::Number => format!("{}", self.value),
+            ElementType::String => { query.push_bind(self.value.clone()); },
+            ElementType::Number => { query.push("CAST(").push_bind(self.value.clone()).push(" AS numeric)"); },
+            ElementType::Boolean | ElementType::Array | ElementType::Object => json_value(query, self.to_json()),
+            //the repository refuses filters with unbound parameters before compiling them
+            ElementType::Parameter => { query.push("NULL"); },
+        }
+    }
+}
+
+fn comparison(query: &mut QueryBuilder<'_, Postgres>, field: &str, operator: &str, value: &Element) {
+    match value.e_type {
+        ElementType::String => property_text(query, field),
+        //properties holding anything but a number are NULL instead of failing the cast
+        ElementType::Number => {
+            query.push("CASE WHEN jsonb_typeof(");
+            property_json(query, field);
+            query.push(") = 'number' THEN CAST(");
+            property_text(query, field);
+            query.push(" AS numeric) END");
+        },
+        ElementType::Boolean | ElementType::Array | ElementType::Object | ElementType::Parameter => property_json(query, field),
+    }
+
+    query.push(format!(" {operator} "));
+    value.to_sql_query(query);
+}
+
+fn like(query: &mut QueryBuilder<'_, Postgres>, field: &str, operator: &str, pattern: String) {
+    property_text(query, field);
+    query.push(format!(" {operator} ")).push_bind(pattern).push(r" ESCAPE '\'");
+}
+
+fn spatial(query: &mut QueryBuilder<'_, Postgres>, function: &str, geometry: &GeometryOperand) {
+    query.push(format!("{function}(geometry, "));
+    geometry.to_sql_query(query);
+    query.push(")");
+}
+
+fn join_expressions(query: &mut QueryBuilder<'_, Postgres>, expressions: &[Expression], separator: &str) {
+    for (index, expression) in expressions.iter().enumerate() {
+        if index > 0 {
+            query.push(separator);
+        }
+
+        query.push("(");
+        expression.to_sql_query(query);
+        query.push(")");
+    }
+}
+
+fn property_text(query: &mut QueryBuilder<'_, Postgres>, field: &str) {
+    query.push("properties->>").push_bind(field.to_string());
+}
+
+fn property_json(query: &mut QueryBuilder<'_, Postgres>, field: &str) {
+    query.push("properties->").push_bind(field.to_string());
+}
+
+fn json_value(query: &mut QueryBuilder<'_, Postgres>, json: String) {
+    query.push("CAST(").push_bind(json).push(" AS jsonb)");
+}
+
+pub(crate) fn escape_like(value: &str) -> String {
+    value.replace('\\', r"\\").replace('%', r"\%").replace('_', r"\_")
+}
+
+//translates the filter wildcards (*, ?) into the LIKE ones, everything else is taken literally
+fn like_pattern(pattern: &str) -> String {
+    let mut translated = String::with_capacity(pattern.len());
+    let mut chars = pattern.chars();
+
+    while let Some(current) = chars.next() {
+        match current {
+            '*' => translated.push('%'),
+            '?' => translated.push('_'),
+            '\\' => {
+                if let Some(escaped) = chars.next() {
+                    translated.push_str(&escape_like(&escaped.to_string()));
+                }
+            },
+            other => translated.push_str(&escape_like(&other.to_string())),
         }
     }
-}
\ No newline at end of file
+
+    translated
+}
+
+#[cfg(test)]
+mod tests {
+    use sqlx::{QueryBuilder, Postgres};
+
+    use crate::model::filter::cql2_text;
+
+    use super::ToSQLQuery;
+
+    fn sql(text: &str) -> String {
+        let mut query: QueryBuilder<'_, Postgres> = QueryBuilder::new("");
+        cql2_text::parse(text).unwrap().to_sql_query(&mut query);
+        query.sql().to_string()
+    }
+
+    #[test]
+    fn casts_only_number_properties() {
+        assert_eq!(sql("population > 1000"),
+            "CASE WHEN jsonb_typeof(properties->$1) = 'number' THEN CAST(properties->>$2 AS numeric) END > CAST($3 AS numeric)");
+    }
+
+    #[test]
+    fn binds_every_value() {
+        assert_eq!(sql("name = 'Salvador' OR name LIKE 'S%'"),
+            r"(properties->>$1 = $2) OR (properties->>$3 LIKE $4 ESCAPE '\')");
+    }
+}
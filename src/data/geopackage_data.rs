@@ -0,0 +1,305 @@
+/*
+    Copyright (c)  Abraão Isvi <avraamisvi@users.noreply.github.com>
+
+    Permission is hereby granted, free of charge, to any
+    person obtaining a copy of this software and associated
+    documentation files (the "Software"), to deal in the
+    Software without restriction, including without
+    limitation the rights to use, copy, modify, merge,
+    publish, distribute, sublicense, and/or sell copies of
+    the Software, and to permit persons to whom the Software
+    is furnished to do so, subject to the following
+    conditions:
+
+    The above copyright notice and this permission notice
+    shall be included in all copies or substantial portions
+    of the Software.
+
+    THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF
+    ANY KIND, EXPRESS OR IMPLIED, INCLUDING BUT NOT LIMITED
+    TO THE WARRANTIES OF MERCHANTABILITY, FITNESS FOR A
+    PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT
+    SHALL THE AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY
+    CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER IN AN ACTION
+    OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR
+    IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+    DEALINGS IN THE SOFTWARE.
+ */
+
+/*!
+ * Conversions between GeoJSON geometries and the GeoPackage binary encoding,
+ * a `GP` header with the SRS and envelope followed by ISO WKB.
+ */
+
+use std::fmt::Display;
+
+use serde_json::{json, Value};
+
+#[derive(Debug)]
+pub struct GeometryEncodingError {
+    pub message: String
+}
+
+impl Display for GeometryEncodingError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Invalid geometry {}", self.message)
+    }
+}
+
+static GEOMETRY_TYPES: [&str; 7] = ["Point", "LineString", "Polygon", "MultiPoint", "MultiLineString",
+    "MultiPolygon", "GeometryCollection"];
+
+/// Encodes a GeoJSON geometry, little endian with an xy envelope.
+pub fn to_geopackage_binary(geometry: &Value, srs_id: i32) -> Result<Vec<u8>, GeometryEncodingError> {
+    let mut bounds = [f64::INFINITY, f64::NEG_INFINITY, f64::INFINITY, f64::NEG_INFINITY];
+    let mut wkb = vec![];
+    write_geometry(geometry, &mut wkb, &mut bounds)?;
+
+    let mut binary = vec![b'G', b'P', 0];
+
+    if bounds[0] > bounds[1] {
+        //empty geometry, no envelope
+        binary.push(0b0001_0001);
+        binary.extend_from_slice(&srs_id.to_le_bytes());
+    } else {
+        binary.push(0b0000_0011);
+        binary.extend_from_slice(&srs_id.to_le_bytes());
+        bounds.iter().for_each(|bound| binary.extend_from_slice(&bound.to_le_bytes()));
+    }
+
+    binary.extend(wkb);
+    Ok(binary)
+}
+
+/// Decodes a GeoPackage geometry into GeoJSON, Z and M values are dropped.
+pub fn from_geopackage_binary(binary: &[u8]) -> Result<Value, GeometryEncodingError> {
+    if binary.len() < 8 || &binary[0..2] != b"GP" {
+        return Err(error("the GeoPackage header is missing"));
+    }
+
+    let flags = binary[3];
+    let envelope = match (flags >> 1) & 0b111 {
+        0 => 0,
+        1 => 32,
+        2 | 3 => 48,
+        4 => 64,
+        _ => return Err(error("the envelope indicator is invalid"))
+    };
+
+    let mut reader = WkbReader { binary, position: 8 + envelope, little_endian: true };
+    reader.read_geometry()
+}
+
+fn write_geometry(geometry: &Value, wkb: &mut Vec<u8>, bounds: &mut [f64; 4]) -> Result<(), GeometryEncodingError> {
+    let geometry_type = geometry["type"].as_str().ok_or_else(|| error("the type is missing"))?;
+    let code = GEOMETRY_TYPES.iter().position(|known| *known == geometry_type)
+        .ok_or_else(|| error(&format!("the type {geometry_type} is unknown")))?;
+
+    wkb.push(1);
+    wkb.extend_from_slice(&(code as u32 + 1).to_le_bytes());
+
+    let coordinates = &geometry["coordinates"];
+    match geometry_type {
+        "Point" => write_position(coordinates, wkb, bounds),
+        "LineString" => write_positions(coordinates, wkb, bounds),
+        "Polygon" => write_rings(coordinates, wkb, bounds),
+        "GeometryCollection" => {
+            let geometries = geometry["geometries"].as_array().ok_or_else(|| error("the geometries are missing"))?;
+            wkb.extend_from_slice(&(geometries.len() as u32).to_le_bytes());
+            geometries.iter().try_for_each(|inner| write_geometry(inner, wkb, bounds))
+        },
+        _ => {
+            //multi geometries hold one full WKB geometry per part
+            let part_type = &geometry_type[5..];
+            let parts = coordinates.as_array().ok_or_else(|| error("the coordinates must be an array"))?;
+            wkb.extend_from_slice(&(parts.len() as u32).to_le_bytes());
+            parts.iter().try_for_each(|part| write_geometry(&json!({"type": part_type, "coordinates": part}), wkb, bounds))
+        }
+    }
+}
+
+fn write_position(position: &Value, wkb: &mut Vec<u8>, bounds: &mut [f64; 4]) -> Result<(), GeometryEncodingError> {
+    let x = position[0].as_f64().ok_or_else(|| error("a position must have numbers"))?;
+    let y = position[1].as_f64().ok_or_else(|| error("a position must have numbers"))?;
+
+    bounds[0] = bounds[0].min(x);
+    bounds[1] = bounds[1].max(x);
+    bounds[2] = bounds[2].min(y);
+    bounds[3] = bounds[3].max(y);
+
+    wkb.extend_from_slice(&x.to_le_bytes());
+    wkb.extend_from_slice(&y.to_le_bytes());
+    Ok(())
+}
+
+fn write_positions(positions: &Value, wkb: &mut Vec<u8>, bounds: &mut [f64; 4]) -> Result<(), GeometryEncodingError> {
+    let positions = positions.as_array().ok_or_else(|| error("the coordinates must be an array"))?;
+    wkb.extend_from_slice(&(positions.len() as u32).to_le_bytes());
+    positions.iter().try_for_each(|position| write_position(position, wkb, bounds))
+}
+
+fn write_rings(rings: &Value, wkb: &mut Vec<u8>, bounds: &mut [f64; 4]) -> Result<(), GeometryEncodingError> {
+    let rings = rings.as_array().ok_or_else(|| error("the coordinates must be an array"))?;
+    wkb.extend_from_slice(&(rings.len() as u32).to_le_bytes());
+    rings.iter().try_for_each(|ring| write_positions(ring, wkb, bounds))
+}
+
+struct WkbReader<'a> {
+    binary: &'a [u8],
+    position: usize,
+    little_endian: bool
+}
+
+impl WkbReader<'_> {
+    fn read_geometry(&mut self) -> Result<Value, GeometryEncodingError> {
+        self.little_endian = self.read_bytes::<1>()?[0] == 1;
+        let code = self.read_u32()?;
+
+        //ISO WKB adds 1000 for Z, 2000 for M and 3000 for ZM
+        let dimensions = match code / 1000 {
+            0 => 2,
+            1 | 2 => 3,
+            3 => 4,
+            _ => return Err(error(&format!("the WKB type {code} is not supported")))
+        };
+
+        let geometry_type = *((code % 1000) as usize).checked_sub(1).and_then(|index| GEOMETRY_TYPES.get(index))
+            .ok_or_else(|| error(&format!("the WKB type {code} is not supported")))?;
+
+        let coordinates = match geometry_type {
+            "Point" => self.read_position(dimensions)?,
+            "LineString" => self.read_positions(dimensions)?,
+            "Polygon" => {
+                let count = self.read_u32()?;
+                Value::Array((0..count).map(|_| self.read_positions(dimensions)).collect::<Result<_, _>>()?)
+            },
+            "GeometryCollection" => {
+                let count = self.read_u32()?;
+                let geometries: Vec<Value> = (0..count).map(|_| self.read_geometry()).collect::<Result<_, _>>()?;
+                return Ok(json!({"type": geometry_type, "geometries": geometries}));
+            },
+            _ => {
+                let count = self.read_u32()?;
+                let parts: Vec<Value> = (0..count).map(|_| self.read_geometry()).collect::<Result<_, _>>()?;
+                Value::Array(parts.into_iter().map(|part| part["coordinates"].clone()).collect())
+            }
+        };
+
+        Ok(json!({"type": geometry_type, "coordinates": coordinates}))
+    }
+
+    fn read_position(&mut self, dimensions: usize) -> Result<Value, GeometryEncodingError> {
+        let x = self.read_f64()?;
+        let y = self.read_f64()?;
+
+        for _ in 2..dimensions {
+            self.read_f64()?;
+        }
+
+        Ok(json!([x, y]))
+    }
+
+    fn read_positions(&mut self, dimensions: usize) -> Result<Value, GeometryEncodingError> {
+        let count = self.read_u32()?;
+        Ok(Value::Array((0..count).map(|_| self.read_position(dimensions)).collect::<Result<_, _>>()?))
+    }
+
+    fn read_u32(&mut self) -> Result<u32, GeometryEncodingError> {
+        let bytes = self.read_bytes::<4>()?;
+        Ok(if self.little_endian { u32::from_le_bytes(bytes) } else { u32::from_be_bytes(bytes) })
+    }
+
+    fn read_f64(&mut self) -> Result<f64, GeometryEncodingError> {
+        let bytes = self.read_bytes::<8>()?;
+        Ok(if self.little_endian { f64::from_le_bytes(bytes) } else { f64::from_be_bytes(bytes) })
+    }
+
+    fn read_bytes<const N: usize>(&mut self) -> Result<[u8; N], GeometryEncodingError> {
+        let bytes = self.binary.get(self.position..self.position + N).ok_or_else(|| error("the WKB is truncated"))?;
+        self.position += N;
+        Ok(bytes.try_into().unwrap())
+    }
+}
+
+fn error(message: &str) -> GeometryEncodingError {
+    GeometryEncodingError { message: message.to_string() }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::{json, Value};
+
+    use super::{to_geopackage_binary, from_geopackage_binary};
+
+    fn f64_at(binary: &[u8], position: usize) -> f64 {
+        f64::from_le_bytes(binary[position..position + 8].try_into().unwrap())
+    }
+
+    #[test]
+    fn writes_the_header_with_srs_and_envelope() {
+        let binary = to_geopackage_binary(&json!({"type": "LineString", "coordinates": [[-38.5, -12.9], [-34.9, -8.0]]}), 4326).unwrap();
+
+        assert_eq!(&binary[0..3], b"GP\0");
+        //little endian with an xy envelope
+        assert_eq!(binary[3], 0b0000_0011);
+        assert_eq!(i32::from_le_bytes(binary[4..8].try_into().unwrap()), 4326);
+        assert_eq!([f64_at(&binary, 8), f64_at(&binary, 16), f64_at(&binary, 24), f64_at(&binary, 32)], [-38.5, -34.9, -12.9, -8.0]);
+        //little endian WKB LineString
+        assert_eq!(binary[40], 1);
+        assert_eq!(u32::from_le_bytes(binary[41..45].try_into().unwrap()), 2);
+    }
+
+    #[test]
+    fn flags_empty_geometries_without_envelope() {
+        let empty = json!({"type": "MultiPoint", "coordinates": []});
+        let binary = to_geopackage_binary(&empty, 4326).unwrap();
+
+        assert_eq!(binary[3], 0b0001_0001);
+        assert_eq!(binary.len(), 8 + 9);
+        assert_eq!(from_geopackage_binary(&binary).unwrap(), empty);
+    }
+
+    #[test]
+    fn round_trips_every_geometry_type() {
+        let geometries = [
+            json!({"type": "Point", "coordinates": [-38.5, -12.9]}),
+            json!({"type": "LineString", "coordinates": [[-38.5, -12.9], [-34.9, -8.0]]}),
+            json!({"type": "Polygon", "coordinates": [[[0.0, 0.0], [1.0, 0.0], [1.0, 1.0], [0.0, 0.0]]]}),
+            json!({"type": "MultiPoint", "coordinates": [[-38.5, -12.9], [-34.9, -8.0]]}),
+            json!({"type": "MultiLineString", "coordinates": [[[0.0, 0.0], [1.0, 1.0]], [[2.0, 2.0], [3.0, 3.0]]]}),
+            json!({"type": "MultiPolygon", "coordinates": [[[[0.0, 0.0], [1.0, 0.0], [1.0, 1.0], [0.0, 0.0]]]]}),
+            json!({"type": "GeometryCollection", "geometries": [
+                {"type": "Point", "coordinates": [1.5, 2.5]},
+                {"type": "LineString", "coordinates": [[0.0, 0.0], [1.0, 1.0]]}
+            ]})
+        ];
+
+        for geometry in geometries {
+            assert_eq!(from_geopackage_binary(&to_geopackage_binary(&geometry, 4326).unwrap()).unwrap(), geometry);
+        }
+    }
+
+    #[test]
+    fn reads_big_endian_wkb_and_drops_z() {
+        //big endian header with an xyz envelope and a big endian ISO WKB Point Z
+        let mut binary = vec![b'G', b'P', 0, 0b0000_0100];
+        binary.extend_from_slice(&4326i32.to_be_bytes());
+        [-38.5f64, -38.5, -12.9, -12.9, 8.0, 8.0].iter().for_each(|bound| binary.extend_from_slice(&bound.to_be_bytes()));
+        binary.push(0);
+        binary.extend_from_slice(&1001u32.to_be_bytes());
+        [-38.5f64, -12.9, 8.0].iter().for_each(|ordinate| binary.extend_from_slice(&ordinate.to_be_bytes()));
+
+        let geometry: Value = from_geopackage_binary(&binary).unwrap();
+
+        assert_eq!(geometry, json!({"type": "Point", "coordinates": [-38.5, -12.9]}));
+    }
+
+    #[test]
+    fn rejects_what_is_not_a_geopackage_geometry() {
+        let point = to_geopackage_binary(&json!({"type": "Point", "coordinates": [1.0, 2.0]}), 4326).unwrap();
+
+        assert!(from_geopackage_binary(&point[8 + 32..]).is_err());
+        assert!(from_geopackage_binary(&point[..point.len() - 1]).is_err());
+        assert!(to_geopackage_binary(&json!({"type": "Circle", "coordinates": [1.0, 2.0]}), 4326).is_err());
+    }
+}
@@ -0,0 +1,46 @@
+/*
+    Copyright (c)  Abraão Isvi <avraamisvi@users.noreply.github.com>
+
+    Permission is hereby granted, free of charge, to any
+    person obtaining a copy of this software and associated
+    documentation files (the "Software"), to deal in the
+    Software without restriction, including without
+    limitation the rights to use, copy, modify, merge,
+    publish, distribute, sublicense, and/or sell copies of
+    the Software, and to permit persons to whom the Software
+    is furnished to do so, subject to the following
+    conditions:
+
+    The above copyright notice and this permission notice
+    shall be included in all copies or substantial portions
+    of the Software.
+
+    THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF
+    ANY KIND, EXPRESS OR IMPLIED, INCLUDING BUT NOT LIMITED
+    TO THE WARRANTIES OF MERCHANTABILITY, FITNESS FOR A
+    PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT
+    SHALL THE AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY
+    CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER IN AN ACTION
+    OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR
+    IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+    DEALINGS IN THE SOFTWARE.
+ */
+
+use sqlx::{postgres::PgRow, sqlite::SqliteRow, Row};
+
+use crate::model::trash::TrashedItem;
+
+//kind, id, label, collection_id, deleted_at
+impl From<&PgRow> for TrashedItem {
+    fn from(row: &PgRow) -> Self {
+        TrashedItem::new(row.try_get(0).unwrap_or_default(), row.try_get(1).unwrap_or_default(),
+            row.try_get(2).unwrap_or(None), row.try_get(3).unwrap_or(None), row.try_get(4).unwrap_or_default())
+    }
+}
+
+impl From<&SqliteRow> for TrashedItem {
+    fn from(row: &SqliteRow) -> Self {
+        TrashedItem::new(row.try_get(0).unwrap_or_default(), row.try_get(1).unwrap_or_default(),
+            row.try_get(2).unwrap_or(None), row.try_get(3).unwrap_or(None), row.try_get(4).unwrap_or_default())
+    }
+}
@@ -28,14 +28,19 @@
 
 #[macro_use] extern crate rocket;
 
-use config::read_config;
+use config::{read_config, Configuration, TrashPolicy};
+use repository::{storage::Storage, memory_repository::MemoryStore, sqlite_repository, geopackage_repository};
 use futures::executor;
 use rocket_cors::{AllowedOrigins, AllowedHeaders, Method};
 use routes::{post_collections, get_collections, get_collections_feature, 
-    get_collections_features, post_feature, put_collections, options_collections,
-    get_features_by_bbox, put_feature};
-use sqlx::{postgres::PgPoolOptions, Pool, Postgres, Error};
-
+    get_collections_features, post_feature, post_features, import_features, put_collections, options_collections,
+    get_features_by_bbox, put_feature, filter_feature, get_conformance, put_filter, get_filters, get_filter,
+    delete_filter, get_collection_geopackage, delete_feature, delete_collection, get_collection,
+    get_feature_collections, attach_feature, detach_feature, get_feature_history, get_trash, restore_feature,
+    restore_collection, purge_trash, not_found};
+use sqlx::{postgres::PgPoolOptions, sqlite::{SqliteConnectOptions, SqlitePoolOptions}, Pool, Postgres, Sqlite, Error};
+
+use rocket::{Build, Rocket};
 use rocket::http::Header;
 use rocket::{Request, Response};
 use rocket::fairing::{Fairing, Info, Kind};
@@ -62,31 +67,101 @@ fn rocket() -> _ {
     // }
     // .to_cors().unwrap();
 
-    let pool: Pool<Postgres> = executor::block_on(async {
-        create_pool().await
-    }).unwrap();
+    let configuration = read_config().unwrap();
+    let storage = create_storage(&configuration);
 
+    server(storage, configuration.get_trash_policy())
+}
+
+/// The routes over `storage`, without reading `Config.toml`, so the tests can run it on the in-memory backend.
+fn server(storage: Storage, trash_policy: TrashPolicy) -> Rocket<Build> {
     rocket::build()
-    .manage(pool)
-    .mount("/", routes![options_collections,
+    .manage(storage)
+    .manage(trash_policy)
+    .mount("/", routes![get_conformance,
+                        options_collections,
                         post_collections, 
                         post_feature,
+                        post_features,
+                        import_features,
                         put_collections,
                         put_feature,
                         get_collections_feature,
                         get_features_by_bbox,
                         get_collections, 
-                        get_collections_features])
+                        get_collections_features,
+                        filter_feature,
+                        put_filter,
+                        get_filters,
+                        get_filter,
+                        delete_filter,
+                        get_collection_geopackage,
+                        delete_feature,
+                        delete_collection,
+                        get_collection,
+                        get_feature_collections,
+                        attach_feature,
+                        detach_feature,
+                        get_feature_history,
+                        get_trash,
+                        restore_feature,
+                        restore_collection,
+                        purge_trash])
+    .register("/", catchers![not_found])
 }
 
-async fn create_pool() -> Result<Pool<Postgres>, Error> {
-    let configuration = read_config().unwrap();
+fn create_storage(configuration: &Configuration) -> Storage {
+
+    match configuration.get_storage_backend() {
+        "memory" => Storage::Memory(MemoryStore::default()),
+        "sqlite" => {
+            let pool: Pool<Sqlite> = executor::block_on(async {
+                let pool = create_sqlite_pool(configuration).await?;
+                sqlite_repository::create_schema(&pool).await
+                    .map_err(|err| Error::Configuration(err.message.into()))?;
+                Ok::<Pool<Sqlite>, Error>(pool)
+            }).unwrap();
+
+            Storage::Sqlite(pool)
+        },
+        "geopackage" => {
+            let pool = executor::block_on(async {
+                geopackage_repository::open_geopackage(configuration.get_storage_path()).await
+            }).unwrap();
+
+            Storage::GeoPackage(pool)
+        },
+        "postgres" => {
+            let pool: Pool<Postgres> = executor::block_on(async {
+                let pool = create_pool(configuration).await?;
+                if configuration.get_migrate() {
+                    sqlx::migrate!().run(&pool).await?;
+                }
+                Ok::<Pool<Postgres>, Error>(pool)
+            }).unwrap();
+
+            Storage::Postgres(pool)
+        },
+        other => panic!("Unknown storage backend {other}")
+    }
+}
 
+async fn create_pool(configuration: &Configuration) -> Result<Pool<Postgres>, Error> {
     PgPoolOptions::new()
     .max_connections(5)
     .connect(&configuration.get_database_url()).await
 }
 
+async fn create_sqlite_pool(configuration: &Configuration) -> Result<Pool<Sqlite>, Error> {
+    let options = SqliteConnectOptions::new()
+    .filename(configuration.get_storage_path())
+    .create_if_missing(true);
+
+    SqlitePoolOptions::new()
+    .max_connections(5)
+    .connect_with(options).await
+}
+
 // pub struct CORS;
 
 // #[rocket::async_trait]
@@ -33,4 +33,13 @@ pub mod json;
 pub mod feature;
 pub mod id;
 pub mod bbox;
-pub mod filter;
\ No newline at end of file
+pub mod filter;
+pub mod conformance;
+pub mod stored_filter;
+pub mod collection_search;
+pub mod search_hit;
+pub mod cursor;
+pub mod paging;
+pub mod instant;
+pub mod feature_revision;
+pub mod trash;
\ No newline at end of file
@@ -44,4 +44,23 @@ impl Bbox {
                                                                 self.max_lng, self.min_lat,
                                                                 self.min_lng, self.min_lat)
     }
+
+    /// `[min_lng, min_lat, max_lng, max_lat]`
+    pub fn bounds(&self) -> [f64; 4] {
+        [self.min_lng, self.min_lat, self.max_lng, self.max_lat]
+    }
+
+    pub fn contains(&self, longitude: f64, latitude: f64) -> bool {
+        longitude >= self.min_lng && longitude <= self.max_lng
+            && latitude >= self.min_lat && latitude <= self.max_lat
+    }
+
+    pub fn to_geo_json(&self) -> String {
+        format!(r#"{{"type": "Polygon", "coordinates": [[[{}, {}], [{}, {}], [{}, {}], [{}, {}], [{}, {}]]]}}"#,
+                                                                self.min_lng, self.min_lat,
+                                                                self.min_lng, self.max_lat,
+                                                                self.max_lng, self.max_lat,
+                                                                self.max_lng, self.min_lat,
+                                                                self.min_lng, self.min_lat)
+    }
 }
\ No newline at end of file
@@ -0,0 +1,48 @@
+/*
+    Copyright (c)  Abraão Isvi <avraamisvi@users.noreply.github.com>
+
+    Permission is hereby granted, free of charge, to any
+    person obtaining a copy of this software and associated
+    documentation files (the "Software"), to deal in the
+    Software without restriction, including without
+    limitation the rights to use, copy, modify, merge,
+    publish, distribute, sublicense, and/or sell copies of
+    the Software, and to permit persons to whom the Software
+    is furnished to do so, subject to the following
+    conditions:
+
+    The above copyright notice and this permission notice
+    shall be included in all copies or substantial portions
+    of the Software.
+
+    THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF
+    ANY KIND, EXPRESS OR IMPLIED, INCLUDING BUT NOT LIMITED
+    TO THE WARRANTIES OF MERCHANTABILITY, FITNESS FOR A
+    PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT
+    SHALL THE AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY
+    CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER IN AN ACTION
+    OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR
+    IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+    DEALINGS IN THE SOFTWARE.
+ */
+
+use derive_new::new;
+
+use super::bbox::Bbox;
+use super::filter::Expression;
+
+/// Criteria to look up collections, every criterion set must match.
+/// `label` is matched as a case insensitive substring, `expression` runs against the
+/// collection properties and `bbox` keeps collections with at least one feature inside it.
+#[derive(new)]
+pub struct CollectionSearch {
+    pub label: Option<String>,
+    pub expression: Option<Expression>,
+    pub bbox: Option<Bbox>
+}
+
+impl CollectionSearch {
+    pub fn is_empty(&self) -> bool {
+        self.label.is_none() && self.expression.is_none() && self.bbox.is_none()
+    }
+}
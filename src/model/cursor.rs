@@ -0,0 +1,81 @@
+/*
+    Copyright (c)  Abraão Isvi <avraamisvi@users.noreply.github.com>
+
+    Permission is hereby granted, free of charge, to any
+    person obtaining a copy of this software and associated
+    documentation files (the "Software"), to deal in the
+    Software without restriction, including without
+    limitation the rights to use, copy, modify, merge,
+    publish, distribute, sublicense, and/or sell copies of
+    the Software, and to permit persons to whom the Software
+    is furnished to do so, subject to the following
+    conditions:
+
+    The above copyright notice and this permission notice
+    shall be included in all copies or substantial portions
+    of the Software.
+
+    THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF
+    ANY KIND, EXPRESS OR IMPLIED, INCLUDING BUT NOT LIMITED
+    TO THE WARRANTIES OF MERCHANTABILITY, FITNESS FOR A
+    PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT
+    SHALL THE AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY
+    CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER IN AN ACTION
+    OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR
+    IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+    DEALINGS IN THE SOFTWARE.
+ */
+
+use derive_new::new;
+use serde_json::{json, Value};
+
+/// Position of a keyset page, the next page holds the ids greater than `after`.
+/// Clients only see it as an opaque token.
+#[derive(new, Clone)]
+pub struct Cursor {
+    pub after: i64
+}
+
+impl Cursor {
+    /// The first page, before every id.
+    pub fn start() -> Self {
+        Cursor::new(i64::MIN)
+    }
+
+    /// Cursor for the page following one that returned `returned` items ending at `last_id`,
+    /// none when the page was not full.
+    pub fn following(last_id: Option<i64>, returned: usize, size: i64) -> Option<Cursor> {
+        match last_id {
+            Some(last_id) if size > 0 && returned as i64 >= size => Some(Cursor::new(last_id)),
+            _ => None
+        }
+    }
+
+    pub fn to_token(&self) -> String {
+        json!({"after": self.after}).to_string().bytes().map(|byte| format!("{byte:02x}")).collect()
+    }
+}
+
+impl TryFrom<&str> for Cursor {
+    type Error = String;
+
+    fn try_from(token: &str) -> Result<Self, Self::Error> {
+        let invalid = || format!("Invalid cursor {token}");
+
+        if !token.len().is_multiple_of(2) || !token.is_ascii() {
+            return Err(invalid());
+        }
+
+        let bytes: Vec<u8> = (0..token.len()).step_by(2)
+            .map(|index| u8::from_str_radix(&token[index..index + 2], 16))
+            .collect::<Result<_, _>>()
+            .map_err(|_| invalid())?;
+
+        let value: Value = serde_json::from_slice(&bytes).map_err(|_| invalid())?;
+
+        match value["after"].as_i64() {
+            Some(after) => Ok(Cursor::new(after)),
+            None => Err(invalid())
+        }
+    }
+}
@@ -38,7 +38,10 @@ use super::json::Json;
 pub struct Feature {
     pub id: Id,
     pub geometry: Geometry,
-    pub properties: ObjectValue
+    pub properties: ObjectValue,
+    /// Moves on with every update, the `ETag` of the feature. On an update it is the version the change was based on.
+    #[new(default)]
+    pub version: Option<i64>
 }
 
 impl Feature {
@@ -50,6 +53,33 @@ impl Feature {
             "properties": {}
         }}"#, self.id, self.geometry.to_geo_json(), &self.properties.to_geo_json())
     }
+
+    /// Checks a GeoJSON feature holds what `From<&Value>` expects, so it can be reported instead of panicking.
+    pub fn check(value: &Value) -> Result<(), String> {
+        if !value.is_object() {
+            return Err("the feature must be an object".to_string());
+        }
+
+        if !(value["id"].is_null() || value["id"].is_i64()) {
+            return Err("the id must be an integer".to_string());
+        }
+
+        if !(value["properties"].is_null() || value["properties"].is_object()) {
+            return Err("the properties must be an object".to_string());
+        }
+
+        let geometry = &value["geometry"];
+        match geometry["type"].as_str() {
+            Some(r#type) if r#type.eq_ignore_ascii_case("point") => (),
+            Some(r#type) => return Err(format!("the geometry type {type} is not supported")),
+            None => return Err("the geometry must have a type".to_string())
+        }
+
+        match geometry["coordinates"].as_array() {
+            Some(coordinates) if coordinates.len() >= 2 && coordinates.iter().all(Value::is_number) => Ok(()),
+            _ => Err("the point coordinates must be numbers".to_string())
+        }
+    }
 }
 
 impl From<&Value> for Feature {
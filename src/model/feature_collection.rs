@@ -29,6 +29,8 @@
 use derive_new::new;
 use serde_json::{Result, Value};
 
+use super::cursor::Cursor;
+use super::paging::Paging;
 use super::id::Id;
 use super::json::Json;
 use super::feature::Feature;
@@ -39,7 +41,13 @@ pub struct FeatureCollection {
     pub id: Id,
     pub label: String,
     pub features: Vec<Feature>,
-    pub properties: ObjectValue
+    pub properties: ObjectValue,
+    /// Set on keyset pages that may be followed by another one.
+    #[new(default)]
+    pub next: Option<Cursor>,
+    /// Set on item responses.
+    #[new(default)]
+    pub paging: Option<Paging>
 }
 
 impl FeatureCollection {
@@ -48,7 +56,9 @@ impl FeatureCollection {
             id: Id::None,
             label: "".to_string(),
             features: vec![],
-            properties: ObjectValue::empty()      
+            properties: ObjectValue::empty(),
+            next: None,
+            paging: None
         }
     }
 }
@@ -85,15 +95,15 @@ pub struct FeatureCollectionList(Vec<FeatureCollection>);
 
 impl FeatureCollectionList {
 
+    /// Cursor for the keyset page after this one, when it returned `size` collections.
+    pub fn next(&self, size: i64) -> Option<Cursor> {
+        let last_id = self.0.last().and_then(|collection| Option::<i64>::from(&collection.id));
+        Cursor::following(last_id, self.0.len(), size)
+    }
+
     pub fn to_json(&self) -> String {
-        let mut separator = "";
-        let collection_json: String = self.0.iter().map(|collection| {
-            let geo_json = collection.to_geo_json();
-            separator = ",";
-            format!("{}{}", geo_json, separator)
-        }).collect();
-
-        format!("[{}]", collection_json)
+        let collections: Vec<String> = self.0.iter().map(|collection| collection.to_geo_json()).collect();
+        format!("[{}]", collections.join(","))
     }
 }
 
@@ -136,6 +146,17 @@ impl FeatureCollection {
             output.push_str(format!(r#","features": {}"#, parse_features(&self.features)).as_str());
         }
 
+        if let Some(next) = &self.next {
+            output.push_str(format!(r#","next": "{}""#, next.to_token()).as_str());
+        }
+
+        if let Some(paging) = &self.paging {
+            let links: Vec<String> = paging.links.iter().map(|link| link.to_json()).collect();
+
+            output.push_str(format!(r#","numberMatched": {},"numberReturned": {},"timeStamp": "{}","links": [{}]"#,
+                paging.number_matched, self.features.len(), paging.time_stamp, links.join(",")).as_str());
+        }
+
         output.push_str("}");
 
         output
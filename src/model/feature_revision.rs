@@ -0,0 +1,79 @@
+/*
+    Copyright (c)  Abraão Isvi <avraamisvi@users.noreply.github.com>
+
+    Permission is hereby granted, free of charge, to any
+    person obtaining a copy of this software and associated
+    documentation files (the "Software"), to deal in the
+    Software without restriction, including without
+    limitation the rights to use, copy, modify, merge,
+    publish, distribute, sublicense, and/or sell copies of
+    the Software, and to permit persons to whom the Software
+    is furnished to do so, subject to the following
+    conditions:
+
+    The above copyright notice and this permission notice
+    shall be included in all copies or substantial portions
+    of the Software.
+
+    THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF
+    ANY KIND, EXPRESS OR IMPLIED, INCLUDING BUT NOT LIMITED
+    TO THE WARRANTIES OF MERCHANTABILITY, FITNESS FOR A
+    PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT
+    SHALL THE AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY
+    CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER IN AN ACTION
+    OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR
+    IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+    DEALINGS IN THE SOFTWARE.
+ */
+
+use derive_new::new;
+use serde_json::{json, Value};
+
+/// A change recorded in the history of a feature: `create`, `update` and `delete` hold the
+/// feature as it was left, `attach` and `detach` the collection it was added to or removed from.
+#[derive(new)]
+pub struct FeatureRevision {
+    pub revision: i64,
+    pub collection_id: Option<i64>,
+    pub operation: String,
+    pub geometry: Option<Value>,
+    pub properties: Option<Value>,
+    pub changed_by: Option<String>,
+    pub changed_at: String
+}
+
+impl FeatureRevision {
+    pub fn to_json(&self) -> Value {
+        let mut revision = json!({
+            "revision": self.revision,
+            "operation": self.operation,
+            "changedBy": self.changed_by,
+            "changedAt": self.changed_at
+        });
+
+        match self.collection_id {
+            Some(collection_id) => revision["collectionId"] = json!(collection_id),
+            None => {
+                revision["geometry"] = self.geometry.clone().unwrap_or(Value::Null);
+                revision["properties"] = self.properties.clone().unwrap_or(Value::Null);
+            }
+        }
+
+        revision
+    }
+}
+
+/// Every revision of a feature, oldest first.
+#[derive(new)]
+pub struct FeatureHistory {
+    pub feature_id: i64,
+    pub revisions: Vec<FeatureRevision>
+}
+
+impl FeatureHistory {
+    pub fn to_json(&self) -> String {
+        let revisions: Vec<Value> = self.revisions.iter().map(FeatureRevision::to_json).collect();
+
+        json!({"id": self.feature_id, "revisions": revisions}).to_string()
+    }
+}
@@ -26,11 +26,17 @@
     DEALINGS IN THE SOFTWARE.
  */
 
+use std::{collections::HashMap, fmt::Display, error::Error};
+
 use derive_new::new;
-use serde_json::Value;
+use serde_json::{json, Value};
 
 use super::json::Json;
 
+pub mod cql2_text;
+pub mod cql2_json;
+pub mod builder;
+
 /**
  * this is the version 1 of the query language, the plan is to allow for querying within sub fields values, like field = "field1.x1.y2.z3" 
 {
@@ -61,9 +67,16 @@ pub struct Filter {
 
 pub enum ElementType {
     String, 
-    Number
+    Number,
+    Boolean,
+    Array,
+    Object,
+    Parameter
 }
 
+/// A literal of the filter, arrays and objects keep their JSON text in `value`.
+/// Parameters, written `{"$param": "name"}`, are placeholders with the parameter
+/// in `name` and must be bound before running the filter.
 pub struct Element {
     pub name: String,
     pub value: String,
@@ -80,24 +93,176 @@ pub struct NotEquals {
     pub value: Element
 }
 
-pub struct And(Vec<Expression>);
-pub struct Or(Vec<Expression>);
+pub struct LessThan {
+    pub field: String,
+    pub value: Element
+}
+
+pub struct LessThanOrEquals {
+    pub field: String,
+    pub value: Element
+}
+
+pub struct GreaterThan {
+    pub field: String,
+    pub value: Element
+}
+
+pub struct GreaterThanOrEquals {
+    pub field: String,
+    pub value: Element
+}
+
+/// Matches when `field` is missing or null.
+pub struct IsNull {
+    pub field: String
+}
+
+/// Matches `field` against `pattern`, where `*` stands for any sequence of characters,
+/// `?` for a single character and `\` escapes the next character.
+pub struct Like {
+    pub field: String,
+    pub pattern: String
+}
+
+/// Same as [`Like`] but case insensitive.
+pub struct ILike {
+    pub field: String,
+    pub pattern: String
+}
+
+/// Matches when `field` begins with `value`, taken literally.
+pub struct StartsWith {
+    pub field: String,
+    pub value: String
+}
+
+/// GeoJSON geometry used as operand of the spatial expressions.
+pub struct GeometryOperand(pub String);
+
+/// Matches features whose geometry intersects `geometry`.
+pub struct Intersects {
+    pub geometry: GeometryOperand
+}
+
+/// Matches features whose geometry lies within `geometry`.
+pub struct Within {
+    pub geometry: GeometryOperand
+}
+
+/// Matches features whose geometry contains `geometry`.
+pub struct Contains {
+    pub geometry: GeometryOperand
+}
+
+/// Matches features whose geometry does not touch `geometry` at all.
+pub struct Disjoint {
+    pub geometry: GeometryOperand
+}
+
+/// Matches features within `distance` of `geometry`, the distance is expressed in
+/// the units of the geometries reference system (degrees for EPSG:4326).
+pub struct DWithin {
+    pub geometry: GeometryOperand,
+    pub distance: f64
+}
+
+/// Matches when the JSON value of `field` contains `value`, e.g. `["bridge", "damaged"]` contains `["bridge"]`.
+pub struct ContainsValue {
+    pub field: String,
+    pub value: Element
+}
+
+/// Matches when the JSON value of `field` is contained by `value`.
+pub struct ContainedBy {
+    pub field: String,
+    pub value: Element
+}
+
+/// Matches when the array in `field` has at least one of `values`.
+pub struct AnyOf {
+    pub field: String,
+    pub values: Vec<Element>
+}
+
+pub enum LengthComparison {
+    Equals,
+    NotEquals,
+    LessThan,
+    LessThanOrEquals,
+    GreaterThan,
+    GreaterThanOrEquals
+}
+
+/// Compares the length of the array in `field` with `length`, non array values never match.
+pub struct ArrayLength {
+    pub field: String,
+    pub comparison: LengthComparison,
+    pub length: i64
+}
+
+pub struct And(pub Vec<Expression>);
+pub struct Or(pub Vec<Expression>);
+pub struct Not(pub Box<Expression>);
 
 pub enum Expression {
     Equals(Equals),
     NotEquals(NotEquals),
+    LessThan(LessThan),
+    LessThanOrEquals(LessThanOrEquals),
+    GreaterThan(GreaterThan),
+    GreaterThanOrEquals(GreaterThanOrEquals),
+    IsNull(IsNull),
+    Like(Like),
+    ILike(ILike),
+    StartsWith(StartsWith),
+    Intersects(Intersects),
+    Within(Within),
+    Contains(Contains),
+    Disjoint(Disjoint),
+    DWithin(DWithin),
+    ContainsValue(ContainsValue),
+    ContainedBy(ContainedBy),
+    AnyOf(AnyOf),
+    ArrayLength(ArrayLength),
     And(And),
-    Or(Or)
+    Or(Or),
+    Not(Not)
+}
+
+/// Error raised when a filter can not be parsed, `pointer` is the JSON pointer
+/// (RFC 6901) of the offending node and `reason` explains what is wrong with it.
+#[derive(new, Debug)]
+pub struct FilterError {
+    pub pointer: String,
+    pub reason: String
+}
+
+impl FilterError {
+    /// Errors are raised relative to the node being parsed, this prefixes them with the parent path.
+    pub fn under(mut self, parent: &str) -> Self {
+        self.pointer = format!("{parent}{}", self.pointer);
+        self
+    }
+}
+
+impl Display for FilterError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Invalid filter at \"{}\": {}", self.pointer, self.reason)
+    }
 }
 
-impl From<Json> for Filter {
+impl Error for FilterError {}
 
-    fn from(value: Json) -> Self {
+impl TryFrom<Json> for Filter {
+    type Error = FilterError;
+
+    fn try_from(value: Json) -> Result<Self, Self::Error> {
         let value: serde_json::Result<Value> = serde_json::from_str(value.to_string_ref());
 
         match value {
-            Ok(parsed) => Filter::from(&parsed),
-            Err(err) => panic!("Error could not parse json into Filter {}", err),
+            Ok(parsed) => Filter::try_from(&parsed),
+            Err(err) => Err(FilterError::new("".to_string(), format!("the filter is not valid JSON: {err}"))),
         }
     }
 }
@@ -107,119 +272,565 @@ struct ExpressionValueWrapper<'a> {
     pub value: &'a Value 
 }
 
-impl From<ExpressionValueWrapper<'_>> for Expression {
-    fn from(value: ExpressionValueWrapper) -> Self {
+impl TryFrom<ExpressionValueWrapper<'_>> for Expression {
+    type Error = FilterError;
+
+    fn try_from(value: ExpressionValueWrapper) -> Result<Self, Self::Error> {
         if value.ex_type == "equals" {
-            Expression::Equals(Equals::from(value))
+            Ok(Expression::Equals(Equals::try_from(value)?))
+        } else if value.ex_type == "not_equals" {
+            Ok(Expression::NotEquals(NotEquals::try_from(value)?))
+        } else if value.ex_type == "less_than" {
+            Ok(Expression::LessThan(LessThan::try_from(value)?))
+        } else if value.ex_type == "less_than_or_equals" {
+            Ok(Expression::LessThanOrEquals(LessThanOrEquals::try_from(value)?))
+        } else if value.ex_type == "greater_than" {
+            Ok(Expression::GreaterThan(GreaterThan::try_from(value)?))
+        } else if value.ex_type == "greater_than_or_equals" {
+            Ok(Expression::GreaterThanOrEquals(GreaterThanOrEquals::try_from(value)?))
+        } else if value.ex_type == "is_null" {
+            Ok(Expression::IsNull(IsNull::try_from(value)?))
+        } else if value.ex_type == "like" {
+            Ok(Expression::Like(Like::try_from(value)?))
+        } else if value.ex_type == "ilike" {
+            Ok(Expression::ILike(ILike::try_from(value)?))
+        } else if value.ex_type == "starts_with" {
+            Ok(Expression::StartsWith(StartsWith::try_from(value)?))
+        } else if value.ex_type == "intersects" {
+            Ok(Expression::Intersects(Intersects::try_from(value)?))
+        } else if value.ex_type == "within" {
+            Ok(Expression::Within(Within::try_from(value)?))
+        } else if value.ex_type == "contains" && value.value.get("geometry").is_some() {
+            Ok(Expression::Contains(Contains::try_from(value)?))
+        } else if value.ex_type == "contains" {
+            Ok(Expression::ContainsValue(ContainsValue::try_from(value)?))
+        } else if value.ex_type == "contained_by" {
+            Ok(Expression::ContainedBy(ContainedBy::try_from(value)?))
+        } else if value.ex_type == "any_of" {
+            Ok(Expression::AnyOf(AnyOf::try_from(value)?))
+        } else if value.ex_type == "array_length" {
+            Ok(Expression::ArrayLength(ArrayLength::try_from(value)?))
+        } else if value.ex_type == "disjoint" {
+            Ok(Expression::Disjoint(Disjoint::try_from(value)?))
+        } else if value.ex_type == "dwithin" {
+            Ok(Expression::DWithin(DWithin::try_from(value)?))
         } else if value.ex_type == "and" {
-            Expression::And(And::from(value))
+            Ok(Expression::And(And::try_from(value)?))
+        } else if value.ex_type == "or" {
+            Ok(Expression::Or(Or::try_from(value)?))
+        } else if value.ex_type == "not" {
+            Ok(Expression::Not(Not::try_from(value)?))
         } else {
-            unimplemented!()
+            Err(FilterError::new("/type".to_string(), format!("the expression type {} is not supported", value.ex_type)))
         }
     }
 }
 
-impl From<&Value> for Expression {
-    fn from(value: &Value) -> Self {
-        Expression::from(parse_expression_value_wrapper(value))
+impl TryFrom<&Value> for Expression {
+    type Error = FilterError;
+
+    fn try_from(value: &Value) -> Result<Self, Self::Error> {
+        Expression::try_from(parse_expression_value_wrapper(value)?)
+    }
+}
+
+impl TryFrom<ExpressionValueWrapper<'_>> for Equals {
+    type Error = FilterError;
+
+    fn try_from(expression: ExpressionValueWrapper) -> Result<Self, Self::Error> {
+        let (field, value) = parse_comparison_operands(&expression)?;
+        Ok(Equals { field, value })
+    }
+}
+
+impl TryFrom<ExpressionValueWrapper<'_>> for NotEquals {
+    type Error = FilterError;
+
+    fn try_from(expression: ExpressionValueWrapper) -> Result<Self, Self::Error> {
+        let (field, value) = parse_comparison_operands(&expression)?;
+        Ok(NotEquals { field, value })
+    }
+}
+
+impl TryFrom<ExpressionValueWrapper<'_>> for LessThan {
+    type Error = FilterError;
+
+    fn try_from(expression: ExpressionValueWrapper) -> Result<Self, Self::Error> {
+        let (field, value) = parse_comparison_operands(&expression)?;
+        Ok(LessThan { field, value })
+    }
+}
+
+impl TryFrom<ExpressionValueWrapper<'_>> for LessThanOrEquals {
+    type Error = FilterError;
+
+    fn try_from(expression: ExpressionValueWrapper) -> Result<Self, Self::Error> {
+        let (field, value) = parse_comparison_operands(&expression)?;
+        Ok(LessThanOrEquals { field, value })
+    }
+}
+
+impl TryFrom<ExpressionValueWrapper<'_>> for GreaterThan {
+    type Error = FilterError;
+
+    fn try_from(expression: ExpressionValueWrapper) -> Result<Self, Self::Error> {
+        let (field, value) = parse_comparison_operands(&expression)?;
+        Ok(GreaterThan { field, value })
+    }
+}
+
+impl TryFrom<ExpressionValueWrapper<'_>> for GreaterThanOrEquals {
+    type Error = FilterError;
+
+    fn try_from(expression: ExpressionValueWrapper) -> Result<Self, Self::Error> {
+        let (field, value) = parse_comparison_operands(&expression)?;
+        Ok(GreaterThanOrEquals { field, value })
     }
 }
 
-impl From<ExpressionValueWrapper<'_>> for Equals {
+impl TryFrom<ExpressionValueWrapper<'_>> for IsNull {
+    type Error = FilterError;
 
-    fn from(expression: ExpressionValueWrapper) -> Self {
-        let value = expression.value;
-        let field = value["field"].as_str();
-        let field_value = &value["value"];
+    fn try_from(expression: ExpressionValueWrapper) -> Result<Self, Self::Error> {
+        let field = required_str(expression.value, "field")?;
+        Ok(IsNull { field })
+    }
+}
 
-        panic_when_none(field.is_none(), "field");
+impl TryFrom<ExpressionValueWrapper<'_>> for Like {
+    type Error = FilterError;
 
-        Equals { field: field.unwrap().to_string(), value: Element::from(field_value) }
+    fn try_from(expression: ExpressionValueWrapper) -> Result<Self, Self::Error> {
+        let (field, pattern) = parse_text_operands(&expression)?;
+        Ok(Like { field, pattern })
     }
 }
 
-impl From<ExpressionValueWrapper<'_>> for And {
+impl TryFrom<ExpressionValueWrapper<'_>> for ILike {
+    type Error = FilterError;
 
-    fn from(expression: ExpressionValueWrapper) -> Self {
-        let expressions = expression.value["expressions"].as_array();
+    fn try_from(expression: ExpressionValueWrapper) -> Result<Self, Self::Error> {
+        let (field, pattern) = parse_text_operands(&expression)?;
+        Ok(ILike { field, pattern })
+    }
+}
 
-        panic_when_none(expressions.is_none(), "Expressions in And must not be empty or null");
+impl TryFrom<ExpressionValueWrapper<'_>> for StartsWith {
+    type Error = FilterError;
 
-        let expressions_parsed = expressions.unwrap().iter().map(|exp|{
-            Expression::from(exp)
+    fn try_from(expression: ExpressionValueWrapper) -> Result<Self, Self::Error> {
+        let (field, value) = parse_text_operands(&expression)?;
+        Ok(StartsWith { field, value })
+    }
+}
+
+impl TryFrom<ExpressionValueWrapper<'_>> for Intersects {
+    type Error = FilterError;
+
+    fn try_from(expression: ExpressionValueWrapper) -> Result<Self, Self::Error> {
+        Ok(Intersects { geometry: parse_geometry_operand(&expression)? })
+    }
+}
+
+impl TryFrom<ExpressionValueWrapper<'_>> for Within {
+    type Error = FilterError;
+
+    fn try_from(expression: ExpressionValueWrapper) -> Result<Self, Self::Error> {
+        Ok(Within { geometry: parse_geometry_operand(&expression)? })
+    }
+}
+
+impl TryFrom<ExpressionValueWrapper<'_>> for Contains {
+    type Error = FilterError;
+
+    fn try_from(expression: ExpressionValueWrapper) -> Result<Self, Self::Error> {
+        Ok(Contains { geometry: parse_geometry_operand(&expression)? })
+    }
+}
+
+impl TryFrom<ExpressionValueWrapper<'_>> for Disjoint {
+    type Error = FilterError;
+
+    fn try_from(expression: ExpressionValueWrapper) -> Result<Self, Self::Error> {
+        Ok(Disjoint { geometry: parse_geometry_operand(&expression)? })
+    }
+}
+
+impl TryFrom<ExpressionValueWrapper<'_>> for DWithin {
+    type Error = FilterError;
+
+    fn try_from(expression: ExpressionValueWrapper) -> Result<Self, Self::Error> {
+        let distance = expression.value["distance"].as_f64()
+            .ok_or_else(|| FilterError::new("/distance".to_string(), "the distance must be a number".to_string()))?;
+
+        Ok(DWithin { geometry: parse_geometry_operand(&expression)?, distance })
+    }
+}
+
+impl TryFrom<&Value> for GeometryOperand {
+    type Error = FilterError;
+
+    fn try_from(value: &Value) -> Result<Self, Self::Error> {
+        if !value.is_object() || value["type"].as_str().is_none() {
+            return Err(FilterError::new("".to_string(), "the geometry must be a GeoJSON geometry object".to_string()));
+        }
+
+        Ok(GeometryOperand(value.to_string()))
+    }
+}
+
+impl TryFrom<ExpressionValueWrapper<'_>> for ContainsValue {
+    type Error = FilterError;
+
+    fn try_from(expression: ExpressionValueWrapper) -> Result<Self, Self::Error> {
+        let (field, value) = parse_comparison_operands(&expression)?;
+        Ok(ContainsValue { field, value })
+    }
+}
+
+impl TryFrom<ExpressionValueWrapper<'_>> for ContainedBy {
+    type Error = FilterError;
+
+    fn try_from(expression: ExpressionValueWrapper) -> Result<Self, Self::Error> {
+        let (field, value) = parse_comparison_operands(&expression)?;
+        Ok(ContainedBy { field, value })
+    }
+}
+
+impl TryFrom<ExpressionValueWrapper<'_>> for AnyOf {
+    type Error = FilterError;
+
+    fn try_from(expression: ExpressionValueWrapper) -> Result<Self, Self::Error> {
+        let field = required_str(expression.value, "field")?;
+        let values = expression.value["value"].as_array()
+            .ok_or_else(|| FilterError::new("/value".to_string(), "the value of any_of must be an array".to_string()))?;
+
+        let values: Result<Vec<Element>, FilterError> = values.iter().enumerate().map(|(index, value)| {
+            Element::try_from(value).map_err(|err| err.under(&format!("/value/{index}")))
         }).collect();
 
-        And(expressions_parsed)
+        Ok(AnyOf { field, values: values? })
+    }
+}
+
+impl TryFrom<ExpressionValueWrapper<'_>> for ArrayLength {
+    type Error = FilterError;
+
+    fn try_from(expression: ExpressionValueWrapper) -> Result<Self, Self::Error> {
+        let field = required_str(expression.value, "field")?;
+        let length = expression.value["value"].as_i64()
+            .ok_or_else(|| FilterError::new("/value".to_string(), "the value of array_length must be an integer".to_string()))?;
+
+        let comparison = match expression.value["operator"].as_str().unwrap_or("=") {
+            "=" => LengthComparison::Equals,
+            "<>" => LengthComparison::NotEquals,
+            "<" => LengthComparison::LessThan,
+            "<=" => LengthComparison::LessThanOrEquals,
+            ">" => LengthComparison::GreaterThan,
+            ">=" => LengthComparison::GreaterThanOrEquals,
+            other => return Err(FilterError::new("/operator".to_string(), format!("the operator {other} is not supported")))
+        };
+
+        Ok(ArrayLength { field, comparison, length })
     }
 }
 
-impl From<&Value> for Element {
+impl TryFrom<ExpressionValueWrapper<'_>> for And {
+    type Error = FilterError;
 
-    fn from(value: &Value) -> Self {
-        match value {
-            Value::String(data) => {
-                Element {
-                    e_type: ElementType::String,
-                    value: data.clone(),
-                    name: "".to_string(),
-                }
-            },
-            Value::Number(data) => {
-                Element {
-                    e_type: ElementType::Number,
-                    value: data.to_string(),
-                    name: "".to_string(),
-                }
-            },
-            _ => panic!("The value type of {value} is not supported.")
+    fn try_from(expression: ExpressionValueWrapper) -> Result<Self, Self::Error> {
+        Ok(And(parse_expression_list(&expression)?))
+    }
+}
+
+impl TryFrom<ExpressionValueWrapper<'_>> for Or {
+    type Error = FilterError;
+
+    fn try_from(expression: ExpressionValueWrapper) -> Result<Self, Self::Error> {
+        Ok(Or(parse_expression_list(&expression)?))
+    }
+}
+
+impl TryFrom<ExpressionValueWrapper<'_>> for Not {
+    type Error = FilterError;
+
+    fn try_from(expression: ExpressionValueWrapper) -> Result<Self, Self::Error> {
+        let negated = &expression.value["expression"];
+
+        if negated.is_null() {
+            return Err(missing("expression"));
         }
+
+        let negated = Expression::try_from(negated).map_err(|err| err.under("/expression"))?;
+
+        Ok(Not(Box::new(negated)))
+    }
+}
+
+fn parse_expression_list(expression: &ExpressionValueWrapper) -> Result<Vec<Expression>, FilterError> {
+    let expressions = expression.value["expressions"].as_array()
+        .ok_or_else(|| FilterError::new("/expressions".to_string(),
+            format!("the expressions in {} must be an array", expression.ex_type)))?;
+
+    expressions.iter().enumerate().map(|(index, exp)| {
+        Expression::try_from(exp).map_err(|err| err.under(&format!("/expressions/{index}")))
+    }).collect()
+}
+
+fn parse_comparison_operands(expression: &ExpressionValueWrapper) -> Result<(String, Element), FilterError> {
+    let field = required_str(expression.value, "field")?;
+    let value = Element::try_from(&expression.value["value"]).map_err(|err| err.under("/value"))?;
+
+    Ok((field, value))
+}
+
+fn parse_text_operands(expression: &ExpressionValueWrapper) -> Result<(String, String), FilterError> {
+    let field = required_str(expression.value, "field")?;
+    let text = required_str(expression.value, "value")?;
+
+    Ok((field, text))
+}
+
+fn parse_geometry_operand(expression: &ExpressionValueWrapper) -> Result<GeometryOperand, FilterError> {
+    GeometryOperand::try_from(&expression.value["geometry"]).map_err(|err| err.under("/geometry"))
+}
+
+impl TryFrom<&Value> for Element {
+    type Error = FilterError;
+
+    fn try_from(value: &Value) -> Result<Self, Self::Error> {
+        let (e_type, data) = match value {
+            Value::String(data) => (ElementType::String, data.clone()),
+            Value::Number(data) => (ElementType::Number, data.to_string()),
+            Value::Bool(data) => (ElementType::Boolean, data.to_string()),
+            Value::Array(_) => (ElementType::Array, value.to_string()),
+            Value::Object(object) if object.len() == 1 && object.contains_key("$param") => {
+                let name = value["$param"].as_str()
+                    .ok_or_else(|| FilterError::new("/$param".to_string(), "the parameter name must be a string".to_string()))?;
+                return Ok(Element { e_type: ElementType::Parameter, value: "".to_string(), name: name.to_string() });
+            },
+            Value::Object(_) => (ElementType::Object, value.to_string()),
+            Value::Null => return Err(FilterError::new("".to_string(), "null is not a valid value, use is_null instead".to_string()))
+        };
+
+        Ok(Element { e_type, value: data, name: "".to_string() })
     }
 }
 
+impl Element {
+    /// The JSON text of the element, as stored in the feature properties.
+    pub fn to_json(&self) -> String {
+        match self.e_type {
+            ElementType::String => Value::String(self.value.clone()).to_string(),
+            ElementType::Parameter => json!({"$param": self.name}).to_string(),
+            _ => self.value.clone()
+        }
+    }
+
+    /// Values are read as JSON when possible, so `10` is a number and `"10"` a string,
+    /// anything else is taken as a plain string.
+    fn bind(&mut self, params: &HashMap<String, String>) -> Result<(), FilterError> {
+        if let ElementType::Parameter = self.e_type {
+            let text = params.get(&self.name)
+                .ok_or_else(|| FilterError::new("".to_string(), format!("the parameter {} has no value", self.name)))?;
 
-// impl From<Value> for Filter {
-//     fn from(value: Value) -> Self {
-//         let expressions_opt = value["query"].as_array();
+            let bound = serde_json::from_str::<Value>(text).ok()
+                .and_then(|value| Element::try_from(&value).ok())
+                .filter(|element| !matches!(element.e_type, ElementType::Parameter))
+                .unwrap_or(Element { e_type: ElementType::String, value: text.clone(), name: "".to_string() });
 
-//         match expressions_opt {
-//             Some(expressions) => {
-//                 let expressions_parsed: Vec<Expression> = expressions.iter().map(|expression|{
-//                     Expression::from(parse_expression_value_wrapper(expression))
-//                 }).collect();
+            *self = bound;
+        }
 
-//                 Filter::new(expressions_parsed)
-//             },
-//             None => Filter::new(vec![]),
-//         }
-//     }
-// }
+        Ok(())
+    }
+}
 
-impl From<&Value> for Filter {
-    fn from(value: &Value) -> Self {
+impl TryFrom<&Value> for Filter {
+    type Error = FilterError;
+
+    fn try_from(value: &Value) -> Result<Self, Self::Error> {
         let expression = &value["where"];
-        let collection_id = value["collection_id"].as_i64();
+        let collection_id = value["collection_id"].as_i64()
+            .ok_or_else(|| FilterError::new("/collection_id".to_string(), "the collection_id must be an integer".to_string()))?;
+
+        if expression.is_null() {
+            return Err(missing("where"));
+        }
+
+        let expressions_parsed = if cql2_json::is_cql2_json(expression) {
+            cql2_json::parse(expression)
+        } else {
+            Expression::try_from(expression)
+        };
+
+        Ok(Filter::new(collection_id, expressions_parsed.map_err(|err| err.under("/where"))?))
+    }
+}
+
+impl Filter {
+    /// Replaces the `{"$param": "name"}` placeholders with the given values.
+    pub fn bind(&mut self, params: &HashMap<String, String>) -> Result<(), FilterError> {
+        bind_expression(&mut self.expressions, params)
+    }
+
+    /// Writes the filter back into the JSON format read by `Filter::try_from`.
+    pub fn to_json(&self) -> String {
+        json!({
+            "collection_id": self.collection_id,
+            "where": Value::from(&self.expressions)
+        }).to_string()
+    }
+}
+
+impl Expression {
+    /// The name of the first `{"$param": "name"}` placeholder left unbound, the backends refuse to run those.
+    pub fn unbound_parameter(&self) -> Option<&str> {
+        match self {
+            Expression::Equals(exp) => parameter(&exp.value),
+            Expression::NotEquals(exp) => parameter(&exp.value),
+            Expression::LessThan(exp) => parameter(&exp.value),
+            Expression::LessThanOrEquals(exp) => parameter(&exp.value),
+            Expression::GreaterThan(exp) => parameter(&exp.value),
+            Expression::GreaterThanOrEquals(exp) => parameter(&exp.value),
+            Expression::ContainsValue(exp) => parameter(&exp.value),
+            Expression::ContainedBy(exp) => parameter(&exp.value),
+            Expression::AnyOf(exp) => exp.values.iter().find_map(parameter),
+            Expression::And(And(expressions)) | Expression::Or(Or(expressions)) => {
+                expressions.iter().find_map(|expression| expression.unbound_parameter())
+            },
+            Expression::Not(Not(expression)) => expression.unbound_parameter(),
+            _ => None
+        }
+    }
 
-        panic_when_none(expression.is_null(), "where");
-        panic_when_none(collection_id.is_none(), "collection_id");
+    /// Whether the expression, or any nested one, compares the feature geometry.
+    pub fn is_spatial(&self) -> bool {
+        match self {
+            Expression::Intersects(_) | Expression::Within(_) | Expression::Contains(_)
+                | Expression::Disjoint(_) | Expression::DWithin(_) => true,
+            Expression::And(And(expressions)) | Expression::Or(Or(expressions)) => {
+                expressions.iter().any(|expression| expression.is_spatial())
+            },
+            Expression::Not(Not(expression)) => expression.is_spatial(),
+            _ => false
+        }
+    }
+}
 
-        let expressions_parsed = Expression::from(parse_expression_value_wrapper(expression));
+impl From<&Expression> for Value {
+    fn from(expression: &Expression) -> Self {
+        match expression {
+            Expression::Equals(exp) => comparison_json("equals", &exp.field, &exp.value),
+            Expression::NotEquals(exp) => comparison_json("not_equals", &exp.field, &exp.value),
+            Expression::LessThan(exp) => comparison_json("less_than", &exp.field, &exp.value),
+            Expression::LessThanOrEquals(exp) => comparison_json("less_than_or_equals", &exp.field, &exp.value),
+            Expression::GreaterThan(exp) => comparison_json("greater_than", &exp.field, &exp.value),
+            Expression::GreaterThanOrEquals(exp) => comparison_json("greater_than_or_equals", &exp.field, &exp.value),
+            Expression::IsNull(exp) => json!({"type": "is_null", "field": exp.field}),
+            Expression::Like(exp) => json!({"type": "like", "field": exp.field, "value": exp.pattern}),
+            Expression::ILike(exp) => json!({"type": "ilike", "field": exp.field, "value": exp.pattern}),
+            Expression::StartsWith(exp) => json!({"type": "starts_with", "field": exp.field, "value": exp.value}),
+            Expression::Intersects(exp) => spatial_json("intersects", &exp.geometry),
+            Expression::Within(exp) => spatial_json("within", &exp.geometry),
+            Expression::Contains(exp) => spatial_json("contains", &exp.geometry),
+            Expression::Disjoint(exp) => spatial_json("disjoint", &exp.geometry),
+            Expression::DWithin(exp) => {
+                let mut value = spatial_json("dwithin", &exp.geometry);
+                value["distance"] = json!(exp.distance);
+                value
+            },
+            Expression::ContainsValue(exp) => comparison_json("contains", &exp.field, &exp.value),
+            Expression::ContainedBy(exp) => comparison_json("contained_by", &exp.field, &exp.value),
+            Expression::AnyOf(exp) => {
+                let values: Vec<Value> = exp.values.iter().map(Value::from).collect();
+                json!({"type": "any_of", "field": exp.field, "value": values})
+            },
+            Expression::ArrayLength(exp) => {
+                let operator = match exp.comparison {
+                    LengthComparison::Equals => "=",
+                    LengthComparison::NotEquals => "<>",
+                    LengthComparison::LessThan => "<",
+                    LengthComparison::LessThanOrEquals => "<=",
+                    LengthComparison::GreaterThan => ">",
+                    LengthComparison::GreaterThanOrEquals => ">=",
+                };
+                json!({"type": "array_length", "field": exp.field, "operator": operator, "value": exp.length})
+            },
+            Expression::And(exp) => {
+                let expressions: Vec<Value> = exp.0.iter().map(Value::from).collect();
+                json!({"type": "and", "expressions": expressions})
+            },
+            Expression::Or(exp) => {
+                let expressions: Vec<Value> = exp.0.iter().map(Value::from).collect();
+                json!({"type": "or", "expressions": expressions})
+            },
+            Expression::Not(exp) => json!({"type": "not", "expression": Value::from(exp.0.as_ref())}),
+        }
+    }
+}
 
-        Filter::new(collection_id.unwrap(), expressions_parsed)
+fn parameter(value: &Element) -> Option<&str> {
+    match value.e_type {
+        ElementType::Parameter => Some(value.name.as_str()),
+        _ => None
     }
 }
 
-fn parse_expression_value_wrapper(value: &Value) -> ExpressionValueWrapper {
+fn bind_expression(expression: &mut Expression, params: &HashMap<String, String>) -> Result<(), FilterError> {
+    match expression {
+        Expression::Equals(exp) => exp.value.bind(params),
+        Expression::NotEquals(exp) => exp.value.bind(params),
+        Expression::LessThan(exp) => exp.value.bind(params),
+        Expression::LessThanOrEquals(exp) => exp.value.bind(params),
+        Expression::GreaterThan(exp) => exp.value.bind(params),
+        Expression::GreaterThanOrEquals(exp) => exp.value.bind(params),
+        Expression::ContainsValue(exp) => exp.value.bind(params),
+        Expression::ContainedBy(exp) => exp.value.bind(params),
+        Expression::AnyOf(exp) => exp.values.iter_mut().try_for_each(|value| value.bind(params)),
+        Expression::And(exp) => exp.0.iter_mut().try_for_each(|exp| bind_expression(exp, params)),
+        Expression::Or(exp) => exp.0.iter_mut().try_for_each(|exp| bind_expression(exp, params)),
+        Expression::Not(exp) => bind_expression(&mut exp.0, params),
+        _ => Ok(())
+    }
+}
+
+impl From<&Element> for Value {
+    fn from(element: &Element) -> Self {
+        match element.e_type {
+            ElementType::String => Value::String(element.value.clone()),
+            ElementType::Parameter => json!({"$param": element.name}),
+            _ => serde_json::from_str(&element.value).unwrap_or(Value::String(element.value.clone()))
+        }
+    }
+}
+
+fn comparison_json(ex_type: &str, field: &str, value: &Element) -> Value {
+    json!({"type": ex_type, "field": field, "value": Value::from(value)})
+}
+
+fn spatial_json(ex_type: &str, geometry: &GeometryOperand) -> Value {
+    let geometry: Value = serde_json::from_str(&geometry.0).unwrap_or(Value::Null);
+    json!({"type": ex_type, "geometry": geometry})
+}
+
+fn parse_expression_value_wrapper(value: &Value) -> Result<ExpressionValueWrapper<'_>, FilterError> {
     let ex_type_opt = value["type"].as_str();
     match ex_type_opt {
         Some(ex_type) => {
-            ExpressionValueWrapper{ex_type: ex_type.to_lowercase(), value: value}
+            Ok(ExpressionValueWrapper{ex_type: ex_type.to_lowercase(), value})
         },
-        None => panic!("It was not possible to determine the type of the expression {}", value),
+        None => Err(FilterError::new("/type".to_string(), "it was not possible to determine the type of the expression".to_string())),
     }
 }
 
-fn panic_when_none(empty: bool, name: &'static str) {
-    if empty {
-        panic!("Error, the {name} is empty or null")
+fn required_str(value: &Value, name: &str) -> Result<String, FilterError> {
+    match value[name].as_str() {
+        Some(text) => Ok(text.to_string()),
+        None => Err(FilterError::new(format!("/{name}"), format!("the {name} must be a string")))
     }
-}
\ No newline at end of file
+}
+
+fn missing(name: &str) -> FilterError {
+    FilterError::new(format!("/{name}"), format!("the {name} is empty or null"))
+}
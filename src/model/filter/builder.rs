@@ -0,0 +1,281 @@
+/*
+    Copyright (c)  Abraão Isvi <avraamisvi@users.noreply.github.com>
+
+    Permission is hereby granted, free of charge, to any
+    person obtaining a copy of this software and associated
+    documentation files (the "Software"), to deal in the
+    Software without restriction, including without
+    limitation the rights to use, copy, modify, merge,
+    publish, distribute, sublicense, and/or sell copies of
+    the Software, and to permit persons to whom the Software
+    is furnished to do so, subject to the following
+    conditions:
+
+    The above copyright notice and this permission notice
+    shall be included in all copies or substantial portions
+    of the Software.
+
+    THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF
+    ANY KIND, EXPRESS OR IMPLIED, INCLUDING BUT NOT LIMITED
+    TO THE WARRANTIES OF MERCHANTABILITY, FITNESS FOR A
+    PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT
+    SHALL THE AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY
+    CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER IN AN ACTION
+    OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR
+    IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+    DEALINGS IN THE SOFTWARE.
+ */
+
+
+/*!
+ * Fluent construction of filter expressions, e.g.
+ *
+ * `prop("a").eq(1).and(prop("b").gt(2)).or(!prop("tags").contains(vec!["bridge"]))`
+ *
+ * produces the same `Expression` tree as the equivalent JSON filter. The CQL2 parsers build their trees with it.
+ */
+
+use std::ops;
+
+use serde_json::Value;
+
+use crate::model::{bbox::Bbox, geometry::{Geometry, GeometryTrait}};
+
+use super::{Expression, Element, ElementType, Equals, NotEquals, LessThan, LessThanOrEquals, GreaterThan,
+    GreaterThanOrEquals, IsNull, Like, ILike, GeometryOperand, Intersects, Within, Contains, Disjoint, DWithin,
+    ContainsValue, ContainedBy, AnyOf, And, Or, Not};
+
+/// Starts an expression over the property `field`.
+pub fn prop(field: &str) -> Property {
+    Property(field.to_string())
+}
+
+pub fn intersects(geometry: impl Into<GeometryOperand>) -> Expression {
+    Expression::Intersects(Intersects { geometry: geometry.into() })
+}
+
+pub fn within(geometry: impl Into<GeometryOperand>) -> Expression {
+    Expression::Within(Within { geometry: geometry.into() })
+}
+
+pub fn contains(geometry: impl Into<GeometryOperand>) -> Expression {
+    Expression::Contains(Contains { geometry: geometry.into() })
+}
+
+pub fn disjoint(geometry: impl Into<GeometryOperand>) -> Expression {
+    Expression::Disjoint(Disjoint { geometry: geometry.into() })
+}
+
+pub fn dwithin(geometry: impl Into<GeometryOperand>, distance: f64) -> Expression {
+    Expression::DWithin(DWithin { geometry: geometry.into(), distance })
+}
+
+pub struct Property(String);
+
+impl Property {
+
+    pub fn eq(self, value: impl Into<Element>) -> Expression {
+        Expression::Equals(Equals { field: self.0, value: value.into() })
+    }
+
+    pub fn ne(self, value: impl Into<Element>) -> Expression {
+        Expression::NotEquals(NotEquals { field: self.0, value: value.into() })
+    }
+
+    pub fn lt(self, value: impl Into<Element>) -> Expression {
+        Expression::LessThan(LessThan { field: self.0, value: value.into() })
+    }
+
+    pub fn le(self, value: impl Into<Element>) -> Expression {
+        Expression::LessThanOrEquals(LessThanOrEquals { field: self.0, value: value.into() })
+    }
+
+    pub fn gt(self, value: impl Into<Element>) -> Expression {
+        Expression::GreaterThan(GreaterThan { field: self.0, value: value.into() })
+    }
+
+    pub fn ge(self, value: impl Into<Element>) -> Expression {
+        Expression::GreaterThanOrEquals(GreaterThanOrEquals { field: self.0, value: value.into() })
+    }
+
+    pub fn is_null(&self) -> Expression {
+        Expression::IsNull(IsNull { field: self.0.clone() })
+    }
+
+    /// `*` and `?` are the wildcards, see [`Like`].
+    pub fn like(self, pattern: &str) -> Expression {
+        Expression::Like(Like { field: self.0, pattern: pattern.to_string() })
+    }
+
+    pub fn ilike(self, pattern: &str) -> Expression {
+        Expression::ILike(ILike { field: self.0, pattern: pattern.to_string() })
+    }
+
+    pub fn contains(self, value: impl Into<Element>) -> Expression {
+        Expression::ContainsValue(ContainsValue { field: self.0, value: value.into() })
+    }
+
+    pub fn contained_by(self, value: impl Into<Element>) -> Expression {
+        Expression::ContainedBy(ContainedBy { field: self.0, value: value.into() })
+    }
+
+    pub fn any_of<T: Into<Element>>(self, values: impl IntoIterator<Item = T>) -> Expression {
+        Expression::AnyOf(AnyOf { field: self.0, values: values.into_iter().map(Into::into).collect() })
+    }
+
+}
+
+impl Expression {
+
+    /// Chained calls are flattened into a single `And`.
+    pub fn and(self, other: Expression) -> Expression {
+        match self {
+            Expression::And(And(mut expressions)) => {
+                expressions.push(other);
+                Expression::And(And(expressions))
+            },
+            _ => Expression::And(And(vec![self, other]))
+        }
+    }
+
+    /// Chained calls are flattened into a single `Or`.
+    pub fn or(self, other: Expression) -> Expression {
+        match self {
+            Expression::Or(Or(mut expressions)) => {
+                expressions.push(other);
+                Expression::Or(Or(expressions))
+            },
+            _ => Expression::Or(Or(vec![self, other]))
+        }
+    }
+}
+
+impl ops::Not for Expression {
+    type Output = Expression;
+
+    fn not(self) -> Self::Output {
+        Expression::Not(Not(Box::new(self)))
+    }
+}
+
+impl From<&str> for Element {
+    fn from(value: &str) -> Self {
+        Element { name: "".to_string(), value: value.to_string(), e_type: ElementType::String }
+    }
+}
+
+impl From<String> for Element {
+    fn from(value: String) -> Self {
+        Element { name: "".to_string(), value, e_type: ElementType::String }
+    }
+}
+
+impl From<i64> for Element {
+    fn from(value: i64) -> Self {
+        Element { name: "".to_string(), value: value.to_string(), e_type: ElementType::Number }
+    }
+}
+
+impl From<i32> for Element {
+    fn from(value: i32) -> Self {
+        Element::from(value as i64)
+    }
+}
+
+impl From<f64> for Element {
+    fn from(value: f64) -> Self {
+        Element { name: "".to_string(), value: Value::from(value).to_string(), e_type: ElementType::Number }
+    }
+}
+
+impl From<bool> for Element {
+    fn from(value: bool) -> Self {
+        Element { name: "".to_string(), value: value.to_string(), e_type: ElementType::Boolean }
+    }
+}
+
+impl<T: Into<Element>> From<Vec<T>> for Element {
+    fn from(values: Vec<T>) -> Self {
+        let values: Vec<Value> = values.into_iter().map(|value| Value::from(&value.into())).collect();
+        Element { name: "".to_string(), value: Value::Array(values).to_string(), e_type: ElementType::Array }
+    }
+}
+
+impl From<&Geometry> for GeometryOperand {
+    fn from(geometry: &Geometry) -> Self {
+        GeometryOperand(compact_json(&geometry.to_geo_json()))
+    }
+}
+
+impl From<&Bbox> for GeometryOperand {
+    fn from(bbox: &Bbox) -> Self {
+        GeometryOperand(compact_json(&bbox.to_geo_json()))
+    }
+}
+
+//same text the JSON parser keeps for the operand
+fn compact_json(geo_json: &str) -> String {
+    serde_json::from_str::<Value>(geo_json).map(|value| value.to_string()).unwrap_or(geo_json.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::{json, Value};
+
+    use crate::model::bbox::Bbox;
+    use crate::model::filter::Expression;
+
+    use super::{prop, intersects, dwithin};
+
+    //the native JSON filter the builder must agree with
+    fn native(value: Value) -> Value {
+        Value::from(&Expression::try_from(&value).unwrap())
+    }
+
+    #[test]
+    fn builds_the_native_comparisons() {
+        assert_eq!(Value::from(&prop("name").eq("Salvador")),
+            native(json!({"type": "equals", "field": "name", "value": "Salvador"})));
+        assert_eq!(Value::from(&prop("population").ge(1000)),
+            native(json!({"type": "greater_than_or_equals", "field": "population", "value": 1000})));
+        assert_eq!(Value::from(&prop("area").lt(2.5)),
+            native(json!({"type": "less_than", "field": "area", "value": 2.5})));
+        assert_eq!(Value::from(&prop("capital").ne(true)),
+            native(json!({"type": "not_equals", "field": "capital", "value": true})));
+        assert_eq!(Value::from(&prop("name").is_null()), native(json!({"type": "is_null", "field": "name"})));
+        assert_eq!(Value::from(&prop("name").ilike("sal*")), native(json!({"type": "ilike", "field": "name", "value": "sal*"})));
+    }
+
+    #[test]
+    fn builds_array_predicates() {
+        assert_eq!(Value::from(&prop("tags").contains(vec!["bridge", "river"])),
+            native(json!({"type": "contains", "field": "tags", "value": ["bridge", "river"]})));
+        assert_eq!(Value::from(&prop("tags").contained_by(vec![1, 2])),
+            native(json!({"type": "contained_by", "field": "tags", "value": [1, 2]})));
+        assert_eq!(Value::from(&prop("tags").any_of(vec!["bridge", "river"])),
+            native(json!({"type": "any_of", "field": "tags", "value": ["bridge", "river"]})));
+    }
+
+    #[test]
+    fn flattens_chained_logical_operators() {
+        let expression = prop("a").eq(1).and(prop("b").gt(2)).and(prop("c").lt(3)).or(!prop("d").is_null());
+
+        assert_eq!(Value::from(&expression), json!({"type": "or", "expressions": [
+            {"type": "and", "expressions": [
+                {"type": "equals", "field": "a", "value": 1},
+                {"type": "greater_than", "field": "b", "value": 2},
+                {"type": "less_than", "field": "c", "value": 3}
+            ]},
+            {"type": "not", "expression": {"type": "is_null", "field": "d"}}
+        ]}));
+    }
+
+    #[test]
+    fn builds_spatial_predicates_from_a_bbox() {
+        let bbox = Bbox::new(-38.6, -13.1, -38.3, -12.8);
+        let polygon = json!({"type": "Polygon", "coordinates": [[[-38.6, -13.1], [-38.6, -12.8], [-38.3, -12.8], [-38.3, -13.1], [-38.6, -13.1]]]});
+
+        assert_eq!(Value::from(&intersects(&bbox)), native(json!({"type": "intersects", "geometry": polygon})));
+        assert_eq!(Value::from(&dwithin(&bbox, 10.0)), native(json!({"type": "dwithin", "geometry": polygon, "distance": 10.0})));
+    }
+}
@@ -0,0 +1,414 @@
+/*
+    Copyright (c)  Abraão Isvi <avraamisvi@users.noreply.github.com>
+
+    Permission is hereby granted, free of charge, to any
+    person obtaining a copy of this software and associated
+    documentation files (the "Software"), to deal in the
+    Software without restriction, including without
+    limitation the rights to use, copy, modify, merge,
+    publish, distribute, sublicense, and/or sell copies of
+    the Software, and to permit persons to whom the Software
+    is furnished to do so, subject to the following
+    conditions:
+
+    The above copyright notice and this permission notice
+    shall be included in all copies or substantial portions
+    of the Software.
+
+    THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF
+    ANY KIND, EXPRESS OR IMPLIED, INCLUDING BUT NOT LIMITED
+    TO THE WARRANTIES OF MERCHANTABILITY, FITNESS FOR A
+    PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT
+    SHALL THE AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY
+    CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER IN AN ACTION
+    OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR
+    IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+    DEALINGS IN THE SOFTWARE.
+ */
+
+/*!
+ * Reads the CQL2-JSON encoding (OGC API Features Part 3) into the filter model, e.g.
+ *
+ * ```text
+ * {
+ *     "op": "and",
+ *     "args": [
+ *         { "op": ">", "args": [ { "property": "population" }, 1000 ] },
+ *         { "op": "s_intersects", "args": [ { "property": "geometry" }, { "bbox": [ -39, -13, -38, -12 ] } ] }
+ *     ]
+ * }
+ * ```
+ */
+
+use serde_json::Value;
+
+use super::{Expression, Element, ElementType, And, Or, GeometryOperand, FilterError};
+use super::builder::{prop, intersects, within, contains, disjoint, dwithin};
+use super::cql2_text::{bbox_polygon, like_pattern};
+
+/// Operators accepted in the `op` member, `s_dwithin` is an extension taking the distance as third argument.
+/// `casei` is not a predicate, it wraps a property or string compared with `like`, `=` or `<>`.
+pub static SUPPORTED_OPERATORS: [&str; 23] = ["and", "or", "not", "=", "<>", "<", "<=", ">", ">=", "like", "casei",
+    "between", "in", "isNull", "s_intersects", "s_within", "s_contains", "s_disjoint", "s_dwithin", "a_equals",
+    "a_contains", "a_containedby", "a_overlaps"];
+
+/// A CQL2-JSON expression is told apart from the native format by its `op` member.
+pub fn is_cql2_json(value: &Value) -> bool {
+    value.get("op").is_some()
+}
+
+/// Parses a CQL2-JSON expression, the pointer of the returned errors is relative to `value`.
+pub fn parse(value: &Value) -> Result<Expression, FilterError> {
+    let op = value["op"].as_str()
+        .ok_or_else(|| FilterError::new("/op".to_string(), "the op must be a string".to_string()))?;
+    let args = value["args"].as_array()
+        .ok_or_else(|| FilterError::new("/args".to_string(), "the args must be an array".to_string()))?;
+
+    let op = op.to_lowercase();
+
+    match op.as_str() {
+        "and" => Ok(Expression::And(And(parse_all(args)?))),
+        "or" => Ok(Expression::Or(Or(parse_all(args)?))),
+        "not" => {
+            expect_arguments(&op, args, 1)?;
+            Ok(!parse(&args[0]).map_err(|err| err.under("/args/0"))?)
+        },
+        "=" | "<>" | "<" | "<=" | ">" | ">=" => parse_comparison(&op, args),
+        "like" => {
+            expect_arguments(&op, args, 2)?;
+            let (field, case_insensitive) = parse_property(&args[0]).map_err(|err| err.under("/args/0"))?;
+            let (pattern, pattern_case_insensitive) = parse_text(&args[1]).map_err(|err| err.under("/args/1"))?;
+            let pattern = like_pattern(&pattern);
+
+            if case_insensitive || pattern_case_insensitive {
+                Ok(prop(&field).ilike(&pattern))
+            } else {
+                Ok(prop(&field).like(&pattern))
+            }
+        },
+        "between" => {
+            expect_arguments(&op, args, 3)?;
+            let (field, _) = parse_property(&args[0]).map_err(|err| err.under("/args/0"))?;
+            let lower = parse_literal(&args[1]).map_err(|err| err.under("/args/1"))?;
+            let upper = parse_literal(&args[2]).map_err(|err| err.under("/args/2"))?;
+
+            Ok(prop(&field).ge(lower).and(prop(&field).le(upper)))
+        },
+        "in" => {
+            expect_arguments(&op, args, 2)?;
+            let (field, _) = parse_property(&args[0]).map_err(|err| err.under("/args/0"))?;
+            let values = args[1].as_array()
+                .ok_or_else(|| FilterError::new("/args/1".to_string(), "the in list must be an array".to_string()))?;
+
+            let expressions: Result<Vec<Expression>, FilterError> = values.iter().enumerate().map(|(index, value)| {
+                let value = parse_literal(value).map_err(|err| err.under(&format!("/args/1/{index}")))?;
+                Ok(prop(&field).eq(value))
+            }).collect();
+
+            Ok(Expression::Or(Or(expressions?)))
+        },
+        "isnull" => {
+            expect_arguments(&op, args, 1)?;
+            let (field, _) = parse_property(&args[0]).map_err(|err| err.under("/args/0"))?;
+            Ok(prop(&field).is_null())
+        },
+        "s_intersects" | "s_within" | "s_contains" | "s_disjoint" | "s_dwithin" => parse_spatial(&op, args),
+        "a_equals" | "a_contains" | "a_containedby" | "a_overlaps" => parse_array(&op, args),
+        "casei" => Err(FilterError::new("/op".to_string(), "casei can only wrap the arguments of like, = and <>".to_string())),
+        _ => Err(FilterError::new("/op".to_string(), format!("the operator {op} is not supported")))
+    }
+}
+
+fn parse_all(args: &[Value]) -> Result<Vec<Expression>, FilterError> {
+    args.iter().enumerate().map(|(index, arg)| {
+        parse(arg).map_err(|err| err.under(&format!("/args/{index}")))
+    }).collect()
+}
+
+fn parse_comparison(op: &str, args: &[Value]) -> Result<Expression, FilterError> {
+    expect_arguments(op, args, 2)?;
+
+    if is_property(&args[0]) && is_property(&args[1]) {
+        return Err(FilterError::new("/args".to_string(), "comparing two properties is not supported".to_string()));
+    }
+
+    //literal op property is turned around into property op' literal
+    let (property, literal, op) = if is_property(&args[0]) || !is_property(&args[1]) {
+        (0, 1, op)
+    } else {
+        (1, 0, mirror(op))
+    };
+
+    let (field, case_insensitive) = parse_property(&args[property]).map_err(|err| err.under(&format!("/args/{property}")))?;
+
+    if case_insensitive || casei(&args[literal]).is_some() {
+        return parse_case_insensitive(op, field, &args[literal]);
+    }
+
+    let value = parse_literal(&args[literal]).map_err(|err| err.under(&format!("/args/{literal}")))?;
+
+    Ok(match op {
+        "=" => prop(&field).eq(value),
+        "<>" => prop(&field).ne(value),
+        "<" => prop(&field).lt(value),
+        "<=" => prop(&field).le(value),
+        ">" => prop(&field).gt(value),
+        _ => prop(&field).ge(value),
+    })
+}
+
+//case-insensitive equality is an ilike without wildcards
+fn parse_case_insensitive(op: &str, field: String, value: &Value) -> Result<Expression, FilterError> {
+    let (text, _) = parse_text(value).map_err(|err| err.under("/args/1"))?;
+    let expression = prop(&field).ilike(&escape_pattern(&text));
+
+    match op {
+        "=" => Ok(expression),
+        "<>" => Ok(!expression),
+        _ => Err(FilterError::new("/op".to_string(), format!("the operator {op} can not compare casei values, only = and <> can")))
+    }
+}
+
+fn parse_spatial(op: &str, args: &[Value]) -> Result<Expression, FilterError> {
+    let distance = if op == "s_dwithin" {
+        expect_arguments(op, args, 3)?;
+        let distance = args[2].as_f64()
+            .ok_or_else(|| FilterError::new("/args/2".to_string(), "the distance must be a number".to_string()))?;
+        Some(distance)
+    } else {
+        expect_arguments(op, args, 2)?;
+        None
+    };
+
+    //the feature geometry is always the left operand, so within and contains swap when mirrored
+    let (geometry, mirrored) = match (is_property(&args[0]), is_property(&args[1])) {
+        (true, false) => (parse_geometry(&args[1]).map_err(|err| err.under("/args/1"))?, false),
+        (false, true) => (parse_geometry(&args[0]).map_err(|err| err.under("/args/0"))?, true),
+        _ => return Err(FilterError::new("/args".to_string(),
+            format!("{op} must compare the feature geometry with a geometry literal")))
+    };
+
+    Ok(match (op, mirrored) {
+        ("s_intersects", _) => intersects(geometry),
+        ("s_disjoint", _) => disjoint(geometry),
+        ("s_within", false) | ("s_contains", true) => within(geometry),
+        ("s_contains", false) | ("s_within", true) => contains(geometry),
+        _ => dwithin(geometry, distance.unwrap()),
+    })
+}
+
+fn parse_array(op: &str, args: &[Value]) -> Result<Expression, FilterError> {
+    expect_arguments(op, args, 2)?;
+
+    let (field, _) = parse_property(&args[0]).map_err(|err| err.under("/args/0"))?;
+    let values = args[1].as_array()
+        .ok_or_else(|| FilterError::new("/args/1".to_string(), format!("the second argument of {op} must be an array")))?;
+
+    let values: Vec<Element> = values.iter().enumerate().map(|(index, value)| {
+        parse_literal(value).map_err(|err| err.under(&format!("/args/1/{index}")))
+    }).collect::<Result<_, _>>()?;
+
+    if op == "a_overlaps" {
+        return Ok(prop(&field).any_of(values));
+    }
+
+    //the items are checked above, the other array functions compare the array as a whole
+    let value = Element::try_from(&args[1]).map_err(|err| err.under("/args/1"))?;
+
+    Ok(match op {
+        "a_equals" => prop(&field).eq(value),
+        "a_contains" => prop(&field).contains(value),
+        _ => prop(&field).contained_by(value),
+    })
+}
+
+fn parse_geometry(value: &Value) -> Result<GeometryOperand, FilterError> {
+    match value["bbox"].as_array() {
+        Some(bbox) => {
+            let values: Vec<f64> = bbox.iter().filter_map(|value| value.as_f64()).collect();
+
+            if values.len() != 4 || bbox.len() != 4 {
+                return Err(FilterError::new("/bbox".to_string(), "the bbox must have 4 numbers".to_string()));
+            }
+
+            Ok(GeometryOperand(bbox_polygon(&values).to_string()))
+        },
+        None => GeometryOperand::try_from(value)
+    }
+}
+
+//CQL2-JSON objects are property references, functions or temporal literals, none of them a value to compare with
+fn parse_literal(value: &Value) -> Result<Element, FilterError> {
+    let element = Element::try_from(value)?;
+
+    match element.e_type {
+        ElementType::Object => Err(FilterError::new("".to_string(),
+            format!("the argument {value} is not supported, expected a string, number, boolean or array literal"))),
+        _ => Ok(element)
+    }
+}
+
+//`{"op": "casei", "args": [...]}`, or the `{"casei": ...}` of the CQL2 drafts, with the pointer of what it wraps
+fn casei(value: &Value) -> Option<(&Value, &'static str)> {
+    if let Some(inner) = value.get("casei") {
+        return Some((inner, "/casei"));
+    }
+
+    match (value["op"].as_str(), value["args"].as_array()) {
+        (Some(op), Some(args)) if op.eq_ignore_ascii_case("casei") && args.len() == 1 => Some((&args[0], "/args/0")),
+        _ => None
+    }
+}
+
+fn is_property(value: &Value) -> bool {
+    value.get("property").is_some() || casei(value).map(|(inner, _)| inner.get("property").is_some()).unwrap_or(false)
+}
+
+fn parse_property(value: &Value) -> Result<(String, bool), FilterError> {
+    if let Some((inner, pointer)) = casei(value) {
+        let (field, _) = parse_property(inner).map_err(|err| err.under(pointer))?;
+        return Ok((field, true));
+    }
+
+    match value["property"].as_str() {
+        Some(field) => Ok((field.to_string(), false)),
+        None => Err(FilterError::new("/property".to_string(), "expected a property reference".to_string()))
+    }
+}
+
+fn parse_text(value: &Value) -> Result<(String, bool), FilterError> {
+    if let Some((inner, pointer)) = casei(value) {
+        let (text, _) = parse_text(inner).map_err(|err| err.under(pointer))?;
+        return Ok((text, true));
+    }
+
+    match value.as_str() {
+        Some(text) => Ok((text.to_string(), false)),
+        None => Err(FilterError::new("".to_string(), "expected a string".to_string()))
+    }
+}
+
+//the text taken literally by a like of the filter model
+fn escape_pattern(text: &str) -> String {
+    text.replace('\\', r"\\").replace('*', r"\*").replace('?', r"\?")
+}
+
+fn mirror(op: &str) -> &str {
+    match op {
+        "<" => ">",
+        "<=" => ">=",
+        ">" => "<",
+        ">=" => "<=",
+        other => other
+    }
+}
+
+fn expect_arguments(op: &str, args: &[Value], count: usize) -> Result<(), FilterError> {
+    if args.len() != count {
+        return Err(FilterError::new("/args".to_string(),
+            format!("the operator {op} expects {count} arguments, got {}", args.len())));
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::{json, Value};
+
+    use super::{parse, SUPPORTED_OPERATORS};
+
+    fn parsed(filter: Value) -> Value {
+        Value::from(&parse(&filter).unwrap())
+    }
+
+    fn rejected(filter: Value) -> String {
+        parse(&filter).err().unwrap().pointer
+    }
+
+    fn name() -> Value {
+        json!({"property": "name"})
+    }
+
+    #[test]
+    fn parses_every_advertised_operator() {
+        let property = json!({"property": "tags"});
+        let point = json!({"type": "Point", "coordinates": [1, 2]});
+
+        let examples = [
+            json!({"op": "and", "args": [{"op": "=", "args": [name(), "a"]}]}),
+            json!({"op": "or", "args": [{"op": "=", "args": [name(), "a"]}]}),
+            json!({"op": "not", "args": [{"op": "=", "args": [name(), "a"]}]}),
+            json!({"op": "=", "args": [name(), "a"]}),
+            json!({"op": "<>", "args": [name(), "a"]}),
+            json!({"op": "<", "args": [name(), 1]}),
+            json!({"op": "<=", "args": [name(), 1]}),
+            json!({"op": ">", "args": [name(), 1]}),
+            json!({"op": ">=", "args": [name(), 1]}),
+            json!({"op": "like", "args": [name(), "a%"]}),
+            json!({"op": "like", "args": [{"op": "casei", "args": [name()]}, "a%"]}),
+            json!({"op": "between", "args": [name(), 1, 2]}),
+            json!({"op": "in", "args": [name(), ["a", "b"]]}),
+            json!({"op": "isNull", "args": [name()]}),
+            json!({"op": "s_intersects", "args": [{"property": "geometry"}, point]}),
+            json!({"op": "s_within", "args": [{"property": "geometry"}, {"bbox": [0, 0, 1, 1]}]}),
+            json!({"op": "s_contains", "args": [{"property": "geometry"}, point]}),
+            json!({"op": "s_disjoint", "args": [{"property": "geometry"}, point]}),
+            json!({"op": "s_dwithin", "args": [{"property": "geometry"}, point, 10]}),
+            json!({"op": "a_equals", "args": [property, ["a"]]}),
+            json!({"op": "a_contains", "args": [property, ["a"]]}),
+            json!({"op": "a_containedby", "args": [property, ["a"]]}),
+            json!({"op": "a_overlaps", "args": [property, ["a"]]}),
+        ];
+
+        for example in &examples {
+            assert!(parse(example).is_ok(), "{example} was rejected");
+        }
+
+        let mut used: Vec<String> = examples.iter().map(|example| example["op"].as_str().unwrap().to_lowercase()).collect();
+        used.push("casei".to_string());
+        used.sort();
+        used.dedup();
+
+        let mut advertised: Vec<String> = SUPPORTED_OPERATORS.iter().map(|op| op.to_lowercase()).collect();
+        advertised.sort();
+
+        assert_eq!(used, advertised);
+    }
+
+    #[test]
+    fn mirrors_literal_first_comparisons() {
+        assert_eq!(parsed(json!({"op": "<", "args": [1000, {"property": "population"}]})),
+            json!({"type": "greater_than", "field": "population", "value": 1000}));
+    }
+
+    #[test]
+    fn compares_casei_values_with_ilike() {
+        assert_eq!(parsed(json!({"op": "=", "args": [{"op": "casei", "args": [name()]}, {"op": "casei", "args": ["Sao*"]}]})),
+            json!({"type": "ilike", "field": "name", "value": "Sao\\*"}));
+
+        assert_eq!(parsed(json!({"op": "<>", "args": [{"casei": name()}, "recife"]})),
+            json!({"type": "not", "expression": {"type": "ilike", "field": "name", "value": "recife"}}));
+
+        assert_eq!(parsed(json!({"op": "like", "args": [name(), {"op": "casei", "args": ["s_o%"]}]})),
+            json!({"type": "ilike", "field": "name", "value": "s?o*"}));
+    }
+
+    #[test]
+    fn rejects_unsupported_arguments() {
+        assert_eq!(rejected(json!({"op": "=", "args": [name(), {"property": "label"}]})), "/args");
+        assert_eq!(rejected(json!({"op": "=", "args": [name(), {"op": "+", "args": [1, 2]}]})), "/args/1");
+        assert_eq!(rejected(json!({"op": "between", "args": [name(), 1, {"timestamp": "2024-01-01T00:00:00Z"}]})), "/args/2");
+        assert_eq!(rejected(json!({"op": "in", "args": [name(), ["a", {"property": "b"}]]})), "/args/1/1");
+        assert_eq!(rejected(json!({"op": "a_contains", "args": [name(), [{"property": "b"}]]})), "/args/1/0");
+        assert_eq!(rejected(json!({"op": ">", "args": [{"op": "casei", "args": [name()]}, "a"]})), "/op");
+        assert_eq!(rejected(json!({"op": "casei", "args": [name()]})), "/op");
+        assert_eq!(rejected(json!({"op": "=", "args": [1, 2]})), "/args/0/property");
+    }
+
+    #[test]
+    fn keeps_parameter_placeholders() {
+        assert_eq!(parsed(json!({"op": "=", "args": [name(), {"$param": "city"}]})),
+            json!({"type": "equals", "field": "name", "value": {"$param": "city"}}));
+    }
+}
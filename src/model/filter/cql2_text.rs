@@ -0,0 +1,793 @@
+/*
+    Copyright (c)  Abraão Isvi <avraamisvi@users.noreply.github.com>
+
+    Permission is hereby granted, free of charge, to any
+    person obtaining a copy of this software and associated
+    documentation files (the "Software"), to deal in the
+    Software without restriction, including without
+    limitation the rights to use, copy, modify, merge,
+    publish, distribute, sublicense, and/or sell copies of
+    the Software, and to permit persons to whom the Software
+    is furnished to do so, subject to the following
+    conditions:
+
+    The above copyright notice and this permission notice
+    shall be included in all copies or substantial portions
+    of the Software.
+
+    THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF
+    ANY KIND, EXPRESS OR IMPLIED, INCLUDING BUT NOT LIMITED
+    TO THE WARRANTIES OF MERCHANTABILITY, FITNESS FOR A
+    PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT
+    SHALL THE AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY
+    CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER IN AN ACTION
+    OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR
+    IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+    DEALINGS IN THE SOFTWARE.
+ */
+
+/*!
+ * Parser for the CQL2-Text encoding (OGC API Features Part 3), the supported subset is:
+ *
+ * - comparisons `=`, `<>`, `<`, `<=`, `>`, `>=` between a property and a literal
+ * - `LIKE` (optionally with `CASEI`), `BETWEEN`, `IN` and `IS NULL`, all of them negatable with `NOT`
+ * - `S_INTERSECTS`, `S_WITHIN`, `S_CONTAINS`, `S_DISJOINT` and `S_DWITHIN` against WKT or `BBOX` literals
+ * - `A_EQUALS`, `A_CONTAINS`, `A_CONTAINEDBY` and `A_OVERLAPS` against array literals such as `('a', 'b')`
+ * - `AND`, `OR`, `NOT` and parentheses
+ */
+
+use std::{fmt::Display, error::Error};
+
+use serde_json::json;
+
+use super::{Expression, Element, ElementType, GeometryOperand};
+use super::builder::{prop, intersects, within, contains, disjoint, dwithin};
+
+/// Syntax error found while parsing, `position` is the character offset (starting at 0)
+/// where the problem was detected.
+#[derive(Debug)]
+pub struct Cql2TextError {
+    pub position: usize,
+    pub message: String
+}
+
+impl Display for Cql2TextError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} at position {}", self.message, self.position)
+    }
+}
+
+impl Error for Cql2TextError {}
+
+pub fn parse(text: &str) -> Result<Expression, Cql2TextError> {
+    let tokens = tokenize(text)?;
+    let mut parser = Parser { tokens, current: 0 };
+
+    let expression = parser.parse_or()?;
+    let trailing = parser.peek();
+
+    match trailing.kind {
+        TokenKind::End => Ok(expression),
+        _ => Err(error_at(trailing, "Unexpected token after the end of the expression"))
+    }
+}
+
+#[derive(Clone, PartialEq)]
+enum TokenKind {
+    Identifier(String),
+    QuotedIdentifier(String),
+    Number(String),
+    Text(String),
+    Operator(&'static str),
+    LeftParen,
+    RightParen,
+    Comma,
+    End
+}
+
+#[derive(Clone)]
+struct Token {
+    kind: TokenKind,
+    position: usize
+}
+
+fn tokenize(text: &str) -> Result<Vec<Token>, Cql2TextError> {
+    let chars: Vec<char> = text.chars().collect();
+    let mut tokens = vec![];
+    let mut index = 0;
+
+    while index < chars.len() {
+        let current = chars[index];
+        let start = index;
+
+        if current.is_whitespace() {
+            index += 1;
+            continue;
+        }
+
+        let kind = match current {
+            '(' => { index += 1; TokenKind::LeftParen },
+            ')' => { index += 1; TokenKind::RightParen },
+            ',' => { index += 1; TokenKind::Comma },
+            '=' => { index += 1; TokenKind::Operator("=") },
+            '<' | '>' => {
+                let next = chars.get(index + 1).copied();
+                let operator = match (current, next) {
+                    ('<', Some('>')) => "<>",
+                    ('<', Some('=')) => "<=",
+                    ('>', Some('=')) => ">=",
+                    ('<', _) => "<",
+                    _ => ">"
+                };
+                index += operator.len();
+                TokenKind::Operator(operator)
+            },
+            '\'' => {
+                let mut value = String::new();
+                index += 1;
+                loop {
+                    match chars.get(index) {
+                        Some('\'') if chars.get(index + 1) == Some(&'\'') => {
+                            value.push('\'');
+                            index += 2;
+                        },
+                        Some('\'') => {
+                            index += 1;
+                            break;
+                        },
+                        Some(other) => {
+                            value.push(*other);
+                            index += 1;
+                        },
+                        None => return Err(Cql2TextError { position: start, message: "Unterminated string literal".to_string() })
+                    }
+                }
+                TokenKind::Text(value)
+            },
+            '"' => {
+                let mut value = String::new();
+                index += 1;
+                loop {
+                    match chars.get(index) {
+                        Some('"') => {
+                            index += 1;
+                            break;
+                        },
+                        Some(other) => {
+                            value.push(*other);
+                            index += 1;
+                        },
+                        None => return Err(Cql2TextError { position: start, message: "Unterminated quoted property name".to_string() })
+                    }
+                }
+                TokenKind::QuotedIdentifier(value)
+            },
+            _ if is_number_start(current, chars.get(index + 1).copied()) => {
+                index += 1;
+                while index < chars.len() && (chars[index].is_ascii_digit() || chars[index] == '.'
+                    || ((chars[index] == 'e' || chars[index] == 'E') && index + 1 < chars.len())
+                    || ((chars[index] == '-' || chars[index] == '+') && matches!(chars[index - 1], 'e' | 'E'))) {
+                    index += 1;
+                }
+                let number: String = chars[start..index].iter().collect();
+                if number.parse::<f64>().is_err() {
+                    return Err(Cql2TextError { position: start, message: format!("Invalid number {number}") });
+                }
+                TokenKind::Number(normalize_number(&number))
+            },
+            _ if current.is_alphabetic() || current == '_' => {
+                while index < chars.len() && (chars[index].is_alphanumeric() || matches!(chars[index], '_' | '.' | ':')) {
+                    index += 1;
+                }
+                TokenKind::Identifier(chars[start..index].iter().collect())
+            },
+            other => return Err(Cql2TextError { position: start, message: format!("Unexpected character '{other}'") })
+        };
+
+        tokens.push(Token { kind, position: start });
+    }
+
+    tokens.push(Token { kind: TokenKind::End, position: chars.len() });
+
+    Ok(tokens)
+}
+
+//numbers end up in JSON as well as in SQL, so they are kept in the JSON notation
+fn normalize_number(number: &str) -> String {
+    let (sign, digits) = match number.chars().next() {
+        Some('-') => ("-", &number[1..]),
+        Some('+') => ("", &number[1..]),
+        _ => ("", number)
+    };
+
+    let digits = if digits.starts_with('.') { format!("0{digits}") } else { digits.to_string() };
+
+    //JSON wants a digit after the decimal point, so `5.` is written `5.0`
+    match digits.find('.') {
+        Some(point) if !digits[point + 1..].starts_with(|next: char| next.is_ascii_digit()) => {
+            format!("{sign}{}.0{}", &digits[..point], &digits[point + 1..])
+        },
+        _ => format!("{sign}{digits}")
+    }
+}
+
+fn is_number_start(current: char, next: Option<char>) -> bool {
+    let next_is_digit = next.map(|next| next.is_ascii_digit() || next == '.').unwrap_or(false);
+
+    current.is_ascii_digit() || ((current == '-' || current == '+' || current == '.') && next_is_digit)
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    current: usize
+}
+
+impl Parser {
+
+    fn peek(&self) -> &Token {
+        &self.tokens[self.current]
+    }
+
+    fn advance(&mut self) -> Token {
+        let token = self.tokens[self.current].clone();
+        if token.kind != TokenKind::End {
+            self.current += 1;
+        }
+        token
+    }
+
+    fn peek_keyword(&self, keyword: &str) -> bool {
+        matches!(&self.peek().kind, TokenKind::Identifier(name) if name.eq_ignore_ascii_case(keyword))
+    }
+
+    fn accept_keyword(&mut self, keyword: &str) -> bool {
+        let found = self.peek_keyword(keyword);
+        if found {
+            self.advance();
+        }
+        found
+    }
+
+    fn expect_keyword(&mut self, keyword: &str) -> Result<(), Cql2TextError> {
+        if self.accept_keyword(keyword) {
+            Ok(())
+        } else {
+            Err(error_at(self.peek(), &format!("Expected {keyword}")))
+        }
+    }
+
+    fn expect(&mut self, kind: TokenKind, description: &str) -> Result<(), Cql2TextError> {
+        if self.peek().kind == kind {
+            self.advance();
+            Ok(())
+        } else {
+            Err(error_at(self.peek(), &format!("Expected {description}")))
+        }
+    }
+
+    fn parse_or(&mut self) -> Result<Expression, Cql2TextError> {
+        let mut expression = self.parse_and()?;
+
+        while self.accept_keyword("OR") {
+            expression = expression.or(self.parse_and()?);
+        }
+
+        Ok(expression)
+    }
+
+    fn parse_and(&mut self) -> Result<Expression, Cql2TextError> {
+        let mut expression = self.parse_not()?;
+
+        while self.accept_keyword("AND") {
+            expression = expression.and(self.parse_not()?);
+        }
+
+        Ok(expression)
+    }
+
+    fn parse_not(&mut self) -> Result<Expression, Cql2TextError> {
+        if self.accept_keyword("NOT") {
+            Ok(!self.parse_not()?)
+        } else {
+            self.parse_primary()
+        }
+    }
+
+    fn parse_primary(&mut self) -> Result<Expression, Cql2TextError> {
+        let token = self.peek().clone();
+
+        match &token.kind {
+            TokenKind::LeftParen => {
+                self.advance();
+                let expression = self.parse_or()?;
+                self.expect(TokenKind::RightParen, "')'")?;
+                Ok(expression)
+            },
+            TokenKind::Identifier(name) if is_spatial_function(name) => self.parse_spatial_predicate(),
+            TokenKind::Identifier(name) if is_array_function(name) => self.parse_array_predicate(),
+            TokenKind::Identifier(_) | TokenKind::QuotedIdentifier(_) => self.parse_property_predicate(),
+            TokenKind::End => Err(error_at(&token, "Unexpected end of the expression")),
+            _ => Err(error_at(&token, "Expected a property name, a spatial function or '('"))
+        }
+    }
+
+    fn parse_property(&mut self) -> Result<(String, bool), Cql2TextError> {
+        let token = self.advance();
+
+        match &token.kind {
+            TokenKind::Identifier(name) if name.eq_ignore_ascii_case("CASEI") => {
+                self.expect(TokenKind::LeftParen, "'(' after CASEI")?;
+                let (property, _) = self.parse_property()?;
+                self.expect(TokenKind::RightParen, "')'")?;
+                Ok((property, true))
+            },
+            TokenKind::Identifier(name) if is_reserved(name) => Err(error_at(&token, &format!("Expected a property name, found {name}"))),
+            TokenKind::Identifier(name) | TokenKind::QuotedIdentifier(name) => Ok((name.clone(), false)),
+            _ => Err(error_at(&token, "Expected a property name"))
+        }
+    }
+
+    fn parse_property_predicate(&mut self) -> Result<Expression, Cql2TextError> {
+        let (field, case_insensitive) = self.parse_property()?;
+        let token = self.peek().clone();
+
+        if let TokenKind::Operator(operator) = token.kind {
+            self.advance();
+            let value = self.parse_literal()?;
+
+            return Ok(match operator {
+                "=" => prop(&field).eq(value),
+                "<>" => prop(&field).ne(value),
+                "<" => prop(&field).lt(value),
+                "<=" => prop(&field).le(value),
+                ">" => prop(&field).gt(value),
+                _ => prop(&field).ge(value),
+            });
+        }
+
+        if self.accept_keyword("IS") {
+            let negated = self.accept_keyword("NOT");
+            self.expect_keyword("NULL")?;
+            let expression = prop(&field).is_null();
+
+            return Ok(if negated { !expression } else { expression });
+        }
+
+        let negated = self.accept_keyword("NOT");
+
+        let expression = if self.accept_keyword("LIKE") {
+            let pattern = like_pattern(&self.parse_text(case_insensitive)?);
+
+            if case_insensitive {
+                prop(&field).ilike(&pattern)
+            } else {
+                prop(&field).like(&pattern)
+            }
+        } else if self.accept_keyword("BETWEEN") {
+            let lower = self.parse_literal()?;
+            self.expect_keyword("AND")?;
+            let upper = self.parse_literal()?;
+
+            prop(&field).ge(lower).and(prop(&field).le(upper))
+        } else if self.accept_keyword("IN") {
+            self.expect(TokenKind::LeftParen, "'(' after IN")?;
+            let mut expression = prop(&field).eq(self.parse_literal()?);
+
+            while self.peek().kind == TokenKind::Comma {
+                self.advance();
+                expression = expression.or(prop(&field).eq(self.parse_literal()?));
+            }
+
+            self.expect(TokenKind::RightParen, "')'")?;
+            expression
+        } else {
+            return Err(error_at(self.peek(), "Expected a comparison operator, LIKE, BETWEEN, IN or IS"));
+        };
+
+        Ok(if negated { !expression } else { expression })
+    }
+
+    fn parse_text(&mut self, case_insensitive: bool) -> Result<String, Cql2TextError> {
+        if case_insensitive && self.accept_keyword("CASEI") {
+            self.expect(TokenKind::LeftParen, "'(' after CASEI")?;
+            let text = self.parse_text(false)?;
+            self.expect(TokenKind::RightParen, "')'")?;
+            return Ok(text);
+        }
+
+        let token = self.advance();
+
+        match token.kind {
+            TokenKind::Text(text) => Ok(text),
+            _ => Err(error_at(&token, "Expected a string literal"))
+        }
+    }
+
+    fn parse_literal(&mut self) -> Result<Element, Cql2TextError> {
+        let token = self.advance();
+
+        match token.kind {
+            TokenKind::Text(value) => Ok(Element { name: "".to_string(), value, e_type: ElementType::String }),
+            TokenKind::Number(value) => Ok(Element { name: "".to_string(), value, e_type: ElementType::Number }),
+            TokenKind::Identifier(name) if name.eq_ignore_ascii_case("TRUE") || name.eq_ignore_ascii_case("FALSE") => {
+                Ok(Element { name: "".to_string(), value: name.to_lowercase(), e_type: ElementType::Boolean })
+            },
+            _ => Err(error_at(&token, "Expected a string, numeric or boolean literal"))
+        }
+    }
+
+    fn parse_array_literal(&mut self) -> Result<Vec<Element>, Cql2TextError> {
+        self.expect(TokenKind::LeftParen, "'(' starting an array")?;
+
+        let values = if self.peek().kind == TokenKind::RightParen {
+            vec![]
+        } else {
+            self.parse_list(|parser| parser.parse_literal())?
+        };
+
+        self.expect(TokenKind::RightParen, "')'")?;
+        Ok(values)
+    }
+
+    fn parse_array_predicate(&mut self) -> Result<Expression, Cql2TextError> {
+        let function = self.advance();
+        let name = match &function.kind {
+            TokenKind::Identifier(name) => name.to_uppercase(),
+            _ => unreachable!()
+        };
+
+        self.expect(TokenKind::LeftParen, "'('")?;
+        let (field, _) = self.parse_property()?;
+        self.expect(TokenKind::Comma, "','")?;
+        let values = self.parse_array_literal()?;
+        self.expect(TokenKind::RightParen, "')'")?;
+
+        if name == "A_OVERLAPS" {
+            return Ok(prop(&field).any_of(values));
+        }
+
+        Ok(match name.as_str() {
+            "A_EQUALS" => prop(&field).eq(values),
+            "A_CONTAINS" => prop(&field).contains(values),
+            _ => prop(&field).contained_by(values),
+        })
+    }
+
+    fn parse_number(&mut self) -> Result<f64, Cql2TextError> {
+        let token = self.advance();
+
+        match &token.kind {
+            TokenKind::Number(value) => Ok(value.parse::<f64>().unwrap()),
+            _ => Err(error_at(&token, "Expected a number"))
+        }
+    }
+
+    fn parse_spatial_predicate(&mut self) -> Result<Expression, Cql2TextError> {
+        let function = self.advance();
+        let name = match &function.kind {
+            TokenKind::Identifier(name) => name.to_uppercase(),
+            _ => unreachable!()
+        };
+
+        self.expect(TokenKind::LeftParen, "'('")?;
+        let first = self.parse_spatial_argument()?;
+        self.expect(TokenKind::Comma, "','")?;
+        let second = self.parse_spatial_argument()?;
+
+        let distance = if name == "S_DWITHIN" {
+            self.expect(TokenKind::Comma, "','")?;
+            Some(self.parse_number()?)
+        } else {
+            None
+        };
+
+        self.expect(TokenKind::RightParen, "')'")?;
+
+        //one side must be the feature geometry, the other a literal, when the literal comes first
+        //the predicate is mirrored so the feature geometry is always the left operand
+        let (geometry, mirrored) = match (first, second) {
+            (SpatialArgument::Property, SpatialArgument::Literal(geometry)) => (geometry, false),
+            (SpatialArgument::Literal(geometry), SpatialArgument::Property) => (geometry, true),
+            _ => return Err(error_at(&function, &format!("{name} must compare the feature geometry with a geometry literal")))
+        };
+
+        Ok(match (name.as_str(), mirrored) {
+            ("S_INTERSECTS", _) => intersects(geometry),
+            ("S_DISJOINT", _) => disjoint(geometry),
+            ("S_WITHIN", false) | ("S_CONTAINS", true) => within(geometry),
+            ("S_CONTAINS", false) | ("S_WITHIN", true) => contains(geometry),
+            _ => dwithin(geometry, distance.unwrap()),
+        })
+    }
+
+    fn parse_spatial_argument(&mut self) -> Result<SpatialArgument, Cql2TextError> {
+        let token = self.peek().clone();
+
+        match &token.kind {
+            TokenKind::Identifier(name) if is_geometry_literal(name) => {
+                Ok(SpatialArgument::Literal(GeometryOperand(self.parse_geometry()?.to_string())))
+            },
+            TokenKind::Identifier(_) | TokenKind::QuotedIdentifier(_) => {
+                self.parse_property()?;
+                Ok(SpatialArgument::Property)
+            },
+            _ => Err(error_at(&token, "Expected a geometry literal or a property name"))
+        }
+    }
+
+    fn parse_geometry(&mut self) -> Result<serde_json::Value, Cql2TextError> {
+        let token = self.advance();
+        let name = match &token.kind {
+            TokenKind::Identifier(name) => name.to_uppercase(),
+            _ => return Err(error_at(&token, "Expected a geometry literal"))
+        };
+
+        match name.as_str() {
+            "POINT" => {
+                let coordinates = self.parse_wrapped(|parser| parser.parse_position())?;
+                Ok(json!({"type": "Point", "coordinates": coordinates}))
+            },
+            "LINESTRING" => {
+                let coordinates = self.parse_wrapped(|parser| parser.parse_positions())?;
+                Ok(json!({"type": "LineString", "coordinates": coordinates}))
+            },
+            "POLYGON" => {
+                let coordinates = self.parse_wrapped(|parser| parser.parse_rings())?;
+                Ok(json!({"type": "Polygon", "coordinates": coordinates}))
+            },
+            "MULTIPOINT" => {
+                let coordinates = self.parse_wrapped(|parser| parser.parse_list(|parser| {
+                    if parser.peek().kind == TokenKind::LeftParen {
+                        parser.parse_wrapped(|parser| parser.parse_position())
+                    } else {
+                        parser.parse_position()
+                    }
+                }))?;
+                Ok(json!({"type": "MultiPoint", "coordinates": coordinates}))
+            },
+            "MULTILINESTRING" => {
+                let coordinates = self.parse_wrapped(|parser| parser.parse_rings())?;
+                Ok(json!({"type": "MultiLineString", "coordinates": coordinates}))
+            },
+            "MULTIPOLYGON" => {
+                let coordinates = self.parse_wrapped(|parser| parser.parse_list(|parser| {
+                    parser.parse_wrapped(|parser| parser.parse_rings())
+                }))?;
+                Ok(json!({"type": "MultiPolygon", "coordinates": coordinates}))
+            },
+            "GEOMETRYCOLLECTION" => {
+                let geometries = self.parse_wrapped(|parser| parser.parse_list(|parser| parser.parse_geometry()))?;
+                Ok(json!({"type": "GeometryCollection", "geometries": geometries}))
+            },
+            "BBOX" | "ENVELOPE" => {
+                let values = self.parse_wrapped(|parser| parser.parse_list(|parser| parser.parse_number()))?;
+
+                if values.len() != 4 {
+                    return Err(error_at(&token, &format!("{name} expects 4 coordinates")));
+                }
+
+                Ok(bbox_polygon(&values))
+            },
+            _ => Err(error_at(&token, &format!("Unsupported geometry type {name}")))
+        }
+    }
+
+    fn parse_wrapped<T>(&mut self, inner: impl Fn(&mut Parser) -> Result<T, Cql2TextError>) -> Result<T, Cql2TextError> {
+        self.expect(TokenKind::LeftParen, "'('")?;
+        let value = inner(self)?;
+        self.expect(TokenKind::RightParen, "')'")?;
+        Ok(value)
+    }
+
+    fn parse_list<T>(&mut self, item: impl Fn(&mut Parser) -> Result<T, Cql2TextError>) -> Result<Vec<T>, Cql2TextError> {
+        let mut items = vec![item(self)?];
+
+        while self.peek().kind == TokenKind::Comma {
+            self.advance();
+            items.push(item(self)?);
+        }
+
+        Ok(items)
+    }
+
+    fn parse_position(&mut self) -> Result<Vec<f64>, Cql2TextError> {
+        let mut position = vec![self.parse_number()?, self.parse_number()?];
+
+        if let TokenKind::Number(_) = self.peek().kind {
+            position.push(self.parse_number()?);
+        }
+
+        Ok(position)
+    }
+
+    fn parse_positions(&mut self) -> Result<Vec<Vec<f64>>, Cql2TextError> {
+        self.parse_list(|parser| parser.parse_position())
+    }
+
+    fn parse_rings(&mut self) -> Result<Vec<Vec<Vec<f64>>>, Cql2TextError> {
+        self.parse_list(|parser| parser.parse_wrapped(|parser| parser.parse_positions()))
+    }
+}
+
+enum SpatialArgument {
+    Property,
+    Literal(GeometryOperand)
+}
+
+fn error_at(token: &Token, message: &str) -> Cql2TextError {
+    Cql2TextError { position: token.position, message: message.to_string() }
+}
+
+fn is_spatial_function(name: &str) -> bool {
+    ["S_INTERSECTS", "S_WITHIN", "S_CONTAINS", "S_DISJOINT", "S_DWITHIN"].iter()
+        .any(|function| function.eq_ignore_ascii_case(name))
+}
+
+fn is_array_function(name: &str) -> bool {
+    ["A_EQUALS", "A_CONTAINS", "A_CONTAINEDBY", "A_OVERLAPS"].iter()
+        .any(|function| function.eq_ignore_ascii_case(name))
+}
+
+fn is_geometry_literal(name: &str) -> bool {
+    ["POINT", "LINESTRING", "POLYGON", "MULTIPOINT", "MULTILINESTRING", "MULTIPOLYGON",
+        "GEOMETRYCOLLECTION", "BBOX", "ENVELOPE"].iter()
+        .any(|geometry| geometry.eq_ignore_ascii_case(name))
+}
+
+fn is_reserved(name: &str) -> bool {
+    ["AND", "OR", "NOT", "LIKE", "BETWEEN", "IN", "IS", "NULL", "TRUE", "FALSE"].iter()
+        .any(|keyword| keyword.eq_ignore_ascii_case(name))
+}
+
+pub(super) fn bbox_polygon(values: &[f64]) -> serde_json::Value {
+    let (min_x, min_y, max_x, max_y) = (values[0], values[1], values[2], values[3]);
+    json!({"type": "Polygon", "coordinates": [[[min_x, min_y], [max_x, min_y], [max_x, max_y], [min_x, max_y], [min_x, min_y]]]})
+}
+
+//CQL2 patterns use % and _ as wildcards, the filter model uses * and ?
+pub(super) fn like_pattern(pattern: &str) -> String {
+    let mut translated = String::with_capacity(pattern.len());
+    let mut chars = pattern.chars();
+
+    while let Some(current) = chars.next() {
+        match current {
+            '%' => translated.push('*'),
+            '_' => translated.push('?'),
+            '\\' => {
+                if let Some(escaped) = chars.next() {
+                    translated.push('\\');
+                    translated.push(escaped);
+                }
+            },
+            '*' | '?' => {
+                translated.push('\\');
+                translated.push(current);
+            },
+            other => translated.push(other),
+        }
+    }
+
+    translated
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::{json, Value};
+
+    use super::parse;
+
+    fn parsed(text: &str) -> Value {
+        Value::from(&parse(text).unwrap())
+    }
+
+    fn equals(field: &str, value: Value) -> Value {
+        json!({"type": "equals", "field": field, "value": value})
+    }
+
+    fn error_position(text: &str) -> usize {
+        parse(text).err().unwrap().position
+    }
+
+    #[test]
+    fn parses_comparisons() {
+        assert_eq!(parsed("name = 'Salvador'"), equals("name", json!("Salvador")));
+        assert_eq!(parsed("population >= 1000"), json!({"type": "greater_than_or_equals", "field": "population", "value": 1000}));
+        assert_eq!(parsed("\"area km2\" <> 2.5"), json!({"type": "not_equals", "field": "area km2", "value": 2.5}));
+        assert_eq!(parsed("capital = TRUE"), equals("capital", json!(true)));
+        assert_eq!(parsed("name = 'Feira de Sant''Ana'"), equals("name", json!("Feira de Sant'Ana")));
+    }
+
+    #[test]
+    fn writes_numbers_as_json() {
+        assert_eq!(parsed("a = 5."), equals("a", json!(5.0)));
+        assert_eq!(parsed("a = -.5"), equals("a", json!(-0.5)));
+        assert_eq!(parsed("a = +5.e2"), equals("a", json!(500.0)));
+        assert_eq!(parsed("a = 1e3"), equals("a", json!(1000.0)));
+    }
+
+    #[test]
+    fn and_binds_tighter_than_or() {
+        assert_eq!(parsed("a = 1 OR b = 2 AND c = 3"), json!({"type": "or", "expressions": [
+            equals("a", json!(1)),
+            {"type": "and", "expressions": [equals("b", json!(2)), equals("c", json!(3))]}
+        ]}));
+
+        assert_eq!(parsed("(a = 1 OR b = 2) AND c = 3"), json!({"type": "and", "expressions": [
+            {"type": "or", "expressions": [equals("a", json!(1)), equals("b", json!(2))]},
+            equals("c", json!(3))
+        ]}));
+    }
+
+    #[test]
+    fn not_applies_to_the_next_predicate() {
+        assert_eq!(parsed("NOT a = 1 AND b = 2"), json!({"type": "and", "expressions": [
+            {"type": "not", "expression": equals("a", json!(1))},
+            equals("b", json!(2))
+        ]}));
+
+        assert_eq!(parsed("not not a = 1"), json!({"type": "not", "expression": {"type": "not", "expression": equals("a", json!(1))}}));
+    }
+
+    #[test]
+    fn parses_like() {
+        assert_eq!(parsed("name LIKE 'S_o%'"), json!({"type": "like", "field": "name", "value": "S?o*"}));
+        assert_eq!(parsed("name LIKE '100\\%*'"), json!({"type": "like", "field": "name", "value": "100\\%\\*"}));
+        assert_eq!(parsed("CASEI(name) LIKE CASEI('s%')"), json!({"type": "ilike", "field": "name", "value": "s*"}));
+        assert_eq!(parsed("name NOT LIKE 'S%'"), json!({"type": "not", "expression": {"type": "like", "field": "name", "value": "S*"}}));
+    }
+
+    #[test]
+    fn parses_between_in_and_is_null() {
+        assert_eq!(parsed("population BETWEEN 10 AND 20"), json!({"type": "and", "expressions": [
+            {"type": "greater_than_or_equals", "field": "population", "value": 10},
+            {"type": "less_than_or_equals", "field": "population", "value": 20}
+        ]}));
+
+        assert_eq!(parsed("state IN ('BA', 'PE')"), json!({"type": "or", "expressions": [
+            equals("state", json!("BA")), equals("state", json!("PE"))
+        ]}));
+
+        assert_eq!(parsed("state NOT IN ('SP')"), json!({"type": "not", "expression": equals("state", json!("SP"))}));
+        assert_eq!(parsed("name IS NOT NULL"), json!({"type": "not", "expression": {"type": "is_null", "field": "name"}}));
+    }
+
+    #[test]
+    fn parses_spatial_predicates() {
+        assert_eq!(parsed("S_INTERSECTS(geometry, POINT(-38.5 -12.9))"),
+            json!({"type": "intersects", "geometry": {"type": "Point", "coordinates": [-38.5, -12.9]}}));
+
+        assert_eq!(parsed("S_WITHIN(geometry, BBOX(-39, -13, -38, -12))"), json!({"type": "within", "geometry": {"type": "Polygon",
+            "coordinates": [[[-39.0, -13.0], [-38.0, -13.0], [-38.0, -12.0], [-39.0, -12.0], [-39.0, -13.0]]]}}));
+
+        assert_eq!(parsed("S_DWITHIN(geometry, POINT(1 2), 0.5)"),
+            json!({"type": "dwithin", "geometry": {"type": "Point", "coordinates": [1.0, 2.0]}, "distance": 0.5}));
+    }
+
+    #[test]
+    fn mirrors_spatial_predicates_with_the_literal_first() {
+        let polygon = json!({"type": "Polygon", "coordinates": [[[0.0, 0.0], [1.0, 0.0], [1.0, 1.0], [0.0, 0.0]]]});
+
+        assert_eq!(parsed("S_CONTAINS(POLYGON((0 0, 1 0, 1 1, 0 0)), geometry)"), json!({"type": "within", "geometry": polygon}));
+        assert_eq!(parsed("S_WITHIN(POLYGON((0 0, 1 0, 1 1, 0 0)), geometry)"), json!({"type": "contains", "geometry": polygon}));
+    }
+
+    #[test]
+    fn parses_array_predicates() {
+        assert_eq!(parsed("A_CONTAINS(tags, ('bridge'))"), json!({"type": "contains", "field": "tags", "value": ["bridge"]}));
+        assert_eq!(parsed("A_OVERLAPS(tags, ('bridge', 'road'))"), json!({"type": "any_of", "field": "tags", "value": ["bridge", "road"]}));
+    }
+
+    #[test]
+    fn reports_where_the_input_is_malformed() {
+        assert_eq!(error_position("population >"), 12);
+        assert_eq!(error_position("name = 'Salvador"), 7);
+        assert_eq!(error_position("a = 1 b = 2"), 6);
+        assert_eq!(error_position("(a = 1"), 6);
+        assert_eq!(error_position("a = 1.2.3"), 4);
+        assert_eq!(error_position("a # 1"), 2);
+        assert_eq!(error_position("AND = 1"), 0);
+        assert_eq!(error_position("S_INTERSECTS(geometry, geometry)"), 0);
+        assert_eq!(error_position("S_WITHIN(geometry, BBOX(1, 2, 3))"), 19);
+        assert_eq!(error_position(""), 0);
+    }
+}
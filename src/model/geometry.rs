@@ -29,11 +29,13 @@
 use derive_new::new;
 use serde_json::Value;
 
+use super::bbox::Bbox;
 use super::json::Json;
 
 #[enum_dispatch::enum_dispatch]
 pub trait GeometryTrait {
     fn to_geo_json(&self) -> String;
+    fn intersects(&self, bbox: &Bbox) -> bool;
 }
 
 #[enum_dispatch::enum_dispatch(GeometryTrait)]
@@ -58,6 +60,10 @@ impl GeometryTrait for Point {
     fn to_geo_json(&self) -> String {
         format!(r#"{{"type": "Point", "coordinates": [{}, {}] }}"#, self.longitude, self.latitude)
     }
+
+    fn intersects(&self, bbox: &Bbox) -> bool {
+        bbox.contains(self.longitude, self.latitude)
+    }
 } 
 
 //I will use the simplest approach here, through match, since Geometry is very limited
@@ -67,6 +67,15 @@ impl From<Option<i64>> for Id {
     }
 }
 
+impl From<&Id> for Option<i64> {
+    fn from(value: &Id) -> Self {
+        match value {
+            Id::IntId(id) => Some(id.0),
+            Id::None => None,
+        }
+    }
+}
+
 impl Display for Id {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
 
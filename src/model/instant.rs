@@ -0,0 +1,75 @@
+/*
+    Copyright (c)  Abraão Isvi <avraamisvi@users.noreply.github.com>
+
+    Permission is hereby granted, free of charge, to any
+    person obtaining a copy of this software and associated
+    documentation files (the "Software"), to deal in the
+    Software without restriction, including without
+    limitation the rights to use, copy, modify, merge,
+    publish, distribute, sublicense, and/or sell copies of
+    the Software, and to permit persons to whom the Software
+    is furnished to do so, subject to the following
+    conditions:
+
+    The above copyright notice and this permission notice
+    shall be included in all copies or substantial portions
+    of the Software.
+
+    THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF
+    ANY KIND, EXPRESS OR IMPLIED, INCLUDING BUT NOT LIMITED
+    TO THE WARRANTIES OF MERCHANTABILITY, FITNESS FOR A
+    PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT
+    SHALL THE AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY
+    CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER IN AN ACTION
+    OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR
+    IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+    DEALINGS IN THE SOFTWARE.
+ */
+
+use std::fmt::Display;
+
+use time::{Duration, OffsetDateTime, UtcOffset, format_description::{FormatItem, well_known::Rfc3339}, macros::format_description};
+
+//UTC with millisecond precision, so instants compare as text
+static FORMAT: &[FormatItem<'static>] = format_description!("[year]-[month]-[day]T[hour]:[minute]:[second].[subsecond digits:3]Z");
+
+/// A point in time of the feature history or the trash.
+#[derive(Debug, Clone)]
+pub struct Instant(String);
+
+impl Instant {
+    pub fn now() -> Self {
+        Instant::from(OffsetDateTime::now_utc())
+    }
+
+    pub fn days_ago(days: i64) -> Self {
+        Instant::from(OffsetDateTime::now_utc() - Duration::days(days))
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl From<OffsetDateTime> for Instant {
+    fn from(value: OffsetDateTime) -> Self {
+        Instant(value.to_offset(UtcOffset::UTC).format(FORMAT).unwrap_or_default())
+    }
+}
+
+/// Parses an RFC 3339 date-time such as `2024-03-01T12:00:00Z`.
+impl TryFrom<&str> for Instant {
+    type Error = String;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        OffsetDateTime::parse(value, &Rfc3339)
+            .map(Instant::from)
+            .map_err(|err| format!("Invalid date-time {value}: {err}"))
+    }
+}
+
+impl Display for Instant {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
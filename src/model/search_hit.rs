@@ -0,0 +1,86 @@
+/*
+    Copyright (c)  Abraão Isvi <avraamisvi@users.noreply.github.com>
+
+    Permission is hereby granted, free of charge, to any
+    person obtaining a copy of this software and associated
+    documentation files (the "Software"), to deal in the
+    Software without restriction, including without
+    limitation the rights to use, copy, modify, merge,
+    publish, distribute, sublicense, and/or sell copies of
+    the Software, and to permit persons to whom the Software
+    is furnished to do so, subject to the following
+    conditions:
+
+    The above copyright notice and this permission notice
+    shall be included in all copies or substantial portions
+    of the Software.
+
+    THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF
+    ANY KIND, EXPRESS OR IMPLIED, INCLUDING BUT NOT LIMITED
+    TO THE WARRANTIES OF MERCHANTABILITY, FITNESS FOR A
+    PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT
+    SHALL THE AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY
+    CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER IN AN ACTION
+    OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR
+    IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+    DEALINGS IN THE SOFTWARE.
+ */
+
+/*!
+ * Full-text search results. The text language of a collection is read from its
+ * `search_language` property (a Postgres text search configuration such as `english`
+ * or `portuguese`), collections without it, or naming a configuration Postgres does not
+ * have, use `simple`. Only the `simple` text is indexed.
+ */
+
+use derive_new::new;
+
+use super::feature::Feature;
+use super::feature_collection::FeatureCollection;
+use super::geometry::GeometryTrait;
+use super::value::ValueTrait;
+
+/// A feature matching the search with its relevance and the matching text, highlighted with `<mark>`.
+#[derive(new)]
+pub struct SearchHit {
+    pub feature: Feature,
+    pub rank: f32,
+    pub snippet: String
+}
+
+impl SearchHit {
+    pub fn to_geo_json(&self) -> String {
+        let feature = &self.feature;
+
+        format!(r#"{{
+            "type": "Feature",
+            "id": {},
+            "geometry": {},
+            "properties": {},
+            "rank": {},
+            "snippet": {}
+        }}"#, feature.id, feature.geometry.to_geo_json(), &feature.properties.to_geo_json(),
+            self.rank, serde_json::Value::String(self.snippet.clone()))
+    }
+}
+
+/// The hits of a search, best ranked first, in the collection they belong to.
+#[derive(new)]
+pub struct SearchResult {
+    pub collection: FeatureCollection,
+    pub hits: Vec<SearchHit>
+}
+
+impl SearchResult {
+    pub fn to_geo_json(&self) -> String {
+        let hits: Vec<String> = self.hits.iter().map(|hit| hit.to_geo_json()).collect();
+
+        format!(r#"{{
+            "type": "FeatureCollection",
+            "id": {},
+            "label": "{}",
+            "properties": {},
+            "features": [ {} ]
+        }}"#, self.collection.id, self.collection.label, &self.collection.properties.to_geo_json(), hits.join(","))
+    }
+}
@@ -0,0 +1,58 @@
+/*
+    Copyright (c)  Abraão Isvi <avraamisvi@users.noreply.github.com>
+
+    Permission is hereby granted, free of charge, to any
+    person obtaining a copy of this software and associated
+    documentation files (the "Software"), to deal in the
+    Software without restriction, including without
+    limitation the rights to use, copy, modify, merge,
+    publish, distribute, sublicense, and/or sell copies of
+    the Software, and to permit persons to whom the Software
+    is furnished to do so, subject to the following
+    conditions:
+
+    The above copyright notice and this permission notice
+    shall be included in all copies or substantial portions
+    of the Software.
+
+    THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF
+    ANY KIND, EXPRESS OR IMPLIED, INCLUDING BUT NOT LIMITED
+    TO THE WARRANTIES OF MERCHANTABILITY, FITNESS FOR A
+    PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT
+    SHALL THE AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY
+    CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER IN AN ACTION
+    OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR
+    IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+    DEALINGS IN THE SOFTWARE.
+ */
+
+
+use derive_new::new;
+use serde_json::Value;
+
+use super::filter::Filter;
+
+/// A filter saved under a name on a collection.
+#[derive(new)]
+pub struct StoredFilter {
+    pub name: String,
+    pub filter: Filter
+}
+
+impl StoredFilter {
+    pub fn to_json(&self) -> String {
+        format!(r#"{{"name": {}, "filter": {}}}"#, Value::String(self.name.clone()), self.filter.to_json())
+    }
+}
+
+#[derive(new)]
+pub struct StoredFilterList(Vec<StoredFilter>);
+
+impl StoredFilterList {
+
+    pub fn to_json(&self) -> String {
+        let filters: Vec<String> = self.0.iter().map(|filter| filter.to_json()).collect();
+
+        format!("[{}]", filters.join(","))
+    }
+}
@@ -0,0 +1,82 @@
+/*
+    Copyright (c)  Abraão Isvi <avraamisvi@users.noreply.github.com>
+
+    Permission is hereby granted, free of charge, to any
+    person obtaining a copy of this software and associated
+    documentation files (the "Software"), to deal in the
+    Software without restriction, including without
+    limitation the rights to use, copy, modify, merge,
+    publish, distribute, sublicense, and/or sell copies of
+    the Software, and to permit persons to whom the Software
+    is furnished to do so, subject to the following
+    conditions:
+
+    The above copyright notice and this permission notice
+    shall be included in all copies or substantial portions
+    of the Software.
+
+    THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF
+    ANY KIND, EXPRESS OR IMPLIED, INCLUDING BUT NOT LIMITED
+    TO THE WARRANTIES OF MERCHANTABILITY, FITNESS FOR A
+    PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT
+    SHALL THE AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY
+    CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER IN AN ACTION
+    OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR
+    IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+    DEALINGS IN THE SOFTWARE.
+ */
+
+use derive_new::new;
+use serde_json::{json, Value};
+
+/// A soft deleted feature or collection, hidden from every read until it is restored or purged.
+/// A trashed feature keeps the collection it was deleted from.
+#[derive(new)]
+pub struct TrashedItem {
+    pub kind: String,
+    pub id: i64,
+    pub label: Option<String>,
+    pub collection_id: Option<i64>,
+    pub deleted_at: String
+}
+
+impl TrashedItem {
+    pub fn to_json(&self) -> Value {
+        let mut item = json!({"type": self.kind, "id": self.id, "deletedAt": self.deleted_at});
+
+        match (&self.label, self.collection_id) {
+            (Some(label), _) => item["label"] = json!(label),
+            (None, Some(collection_id)) => item["collectionId"] = json!(collection_id),
+            _ => {}
+        }
+
+        item
+    }
+}
+
+/// The trashed items, most recently deleted first.
+#[derive(new)]
+pub struct Trash {
+    pub items: Vec<TrashedItem>
+}
+
+impl Trash {
+    pub fn to_json(&self) -> String {
+        let items: Vec<Value> = self.items.iter().map(TrashedItem::to_json).collect();
+
+        json!({"items": items}).to_string()
+    }
+}
+
+/// What a purge removed for good, the features include those only held by a purged collection.
+#[derive(new)]
+pub struct PurgedTrash {
+    pub features: i64,
+    pub collections: i64
+}
+
+impl PurgedTrash {
+    pub fn to_json(&self) -> String {
+        json!({"purgedFeatures": self.features, "purgedCollections": self.collections}).to_string()
+    }
+}
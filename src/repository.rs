@@ -26,4 +26,10 @@
     DEALINGS IN THE SOFTWARE.
  */
 
-pub mod features_repository;
\ No newline at end of file
+pub mod features_repository;
+pub mod postgres_repository;
+pub mod memory_repository;
+pub mod matcher;
+pub mod sqlite_repository;
+pub mod geopackage_repository;
+pub mod storage;
\ No newline at end of file
@@ -28,18 +28,62 @@
 
 use std::{fmt::Display, error::Error};
 
+use async_trait::async_trait;
 use derive_new::new;
+use futures::stream::BoxStream;
 
-use crate::model::{feature::Feature, feature_collection::FeatureCollection, value::ValueTrait, geometry::GeometryTrait, bbox::Bbox, filter::Filter};
+use crate::model::{feature::Feature, feature_collection::FeatureCollection, bbox::Bbox, filter::Filter,
+    stored_filter::StoredFilter, collection_search::CollectionSearch, search_hit::SearchHit,
+    feature_revision::FeatureRevision, instant::Instant, trash::{TrashedItem, PurgedTrash}};
 
-static GEOXIDATED_SCHEMA: &str = "geoxidated";
-static FEATURE_TABLE: &str = "feature";
-static COLLECTION_TABLE: &str = "features_collection";
-static FEATURES_IN_COLLECTION: &str = "features_in_collection";
-
-#[derive(Debug)]
+#[derive(new, Debug)]
 pub struct FeatureRepositoryError {
-    pub message: String
+    pub message: String,
+    /// The feature, collection or filter asked for does not exist.
+    #[new(default)]
+    pub not_found: bool,
+    /// The feature was changed since the version the write was based on.
+    #[new(default)]
+    pub conflict: bool,
+    /// The write would break a rule of the data, like leaving a feature without a collection.
+    #[new(default)]
+    pub constraint: bool,
+    /// The backend can not do what was asked, like keeping the history of a GeoPackage feature.
+    #[new(default)]
+    pub unsupported: bool
+}
+
+impl FeatureRepositoryError {
+    pub fn not_found(message: String) -> Self {
+        FeatureRepositoryError { message, not_found: true, conflict: false, constraint: false, unsupported: false }
+    }
+
+    pub fn conflict(message: String) -> Self {
+        FeatureRepositoryError { message, not_found: false, conflict: true, constraint: false, unsupported: false }
+    }
+
+    pub fn constraint(message: String) -> Self {
+        FeatureRepositoryError { message, not_found: false, conflict: false, constraint: true, unsupported: false }
+    }
+
+    pub fn unsupported(message: String) -> Self {
+        FeatureRepositoryError { message, not_found: false, conflict: false, constraint: false, unsupported: true }
+    }
+
+    /// Like `From<sqlx::Error>`, but names what was looked up when no row came back.
+    pub fn from_lookup(err: sqlx::Error, what: String) -> Self {
+        match err {
+            sqlx::Error::RowNotFound => FeatureRepositoryError::not_found(format!("{what} not found")),
+            err => FeatureRepositoryError::from(err)
+        }
+    }
+}
+
+impl From<sqlx::Error> for FeatureRepositoryError {
+    fn from(err: sqlx::Error) -> Self {
+        FeatureRepositoryError { not_found: matches!(err, sqlx::Error::RowNotFound), conflict: false, constraint: false, unsupported: false,
+            message: err.to_string() }
+    }
 }
 
 impl Display for FeatureRepositoryError {
@@ -50,291 +94,157 @@ impl Display for FeatureRepositoryError {
 
 impl Error for FeatureRepositoryError {}
 
-#[derive(new)]
-pub struct FeatureRepository {
-    pool: sqlx::postgres::PgPool
+/// Features read while they are imported, an error ends the import.
+pub type FeatureStream<'a> = BoxStream<'a, Result<Feature, BulkInsertError>>;
+
+/// Failure of a bulk insert, `index` is the position of the feature that failed, if one did.
+#[derive(Debug)]
+pub struct BulkInsertError {
+    pub index: Option<usize>,
+    pub message: String
 }
 
-impl FeatureRepository {
-   
-    pub async fn get_features_in_collection(&mut self, id: i64, offset: i64, size: i64) -> Result<Vec<Feature>, FeatureRepositoryError> {
-        
-        let db = &self.pool;
-
-        let query = format!("SELECT id, \
-                                     properties::text,\
-                                     ST_AsGeoJSON(geometry) \
-                                     FROM {GEOXIDATED_SCHEMA}.{FEATURE_TABLE} fa \
-                                     INNER JOIN {GEOXIDATED_SCHEMA}.{FEATURES_IN_COLLECTION} fi \
-                                     ON fi.feature_id = fa.id AND fi.collection_id = {id} LIMIT {size} OFFSET {offset}");
-
-        let result = sqlx::query(&query)
-        .fetch_all(db).await;
-
-        match result {
-            Ok(rows) => {
-                let collections: Vec<Feature> = rows.iter().map(|row| {
-                    Feature::from(row)
-                }).collect();
-
-                Ok(collections)
-            },
-            Err(err) => {
-                println!("DB Error {}", err.to_string());
-                Err(FeatureRepositoryError{message: err.to_string()})
-            }
+impl Display for BulkInsertError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.index {
+            Some(index) => write!(f, "Feature {index} could not be inserted: {}", self.message),
+            None => write!(f, "Features could not be inserted: {}", self.message)
         }
     }
+}
 
-    pub async fn get_features_in_collection_by_bbox(&mut self, collection_id: i64, bbox: &Bbox, offset: i64, size: i64) -> Result<Vec<Feature>, FeatureRepositoryError> {
-        
-        let db = &self.pool;
-
-        let bbox_geom = bbox.to_wkt();
-        let query = format!(r#"SELECT id, \
-                                     properties::text,\
-                                     ST_AsGeoJSON(geometry) \
-                                     FROM {GEOXIDATED_SCHEMA}.{FEATURE_TABLE} fa \
-                                     INNER JOIN {GEOXIDATED_SCHEMA}.{FEATURES_IN_COLLECTION} fi \
-                                     ON fi.feature_id = fa.id AND fi.collection_id = {collection_id}
-                                     WHERE ST_Intersects(ST_GeomFromText('{bbox_geom}'), 4326, fa.geometry) 
-                                     LIMIT {size} OFFSET {offset}"#);
-
-        let result = sqlx::query(&query)
-        .fetch_all(db).await;
-
-        match result {
-            Ok(rows) => {
-                let collections: Vec<Feature> = rows.iter().map(|row| {
-                    Feature::from(row)
-                }).collect();
-
-                Ok(collections)
-            },
-            Err(err) => {
-                println!("DB Error {}", err.to_string());
-                Err(FeatureRepositoryError{message: err.to_string()})
-            }
-        }
-    }
+impl Error for BulkInsertError {}
 
-    pub async fn get_features_in_collection_by_filter(&mut self, collection_id: i64, 
-        filter: &Filter, offset: i64, size: i64) -> Result<Vec<Feature>, FeatureRepositoryError> {
-       todo!() 
-        // let db = &self.pool;
-
-        // let bbox_geom = filter.;
-        // let query = format!(r#"SELECT id, \
-        //                              properties::text,\
-        //                              ST_AsGeoJSON(geometry) \
-        //                              FROM {GEOXIDATED_SCHEMA}.{FEATURE_TABLE} fa \
-        //                              INNER JOIN {GEOXIDATED_SCHEMA}.{FEATURES_IN_COLLECTION} fi \
-        //                              ON fi.feature_id = fa.id AND fi.collection_id = {collection_id}
-        //                              WHERE ST_Intersects(ST_GeomFromText('{bbox_geom}'), 4326, fa.geometry) 
-        //                              LIMIT {size} OFFSET {offset}"#);
-
-        // let result = sqlx::query(&query)
-        // .fetch_all(db).await;
-
-        // match result {
-        //     Ok(rows) => {
-        //         let collections: Vec<Feature> = rows.iter().map(|row| {
-        //             Feature::from(row)
-        //         }).collect();
-
-        //         Ok(collections)
-        //     },
-        //     Err(err) => {
-        //         println!("DB Error {}", err.to_string());
-        //         Err(FeatureRepositoryError{message: err.to_string()})
-        //     }
-        // }
+impl From<FeatureRepositoryError> for BulkInsertError {
+    fn from(err: FeatureRepositoryError) -> Self {
+        BulkInsertError { index: None, message: err.message }
     }
+}
 
-    pub async fn get_feature_by_id(&mut self, feature_id: i64) -> Result<Feature, FeatureRepositoryError> {
-        
-        let db = &self.pool;
-
-        let query = format!(r#"SELECT id,
-                                     properties::text,
-                                     ST_AsGeoJSON(geometry)
-                                     FROM {GEOXIDATED_SCHEMA}.{FEATURE_TABLE} fa
-                                     WHERE fa.id = {feature_id}"#);
-
-        let result = sqlx::query(&query)
-        .fetch_one(db).await;
-
-        match result {
-            Ok(row) => {
-                Ok(Feature::from(&row))
-            },
-            Err(err) => {
-                println!("DB Error {}", err.to_string());
-                Err(FeatureRepositoryError{message: err.to_string()})
-            }
-        }
-    }    
-
-    pub async fn create_feature(&mut self, collection_id: i64, feature: &Feature) -> Result<Feature, FeatureRepositoryError> {
-        
-        let db = &self.pool;
-        let geo_json = feature.geometry.to_geo_json();
-        let properties_json = feature.properties.to_geo_json();
-
-        let query = format!(r#"
-            WITH data(data_properties, data_geometry) AS (
-                VALUES('{properties_json}'::json, ST_GeomFromGeoJSON('{geo_json}'))              
-            )
-            , insert_feature AS (
-                INSERT INTO {GEOXIDATED_SCHEMA}.{FEATURE_TABLE}(properties, geometry)
-                SELECT data_properties, data_geometry
-                FROM   data
-                RETURNING properties, geometry, id AS insert_feature_id
-                )
-            , insert_features_in_col AS (
-                INSERT INTO {GEOXIDATED_SCHEMA}.{FEATURES_IN_COLLECTION}(feature_id, collection_id)
-                SELECT insert_feature.insert_feature_id, {collection_id} FROM insert_feature
-            )
-            SELECT insert_feature.insert_feature_id, insert_feature.properties::text, ST_AsGeoJSON(insert_feature.geometry)
-            FROM   insert_feature
-        "#);
-
-        let result = sqlx::query(&query)
-        .fetch_one(db).await;
-
-        match result {
-            Ok(row) => Ok(Feature::from(&row)),
-            Err(err) => {
-                println!("DB Error {}", err.to_string());
-                Err(FeatureRepositoryError{message: err.to_string()})
-            }
-        }
+/// Fails with a conflict when a write based on `version` would overwrite changes it has not seen, `None` matches any version.
+pub fn check_current_version(feature_id: i64, current: i64, version: Option<i64>) -> Result<(), FeatureRepositoryError> {
+    match version {
+        Some(version) if version != current =>
+            Err(FeatureRepositoryError::conflict(format!("Feature {feature_id} is at version {current}, not {version}"))),
+        _ => Ok(())
     }
+}
 
-    pub async fn update_feature(&mut self, collection_id: i64, feature: &Feature) -> Result<Feature, FeatureRepositoryError> {
-        
-        let db = &self.pool;
-        let geo_json = feature.geometry.to_geo_json();
-        let properties_json = feature.properties.to_geo_json();
-        let feature_id = &feature.id;
-
-
-        let query = format!(r#"
-            WITH data(data_properties, data_geometry) AS (
-                VALUES('{properties_json}'::json, ST_GeomFromGeoJSON('{geo_json}'))              
-            )
-            , update_feature AS (
-                UPDATE {GEOXIDATED_SCHEMA}.{FEATURE_TABLE}
-                    SET properties = '{properties_json}'::json,
-                        geometry = ST_GeomFromGeoJSON('{geo_json}')
-                    WHERE id = {feature_id}
-                RETURNING id, properties, geometry 
-                )
-            , insert_features_in_col AS (
-                INSERT INTO {GEOXIDATED_SCHEMA}.{FEATURES_IN_COLLECTION}(feature_id, collection_id)
-                SELECT update_feature.id, {collection_id} FROM update_feature ON CONFLICT (feature_id, collection_id) DO NOTHING
-            )
-            SELECT update_feature.id, update_feature.properties::text, ST_AsGeoJSON(update_feature.geometry)
-            FROM   update_feature
-        "#);
-
-        let result = sqlx::query(&query)
-        .fetch_one(db).await;
-
-        match result {
-            Ok(row) => Ok(Feature::from(&row)),
-            Err(err) => {
-                println!("DB Error {}", err.to_string());
-                Err(FeatureRepositoryError{message: err.to_string()})
-            }
-        }
+/// The `size` items after the first `offset`, for backends that page in memory.
+pub(crate) fn page<T>(items: impl IntoIterator<Item = T>, offset: i64, size: i64) -> impl Iterator<Item = T> {
+    items.into_iter().skip(offset.max(0) as usize).take(size.max(0) as usize)
+}
+
+/// Maps how many links the feature has, to the collection and in total, to the outcome of a detach.
+pub(crate) fn detached(collection_id: i64, feature_id: i64, (member, total): (i64, i64)) -> Result<(), FeatureRepositoryError> {
+    match (member, total) {
+        (0, _) => Err(FeatureRepositoryError::not_found(format!("Feature {feature_id} not found in collection {collection_id}"))),
+        (_, 1) => Err(FeatureRepositoryError::constraint(format!("Feature {feature_id} only belongs to collection {collection_id}, delete it instead"))),
+        _ => Ok(())
     }
+}
 
-     pub async fn create_collection(&mut self, collection: &FeatureCollection) -> Result<FeatureCollection, FeatureRepositoryError> {
-        
-        let db = &self.pool;
-        let label = &collection.label;
-        let properties = &collection.properties.to_geo_json();
+pub(crate) fn bulk_error(index: Option<usize>, err: sqlx::Error) -> BulkInsertError {
+    BulkInsertError { index, message: err.to_string() }
+}
 
-        let query = format!("INSERT INTO {GEOXIDATED_SCHEMA}.{COLLECTION_TABLE}(label, properties)
-         VALUES('{label}', '{properties}'::json) RETURNING id, label, properties::text");
+/// Storage operations behind `FeatureService`, implemented once per backend.
+#[async_trait]
+pub trait FeatureRepository: Send {
 
-        let result = sqlx::query(&query)
-        .fetch_one(db).await;
+    /// Who the following writes are recorded for in the feature history, `None` when unknown.
+    fn set_editor(&mut self, editor: Option<String>);
 
-        match result {
-            Ok(row) => Ok(FeatureCollection::from(&row)),
-            Err(err) => {
-                println!("DB Error {}", err.to_string());
-                Err(FeatureRepositoryError{message: err.to_string()})
-            }
-        }
-    }
+    async fn get_features_in_collection(&mut self, id: i64, offset: i64, size: i64) -> Result<Vec<Feature>, FeatureRepositoryError>;
 
-    pub async fn update_collection(&mut self, collection: &FeatureCollection) -> Result<FeatureCollection, FeatureRepositoryError> {
-        
-        let db = &self.pool;
-        let label = &collection.label;
-        let properties = &collection.properties.to_geo_json();
-        let id = &collection.id;
+    async fn get_features_in_collection_by_bbox(&mut self, collection_id: i64, bbox: &Bbox, offset: i64, size: i64) -> Result<Vec<Feature>, FeatureRepositoryError>;
 
-        let query = format!(r#"UPDATE {GEOXIDATED_SCHEMA}.{COLLECTION_TABLE}
-        SET label = '{label}', properties = '{properties}'::json
-        WHERE id = {id} RETURNING id, label, properties::text"#);
+    /// Keyset page of the collection, the `size` features with the smallest ids greater than `after`.
+    async fn get_features_in_collection_after(&mut self, id: i64, after: i64, size: i64) -> Result<Vec<Feature>, FeatureRepositoryError>;
 
-        print!("{}", query);
+    async fn get_features_in_collection_by_bbox_after(&mut self, collection_id: i64, bbox: &Bbox, after: i64, size: i64) -> Result<Vec<Feature>, FeatureRepositoryError>;
 
-        let result = sqlx::query(&query)
-        .fetch_one(db).await;
+    async fn get_features_in_collection_by_filter(&mut self, collection_id: i64, filter: &Filter, offset: i64, size: i64) -> Result<Vec<Feature>, FeatureRepositoryError>;
 
-        match result {
-            Ok(row) => Ok(FeatureCollection::from(&row)),
-            Err(err) => {
-                println!("DB Error {}", err.to_string());
-                Err(FeatureRepositoryError{message: err.to_string()})
-            }
-        }
-    }    
-
-    pub async fn get_collections(&mut self, offset: i64, size: i64) -> Result<Vec<FeatureCollection>, FeatureRepositoryError> {
-        
-        let db = &self.pool;
-
-        //NOTE: using deferred join to improve pagination
-        let query = format!("SELECT id, label, properties::text FROM {GEOXIDATED_SCHEMA}.{COLLECTION_TABLE} \
-            INNER JOIN ( SELECT id FROM {GEOXIDATED_SCHEMA}.{COLLECTION_TABLE} LIMIT {size} OFFSET {offset} \
-        ) AS tmp USING(id) ORDER BY id, label");
-
-        let result = sqlx::query(&query)
-        .fetch_all(db).await;
-
-        match result {
-            Ok(rows) => {
-                let collections: Vec<FeatureCollection> = rows.iter().map(|row| {
-                    FeatureCollection::from(row)
-                }).collect();
-
-                Ok(collections)
-            },
-            Err(err) => Err(FeatureRepositoryError{message: err.to_string()})
-        } 
-    }
+    /// Number of features in the collection, `estimated` allows a cheaper approximation where the backend has one.
+    async fn count_features_in_collection(&mut self, id: i64, estimated: bool) -> Result<i64, FeatureRepositoryError>;
 
-    pub async fn get_collection_by_id(&mut self, id: i64) -> Result<FeatureCollection, FeatureRepositoryError> {
-        
-        let db = &self.pool;
+    async fn count_features_in_collection_by_bbox(&mut self, collection_id: i64, bbox: &Bbox, estimated: bool) -> Result<i64, FeatureRepositoryError>;
 
-        let query = format!("SELECT id, label, properties::text FROM {GEOXIDATED_SCHEMA}.{COLLECTION_TABLE} WHERE id = {id}");
+    async fn count_features_in_collection_by_filter(&mut self, collection_id: i64, filter: &Filter, estimated: bool) -> Result<i64, FeatureRepositoryError>;
 
-        let result = sqlx::query(&query)
-        .fetch_one(db).await;
+    /// Ranks the features of the collection against a web search style text (`"exact phrase"`, `or`, `-excluded`),
+    /// only string properties are searched.
+    async fn search_features_in_collection(&mut self, collection_id: i64, text: &str, filter: Option<&Filter>, offset: i64, size: i64) -> Result<Vec<SearchHit>, FeatureRepositoryError>;
 
-        match result {
-            Ok(row) => { Ok(FeatureCollection::from(&row))},
-            Err(err) => Err(FeatureRepositoryError{message: err.to_string()})
-        } 
-    }    
+    async fn get_feature_by_id(&mut self, collection_id: i64, feature_id: i64) -> Result<Feature, FeatureRepositoryError>;
 
-    
-}
+    /// Every revision of a feature that is or was in the collection, oldest first.
+    async fn get_feature_history(&mut self, collection_id: i64, feature_id: i64) -> Result<Vec<FeatureRevision>, FeatureRepositoryError>;
+
+    /// The features the collection held at `as_of`, as they were then, ordered by id.
+    async fn get_features_in_collection_as_of(&mut self, collection_id: i64, as_of: &Instant, offset: i64, size: i64) -> Result<Vec<Feature>, FeatureRepositoryError>;
+
+    async fn create_feature(&mut self, collection_id: i64, feature: &Feature) -> Result<Feature, FeatureRepositoryError>;
+
+    /// Inserts the features in one transaction, none is kept when one fails.
+    /// The error carries the index of the first feature that failed, the later ones are not tried.
+    async fn create_features(&mut self, collection_id: i64, features: &[Feature]) -> Result<Vec<Feature>, BulkInsertError>;
+
+    /// Loads a large stream of features in one transaction and returns how many were imported.
+    /// Unlike `create_features` the features are not read back.
+    async fn import_features(&mut self, collection_id: i64, features: FeatureStream<'_>) -> Result<u64, BulkInsertError>;
+
+    /// Fails with a conflict when the feature is no longer at `feature.version`, any version is updated when it is `None`.
+    async fn update_feature(&mut self, collection_id: i64, feature: &Feature) -> Result<Feature, FeatureRepositoryError>;
+
+    /// Removes the feature from the collection, the feature itself is moved to the trash once no other collection holds it.
+    /// Like `update_feature` it fails with a conflict when the feature is no longer at `version`.
+    async fn delete_feature(&mut self, collection_id: i64, feature_id: i64, version: Option<i64>) -> Result<(), FeatureRepositoryError>;
+
+    /// Adds an existing feature to the collection, nothing changes when it is already there.
+    async fn attach_feature(&mut self, collection_id: i64, feature_id: i64) -> Result<(), FeatureRepositoryError>;
+
+    /// Removes the feature from the collection but keeps it, so it must still belong to another collection.
+    async fn detach_feature(&mut self, collection_id: i64, feature_id: i64) -> Result<(), FeatureRepositoryError>;
+
+    /// The collections holding the feature, ordered by id.
+    async fn get_collections_of_feature(&mut self, feature_id: i64) -> Result<Vec<FeatureCollection>, FeatureRepositoryError>;
 
+    async fn create_collection(&mut self, collection: &FeatureCollection) -> Result<FeatureCollection, FeatureRepositoryError>;
+
+    /// Inserts the collection and its `features` in one transaction.
+    async fn create_collection_with_features(&mut self, collection: &FeatureCollection) -> Result<FeatureCollection, BulkInsertError>;
+
+    async fn update_collection(&mut self, collection: &FeatureCollection) -> Result<FeatureCollection, FeatureRepositoryError>;
+
+    /// Moves the collection to the trash, its stored filters and features are kept until it is purged.
+    async fn delete_collection(&mut self, id: i64) -> Result<(), FeatureRepositoryError>;
+
+    /// The trashed features and collections, most recently deleted first.
+    async fn get_trash(&mut self, offset: i64, size: i64) -> Result<Vec<TrashedItem>, FeatureRepositoryError>;
+
+    /// Takes the feature out of the trash, it shows again once its collection is not trashed either.
+    async fn restore_feature(&mut self, feature_id: i64) -> Result<(), FeatureRepositoryError>;
+
+    async fn restore_collection(&mut self, id: i64) -> Result<(), FeatureRepositoryError>;
+
+    /// Deletes for good what was trashed at or before `before`, with the features only purged collections hold.
+    async fn purge_trash(&mut self, before: &Instant) -> Result<PurgedTrash, FeatureRepositoryError>;
+
+    async fn get_collections(&mut self, offset: i64, size: i64) -> Result<Vec<FeatureCollection>, FeatureRepositoryError>;
+
+    async fn get_collections_after(&mut self, after: i64, size: i64) -> Result<Vec<FeatureCollection>, FeatureRepositoryError>;
+
+    async fn search_collections(&mut self, search: &CollectionSearch, offset: i64, size: i64) -> Result<Vec<FeatureCollection>, FeatureRepositoryError>;
+
+    async fn get_collection_by_id(&mut self, id: i64) -> Result<FeatureCollection, FeatureRepositoryError>;
+
+    async fn save_filter(&mut self, collection_id: i64, name: &str, filter: &Filter) -> Result<StoredFilter, FeatureRepositoryError>;
+
+    async fn get_filter(&mut self, collection_id: i64, name: &str) -> Result<StoredFilter, FeatureRepositoryError>;
+
+    async fn get_filters(&mut self, collection_id: i64) -> Result<Vec<StoredFilter>, FeatureRepositoryError>;
+
+    async fn delete_filter(&mut self, collection_id: i64, name: &str) -> Result<(), FeatureRepositoryError>;
+}
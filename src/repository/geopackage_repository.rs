@@ -0,0 +1,949 @@
+/*
+    Copyright (c)  Abraão Isvi <avraamisvi@users.noreply.github.com>
+
+    Permission is hereby granted, free of charge, to any
+    person obtaining a copy of this software and associated
+    documentation files (the "Software"), to deal in the
+    Software without restriction, including without
+    limitation the rights to use, copy, modify, merge,
+    publish, distribute, sublicense, and/or sell copies of
+    the Software, and to permit persons to whom the Software
+    is furnished to do so, subject to the following
+    conditions:
+
+    The above copyright notice and this permission notice
+    shall be included in all copies or substantial portions
+    of the Software.
+
+    THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF
+    ANY KIND, EXPRESS OR IMPLIED, INCLUDING BUT NOT LIMITED
+    TO THE WARRANTIES OF MERCHANTABILITY, FITNESS FOR A
+    PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT
+    SHALL THE AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY
+    CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER IN AN ACTION
+    OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR
+    IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+    DEALINGS IN THE SOFTWARE.
+ */
+
+/*!
+ * Serves an OGC GeoPackage, every feature table registered in `gpkg_contents` is a collection
+ * whose id is the rowid of its `gpkg_contents` entry. Feature properties are the table columns,
+ * new properties add columns. Filters and text searches are evaluated with `matcher`, stored
+ * filters go in a `geoxidated_collection_filter` table inside the file. Deletes are permanent,
+ * other GeoPackage readers would not know to skip trashed rows.
+ */
+
+use async_trait::async_trait;
+use derive_new::new;
+use futures::TryStreamExt;
+use serde_json::{json, Value};
+use sqlx::{sqlite::{SqliteConnectOptions, SqliteJournalMode, SqlitePool, SqlitePoolOptions, SqliteRow}, Row, SqliteConnection};
+
+use crate::data::geopackage_data::{to_geopackage_binary, from_geopackage_binary};
+use crate::model::{feature::Feature, feature_collection::FeatureCollection, value::ValueTrait,
+    geometry::GeometryTrait, bbox::Bbox, filter::Filter, stored_filter::StoredFilter,
+    collection_search::CollectionSearch, search_hit::SearchHit,
+    feature_revision::FeatureRevision, instant::Instant, trash::{TrashedItem, PurgedTrash}};
+
+use super::features_repository::{FeatureRepository, FeatureRepositoryError, BulkInsertError, FeatureStream,
+    page, bulk_error};
+use super::matcher::{matches, text_match, parse_json};
+
+static GEOPACKAGE_SCHEMA: &str = r#"
+    CREATE TABLE IF NOT EXISTS gpkg_spatial_ref_sys(
+        srs_name TEXT NOT NULL,
+        srs_id INTEGER NOT NULL PRIMARY KEY,
+        organization TEXT NOT NULL,
+        organization_coordsys_id INTEGER NOT NULL,
+        definition TEXT NOT NULL,
+        description TEXT
+    );
+
+    INSERT OR IGNORE INTO gpkg_spatial_ref_sys VALUES
+        ('Undefined cartesian SRS', -1, 'NONE', -1, 'undefined', 'undefined cartesian coordinate reference system'),
+        ('Undefined geographic SRS', 0, 'NONE', 0, 'undefined', 'undefined geographic coordinate reference system'),
+        ('WGS 84 geodetic', 4326, 'EPSG', 4326, 'GEOGCS["WGS 84",DATUM["WGS_1984",SPHEROID["WGS 84",6378137,298.257223563,AUTHORITY["EPSG","7030"]],AUTHORITY["EPSG","6326"]],PRIMEM["Greenwich",0,AUTHORITY["EPSG","8901"]],UNIT["degree",0.0174532925199433,AUTHORITY["EPSG","9122"]],AUTHORITY["EPSG","4326"]]', 'longitude/latitude coordinates in decimal degrees on the WGS 84 spheroid');
+
+    CREATE TABLE IF NOT EXISTS gpkg_contents(
+        table_name TEXT NOT NULL PRIMARY KEY,
+        data_type TEXT NOT NULL,
+        identifier TEXT UNIQUE,
+        description TEXT DEFAULT '',
+        last_change DATETIME NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ','now')),
+        min_x DOUBLE,
+        min_y DOUBLE,
+        max_x DOUBLE,
+        max_y DOUBLE,
+        srs_id INTEGER REFERENCES gpkg_spatial_ref_sys(srs_id)
+    );
+
+    CREATE TABLE IF NOT EXISTS gpkg_geometry_columns(
+        table_name TEXT NOT NULL UNIQUE REFERENCES gpkg_contents(table_name),
+        column_name TEXT NOT NULL,
+        geometry_type_name TEXT NOT NULL,
+        srs_id INTEGER NOT NULL REFERENCES gpkg_spatial_ref_sys(srs_id),
+        z TINYINT NOT NULL,
+        m TINYINT NOT NULL,
+        PRIMARY KEY(table_name, column_name)
+    );
+
+    CREATE TABLE IF NOT EXISTS geoxidated_collection_filter(
+        table_name TEXT NOT NULL,
+        name TEXT NOT NULL,
+        filter TEXT NOT NULL,
+        PRIMARY KEY(table_name, name)
+    );
+"#;
+
+//'GPKG' and version 1.3.0, only set on new files
+static GEOPACKAGE_PRAGMAS: &str = "PRAGMA application_id = 1196444487; PRAGMA user_version = 10300;";
+
+static COLLECTION_COLUMNS: &str = "c.rowid, COALESCE(c.identifier, c.table_name), \
+    json_object('table_name', c.table_name, 'description', c.description)";
+
+static MEMBERSHIP_UNSUPPORTED: &str = "GeoPackage features belong to the table of their collection only";
+static HISTORY_UNSUPPORTED: &str = "GeoPackage storage keeps no feature history";
+static VERSION_UNSUPPORTED: &str = "GeoPackage features have no version, only If-Match: * matches them";
+static TRASH_UNSUPPORTED: &str = "GeoPackage storage deletes for good, nothing is in the trash";
+
+/// Opens the GeoPackage at `path`, creating it and the required tables when missing.
+pub async fn open_geopackage(path: &str) -> Result<SqlitePool, FeatureRepositoryError> {
+    //a single file, so it can be handed over without its -wal companion
+    let options = SqliteConnectOptions::new()
+    .filename(path)
+    .journal_mode(SqliteJournalMode::Delete)
+    .create_if_missing(true);
+
+    let result: Result<SqlitePool, sqlx::Error> = async {
+        let pool = SqlitePoolOptions::new()
+        .max_connections(5)
+        .connect_with(options).await?;
+
+        let existing: i64 = sqlx::query("SELECT count(*) FROM sqlite_master WHERE name = 'gpkg_contents'")
+        .fetch_one(&pool).await?
+        .try_get(0)?;
+
+        if existing == 0 {
+            sqlx::query(GEOPACKAGE_PRAGMAS).execute(&pool).await?;
+        }
+
+        sqlx::query(GEOPACKAGE_SCHEMA).execute(&pool).await?;
+        Ok(pool)
+    }.await;
+
+    result.map_err(database_error)
+}
+
+/// A feature table and the columns holding its id and geometry.
+struct Layer {
+    table_name: String,
+    primary_key: String,
+    geometry_column: String,
+    srs_id: i32,
+    columns: Vec<Column>
+}
+
+struct Column {
+    name: String,
+    declared_type: String
+}
+
+struct LayerRow {
+    id: i64,
+    geometry: Value,
+    properties: Value
+}
+
+#[derive(new)]
+pub struct GeoPackageFeatureRepository {
+    pool: SqlitePool
+}
+
+impl GeoPackageFeatureRepository {
+
+    async fn layer(&self, collection_id: i64) -> Result<Layer, FeatureRepositoryError> {
+        let row = sqlx::query("SELECT c.table_name, g.column_name, g.srs_id FROM gpkg_contents c \
+            INNER JOIN gpkg_geometry_columns g ON g.table_name = c.table_name \
+            WHERE c.rowid = ? AND c.data_type = 'features'")
+        .bind(collection_id)
+        .fetch_optional(&self.pool).await
+        .map_err(database_error)?
+        .ok_or_else(|| FeatureRepositoryError::not_found(format!("Collection {collection_id} not found")))?;
+
+        let table_name: String = row.try_get(0).map_err(database_error)?;
+        let geometry_column: String = row.try_get(1).map_err(database_error)?;
+        let srs_id: i32 = row.try_get(2).map_err(database_error)?;
+
+        let rows = sqlx::query("SELECT name, type, pk FROM pragma_table_info(?) ORDER BY cid")
+        .bind(&table_name)
+        .fetch_all(&self.pool).await
+        .map_err(database_error)?;
+
+        let mut primary_key = "rowid".to_string();
+        let mut columns = vec![];
+
+        for row in rows.iter() {
+            let name: String = row.try_get(0).map_err(database_error)?;
+            let declared_type: String = row.try_get(1).unwrap_or_default();
+            let pk: i64 = row.try_get(2).unwrap_or(0);
+
+            if pk == 1 {
+                primary_key = name;
+            } else if name != geometry_column {
+                columns.push(Column { name, declared_type: declared_type.to_uppercase() });
+            }
+        }
+
+        Ok(Layer { table_name, primary_key, geometry_column, srs_id, columns })
+    }
+
+    async fn read(&self, layer: &Layer, condition: &str, bind: Option<i64>, limit: Option<(i64, i64)>) -> Result<Vec<LayerRow>, FeatureRepositoryError> {
+        let mut query = format!("SELECT {}, {}, {} FROM {} {condition} ORDER BY 1",
+            quote_identifier(&layer.primary_key), quote_identifier(&layer.geometry_column),
+            layer.properties_json(), quote_identifier(&layer.table_name));
+
+        if limit.is_some() {
+            query.push_str(" LIMIT ? OFFSET ?");
+        }
+
+        let mut statement = sqlx::query(&query);
+        if let Some(value) = bind {
+            statement = statement.bind(value);
+        }
+        if let Some((size, offset)) = limit {
+            statement = statement.bind(size).bind(offset);
+        }
+
+        let rows = statement.fetch_all(&self.pool).await.map_err(database_error)?;
+        rows.iter().map(to_row).collect()
+    }
+
+    async fn read_features(&mut self, collection_id: i64, feature_ids: &[i64]) -> Result<Vec<Feature>, FeatureRepositoryError> {
+        let mut features = Vec::with_capacity(feature_ids.len());
+        for feature_id in feature_ids {
+            features.push(self.get_feature_by_id(collection_id, *feature_id).await?);
+        }
+
+        Ok(features)
+    }
+
+    async fn all_collections(&self) -> Result<Vec<SqliteRow>, FeatureRepositoryError> {
+        sqlx::query(&format!("SELECT {COLLECTION_COLUMNS} FROM gpkg_contents c WHERE c.data_type = 'features' ORDER BY c.rowid"))
+        .fetch_all(&self.pool).await
+        .map_err(database_error)
+    }
+}
+
+impl Layer {
+    //blobs can not go into JSON, booleans are stored as 0 and 1
+    fn properties_json(&self) -> String {
+        let members: Vec<String> = self.columns.iter()
+            .filter(|column| column.declared_type != "BLOB")
+            .map(|column| {
+                let name = quote_identifier(&column.name);
+                let value = if column.declared_type == "BOOLEAN" {
+                    format!("CASE WHEN {name} IS NULL THEN NULL WHEN {name} THEN json('true') ELSE json('false') END")
+                } else {
+                    name
+                };
+
+                format!("{}, {value}", quote_text(&column.name))
+            })
+            .collect();
+
+        format!("json_object({})", members.join(", "))
+    }
+
+    fn writable_columns(&self) -> impl Iterator<Item = &Column> {
+        self.columns.iter().filter(|column| column.declared_type != "BLOB")
+    }
+}
+
+#[async_trait]
+impl FeatureRepository for GeoPackageFeatureRepository {
+
+    //the file is meant to be read by other tools, so no history is written to it
+    fn set_editor(&mut self, _editor: Option<String>) {}
+
+    async fn get_features_in_collection(&mut self, id: i64, offset: i64, size: i64) -> Result<Vec<Feature>, FeatureRepositoryError> {
+        let layer = self.layer(id).await?;
+
+        self.read(&layer, "", None, Some((size, offset))).await?
+            .iter().map(|row| row.to_model(&layer)).collect()
+    }
+
+    async fn get_features_in_collection_by_bbox(&mut self, collection_id: i64, bbox: &Bbox, offset: i64, size: i64) -> Result<Vec<Feature>, FeatureRepositoryError> {
+        let layer = self.layer(collection_id).await?;
+
+        let mut features = vec![];
+        for row in self.read(&layer, "", None, None).await? {
+            let feature = row.to_model(&layer)?;
+
+            if feature.geometry.intersects(bbox) {
+                features.push(feature);
+            }
+        }
+
+        Ok(page(features, offset, size).collect())
+    }
+
+    async fn get_features_in_collection_after(&mut self, id: i64, after: i64, size: i64) -> Result<Vec<Feature>, FeatureRepositoryError> {
+        let layer = self.layer(id).await?;
+        let condition = format!("WHERE {} > ?", quote_identifier(&layer.primary_key));
+
+        self.read(&layer, &condition, Some(after), Some((size, 0))).await?
+            .iter().map(|row| row.to_model(&layer)).collect()
+    }
+
+    async fn get_features_in_collection_by_bbox_after(&mut self, collection_id: i64, bbox: &Bbox, after: i64, size: i64) -> Result<Vec<Feature>, FeatureRepositoryError> {
+        let layer = self.layer(collection_id).await?;
+        let condition = format!("WHERE {} > ?", quote_identifier(&layer.primary_key));
+
+        let mut features = vec![];
+        for row in self.read(&layer, &condition, Some(after), None).await? {
+            let feature = row.to_model(&layer)?;
+
+            if feature.geometry.intersects(bbox) {
+                features.push(feature);
+            }
+        }
+
+        Ok(page(features, 0, size).collect())
+    }
+
+    async fn get_features_in_collection_by_filter(&mut self, collection_id: i64, filter: &Filter, offset: i64, size: i64) -> Result<Vec<Feature>, FeatureRepositoryError> {
+        let layer = self.layer(collection_id).await?;
+
+        let mut features = vec![];
+        for row in self.read(&layer, "", None, None).await? {
+            if matches(&filter.expressions, &row.properties, Some(&row.geometry))? {
+                features.push(row.to_model(&layer)?);
+            }
+        }
+
+        Ok(page(features, offset, size).collect())
+    }
+
+    async fn count_features_in_collection(&mut self, id: i64, _estimated: bool) -> Result<i64, FeatureRepositoryError> {
+        let layer = self.layer(id).await?;
+
+        sqlx::query(&format!("SELECT count(*) FROM {}", quote_identifier(&layer.table_name)))
+        .fetch_one(&self.pool).await
+        .and_then(|row| row.try_get(0))
+        .map_err(database_error)
+    }
+
+    async fn count_features_in_collection_by_bbox(&mut self, collection_id: i64, bbox: &Bbox, _estimated: bool) -> Result<i64, FeatureRepositoryError> {
+        let layer = self.layer(collection_id).await?;
+
+        let mut count = 0;
+        for row in self.read(&layer, "", None, None).await? {
+            if row.to_model(&layer)?.geometry.intersects(bbox) {
+                count += 1;
+            }
+        }
+
+        Ok(count)
+    }
+
+    async fn count_features_in_collection_by_filter(&mut self, collection_id: i64, filter: &Filter, _estimated: bool) -> Result<i64, FeatureRepositoryError> {
+        let layer = self.layer(collection_id).await?;
+
+        let mut count = 0;
+        for row in self.read(&layer, "", None, None).await? {
+            if matches(&filter.expressions, &row.properties, Some(&row.geometry))? {
+                count += 1;
+            }
+        }
+
+        Ok(count)
+    }
+
+    async fn search_features_in_collection(&mut self, collection_id: i64, text: &str,
+        filter: Option<&Filter>, offset: i64, size: i64) -> Result<Vec<SearchHit>, FeatureRepositoryError> {
+        let layer = self.layer(collection_id).await?;
+
+        let mut hits = vec![];
+        for row in self.read(&layer, "", None, None).await? {
+            let accepted = match filter {
+                Some(filter) => matches(&filter.expressions, &row.properties, Some(&row.geometry))?,
+                None => true
+            };
+
+            if let Some((rank, snippet)) = text_match(text, &row.properties).filter(|_| accepted) {
+                hits.push(SearchHit::new(row.to_model(&layer)?, rank, snippet));
+            }
+        }
+
+        hits.sort_by(|left, right| right.rank.total_cmp(&left.rank));
+
+        Ok(page(hits, offset, size).collect())
+    }
+
+    async fn get_feature_by_id(&mut self, collection_id: i64, feature_id: i64) -> Result<Feature, FeatureRepositoryError> {
+        let layer = self.layer(collection_id).await?;
+        let condition = format!("WHERE {} = ?", quote_identifier(&layer.primary_key));
+
+        match self.read(&layer, &condition, Some(feature_id), None).await?.first() {
+            Some(row) => row.to_model(&layer),
+            None => Err(FeatureRepositoryError::not_found(format!("Feature {feature_id} not found")))
+        }
+    }
+
+    async fn get_feature_history(&mut self, _collection_id: i64, _feature_id: i64) -> Result<Vec<FeatureRevision>, FeatureRepositoryError> {
+        Err(FeatureRepositoryError::unsupported(HISTORY_UNSUPPORTED.to_string()))
+    }
+
+    async fn get_features_in_collection_as_of(&mut self, _collection_id: i64, _as_of: &Instant, _offset: i64, _size: i64) -> Result<Vec<Feature>, FeatureRepositoryError> {
+        Err(FeatureRepositoryError::unsupported(HISTORY_UNSUPPORTED.to_string()))
+    }
+
+    async fn create_feature(&mut self, collection_id: i64, feature: &Feature) -> Result<Feature, FeatureRepositoryError> {
+        let mut layer = self.layer(collection_id).await?;
+
+        let mut connection = self.pool.acquire().await.map_err(database_error)?;
+        let feature_id = insert_row(&mut connection, &mut layer, feature).await?;
+        drop(connection);
+
+        self.get_feature_by_id(collection_id, feature_id).await
+    }
+
+    async fn create_features(&mut self, collection_id: i64, features: &[Feature]) -> Result<Vec<Feature>, BulkInsertError> {
+        let mut layer = self.layer(collection_id).await?;
+
+        let mut transaction = self.pool.begin().await.map_err(|err| bulk_error(None, err))?;
+        let feature_ids = insert_rows(&mut transaction, &mut layer, features).await?;
+        transaction.commit().await.map_err(|err| bulk_error(None, err))?;
+
+        Ok(self.read_features(collection_id, &feature_ids).await?)
+    }
+
+    async fn import_features(&mut self, collection_id: i64, mut features: FeatureStream<'_>) -> Result<u64, BulkInsertError> {
+        let mut layer = self.layer(collection_id).await?;
+
+        let mut transaction = self.pool.begin().await.map_err(|err| bulk_error(None, err))?;
+
+        let mut index = 0;
+        while let Some(feature) = features.try_next().await? {
+            insert_row(&mut transaction, &mut layer, &feature).await
+                .map_err(|err| BulkInsertError { index: Some(index), message: err.message })?;
+            index += 1;
+        }
+
+        transaction.commit().await.map_err(|err| bulk_error(None, err))?;
+        Ok(index as u64)
+    }
+
+    async fn update_feature(&mut self, collection_id: i64, feature: &Feature) -> Result<Feature, FeatureRepositoryError> {
+        unversioned(feature.version)?;
+        let mut layer = self.layer(collection_id).await?;
+
+        let feature_id: Option<i64> = (&feature.id).into();
+        let properties = parse_json(&feature.properties.to_geo_json())?;
+        let geometry = encode(&feature.geometry.to_geo_json(), layer.srs_id)?;
+
+        let mut connection = self.pool.acquire().await.map_err(database_error)?;
+        add_columns(&mut connection, &mut layer, &properties).await?;
+
+        //properties left out of the feature are cleared, as in the other backends
+        let mut assignments = vec![format!("{} = ?1", quote_identifier(&layer.geometry_column))];
+        for column in layer.writable_columns() {
+            assignments.push(format!("{} = json_extract(?2, {})", quote_identifier(&column.name), json_path(&column.name)));
+        }
+
+        let query = format!("UPDATE {} SET {} WHERE {} = ?3", quote_identifier(&layer.table_name),
+            assignments.join(", "), quote_identifier(&layer.primary_key));
+
+        let result = sqlx::query(&query)
+        .bind(geometry)
+        .bind(properties.to_string())
+        .bind(feature_id)
+        .execute(&mut *connection).await
+        .map_err(database_error)?;
+        drop(connection);
+
+        match (result.rows_affected(), feature_id) {
+            (1, Some(feature_id)) => self.get_feature_by_id(collection_id, feature_id).await,
+            _ => Err(FeatureRepositoryError::not_found(format!("Feature {} not found", feature.id)))
+        }
+    }
+
+    //a feature table row belongs to a single collection, so it is always deleted
+    async fn delete_feature(&mut self, collection_id: i64, feature_id: i64, version: Option<i64>) -> Result<(), FeatureRepositoryError> {
+        unversioned(version)?;
+        let layer = self.layer(collection_id).await?;
+
+        let query = format!("DELETE FROM {} WHERE {} = ?", quote_identifier(&layer.table_name), quote_identifier(&layer.primary_key));
+        let result = sqlx::query(&query)
+        .bind(feature_id)
+        .execute(&self.pool).await
+        .map_err(database_error)?;
+
+        match result.rows_affected() {
+            0 => Err(FeatureRepositoryError::not_found(format!("Feature {feature_id} not found"))),
+            _ => Ok(())
+        }
+    }
+
+    //feature ids are only unique within their table
+    async fn attach_feature(&mut self, _collection_id: i64, _feature_id: i64) -> Result<(), FeatureRepositoryError> {
+        Err(FeatureRepositoryError::unsupported(MEMBERSHIP_UNSUPPORTED.to_string()))
+    }
+
+    async fn detach_feature(&mut self, _collection_id: i64, _feature_id: i64) -> Result<(), FeatureRepositoryError> {
+        Err(FeatureRepositoryError::unsupported(MEMBERSHIP_UNSUPPORTED.to_string()))
+    }
+
+    async fn get_collections_of_feature(&mut self, _feature_id: i64) -> Result<Vec<FeatureCollection>, FeatureRepositoryError> {
+        Err(FeatureRepositoryError::unsupported(MEMBERSHIP_UNSUPPORTED.to_string()))
+    }
+
+    async fn create_collection(&mut self, collection: &FeatureCollection) -> Result<FeatureCollection, FeatureRepositoryError> {
+        let properties = parse_json(&collection.properties.to_geo_json())?;
+
+        let result: Result<i64, sqlx::Error> = async {
+            let mut transaction = self.pool.begin().await?;
+            let (id, _) = create_layer(&mut transaction, collection, &properties).await?;
+
+            transaction.commit().await?;
+            Ok(id)
+        }.await;
+
+        self.get_collection_by_id(result.map_err(database_error)?).await
+    }
+
+    async fn create_collection_with_features(&mut self, collection: &FeatureCollection) -> Result<FeatureCollection, BulkInsertError> {
+        let properties = parse_json(&collection.properties.to_geo_json())?;
+
+        let mut transaction = self.pool.begin().await.map_err(|err| bulk_error(None, err))?;
+        let (id, mut layer) = create_layer(&mut transaction, collection, &properties).await.map_err(|err| bulk_error(None, err))?;
+        let feature_ids = insert_rows(&mut transaction, &mut layer, &collection.features).await?;
+        transaction.commit().await.map_err(|err| bulk_error(None, err))?;
+
+        let mut created = self.get_collection_by_id(id).await?;
+        created.features = self.read_features(id, &feature_ids).await?;
+        Ok(created)
+    }
+
+    async fn update_collection(&mut self, collection: &FeatureCollection) -> Result<FeatureCollection, FeatureRepositoryError> {
+        let id: Option<i64> = (&collection.id).into();
+        let properties = parse_json(&collection.properties.to_geo_json())?;
+
+        sqlx::query("UPDATE gpkg_contents SET identifier = ?, description = COALESCE(?, description), \
+            last_change = strftime('%Y-%m-%dT%H:%M:%fZ','now') WHERE rowid = ?")
+        .bind(&collection.label)
+        .bind(properties["description"].as_str())
+        .bind(id)
+        .execute(&self.pool).await
+        .map_err(database_error)?;
+
+        self.get_collection_by_id(id.unwrap_or_default()).await
+    }
+
+    async fn delete_collection(&mut self, id: i64) -> Result<(), FeatureRepositoryError> {
+        let layer = self.layer(id).await?;
+
+        let result: Result<(), sqlx::Error> = async {
+            let mut transaction = self.pool.begin().await?;
+
+            for table in ["geoxidated_collection_filter", "gpkg_geometry_columns", "gpkg_contents"] {
+                sqlx::query(&format!("DELETE FROM {table} WHERE table_name = ?"))
+                .bind(&layer.table_name)
+                .execute(&mut *transaction).await?;
+            }
+
+            sqlx::query(&format!("DROP TABLE {}", quote_identifier(&layer.table_name)))
+            .execute(&mut *transaction).await?;
+
+            transaction.commit().await
+        }.await;
+
+        result.map_err(database_error)
+    }
+
+    async fn get_trash(&mut self, _offset: i64, _size: i64) -> Result<Vec<TrashedItem>, FeatureRepositoryError> {
+        Ok(vec![])
+    }
+
+    async fn restore_feature(&mut self, _feature_id: i64) -> Result<(), FeatureRepositoryError> {
+        Err(FeatureRepositoryError::not_found(TRASH_UNSUPPORTED.to_string()))
+    }
+
+    async fn restore_collection(&mut self, _id: i64) -> Result<(), FeatureRepositoryError> {
+        Err(FeatureRepositoryError::not_found(TRASH_UNSUPPORTED.to_string()))
+    }
+
+    async fn purge_trash(&mut self, _before: &Instant) -> Result<PurgedTrash, FeatureRepositoryError> {
+        Ok(PurgedTrash::new(0, 0))
+    }
+
+    async fn get_collections(&mut self, offset: i64, size: i64) -> Result<Vec<FeatureCollection>, FeatureRepositoryError> {
+        let result = sqlx::query(&format!("SELECT {COLLECTION_COLUMNS} FROM gpkg_contents c \
+            WHERE c.data_type = 'features' ORDER BY c.rowid LIMIT ? OFFSET ?"))
+        .bind(size)
+        .bind(offset)
+        .fetch_all(&self.pool).await;
+
+        match result {
+            Ok(rows) => Ok(rows.iter().map(FeatureCollection::from).collect()),
+            Err(err) => Err(database_error(err))
+        }
+    }
+
+    async fn get_collections_after(&mut self, after: i64, size: i64) -> Result<Vec<FeatureCollection>, FeatureRepositoryError> {
+        let result = sqlx::query(&format!("SELECT {COLLECTION_COLUMNS} FROM gpkg_contents c \
+            WHERE c.data_type = 'features' AND c.rowid > ? ORDER BY c.rowid LIMIT ?"))
+        .bind(after)
+        .bind(size)
+        .fetch_all(&self.pool).await;
+
+        match result {
+            Ok(rows) => Ok(rows.iter().map(FeatureCollection::from).collect()),
+            Err(err) => Err(database_error(err))
+        }
+    }
+
+    async fn search_collections(&mut self, search: &CollectionSearch, offset: i64, size: i64) -> Result<Vec<FeatureCollection>, FeatureRepositoryError> {
+        let mut collections = vec![];
+
+        for row in self.all_collections().await? {
+            let collection = FeatureCollection::from(&row);
+            let id: i64 = row.try_get(0).map_err(database_error)?;
+            let properties: String = row.try_get(2).map_err(database_error)?;
+
+            let label_matches = match &search.label {
+                Some(label) => collection.label.to_lowercase().contains(&label.to_lowercase()),
+                None => true
+            };
+
+            let properties_match = match &search.expression {
+                Some(expression) => matches(expression, &parse_json(&properties)?, None)?,
+                None => true
+            };
+
+            let extent_matches = match &search.bbox {
+                Some(bbox) if label_matches && properties_match => {
+                    let layer = self.layer(id).await?;
+                    let rows = self.read(&layer, "", None, None).await?;
+                    rows.iter().map(|row| row.to_model(&layer)).collect::<Result<Vec<Feature>, _>>()?
+                        .iter().any(|feature| feature.geometry.intersects(bbox))
+                },
+                Some(_) => false,
+                None => true
+            };
+
+            if label_matches && properties_match && extent_matches {
+                collections.push(collection);
+            }
+        }
+
+        Ok(page(collections, offset, size).collect())
+    }
+
+    async fn get_collection_by_id(&mut self, id: i64) -> Result<FeatureCollection, FeatureRepositoryError> {
+        let result = sqlx::query(&format!("SELECT {COLLECTION_COLUMNS} FROM gpkg_contents c \
+            WHERE c.rowid = ? AND c.data_type = 'features'"))
+        .bind(id)
+        .fetch_one(&self.pool).await;
+
+        match result {
+            Ok(row) => Ok(FeatureCollection::from(&row)),
+            Err(err) => Err(FeatureRepositoryError::from_lookup(err, format!("Collection {id}")))
+        }
+    }
+
+    async fn save_filter(&mut self, collection_id: i64, name: &str, filter: &Filter) -> Result<StoredFilter, FeatureRepositoryError> {
+        let layer = self.layer(collection_id).await?;
+
+        let result = sqlx::query("INSERT INTO geoxidated_collection_filter(table_name, name, filter) VALUES(?, ?, ?) \
+            ON CONFLICT (table_name, name) DO UPDATE SET filter = excluded.filter \
+            RETURNING name, filter")
+        .bind(&layer.table_name)
+        .bind(name)
+        .bind(filter.to_json())
+        .fetch_one(&self.pool).await;
+
+        match result {
+            Ok(row) => Ok(StoredFilter::from(&row)),
+            Err(err) => Err(database_error(err))
+        }
+    }
+
+    async fn get_filter(&mut self, collection_id: i64, name: &str) -> Result<StoredFilter, FeatureRepositoryError> {
+        let layer = self.layer(collection_id).await?;
+
+        let result = sqlx::query("SELECT name, filter FROM geoxidated_collection_filter WHERE table_name = ? AND name = ?")
+        .bind(&layer.table_name)
+        .bind(name)
+        .fetch_one(&self.pool).await;
+
+        match result {
+            Ok(row) => Ok(StoredFilter::from(&row)),
+            Err(err) => Err(FeatureRepositoryError::from_lookup(err, format!("Filter {name} in collection {collection_id}")))
+        }
+    }
+
+    async fn get_filters(&mut self, collection_id: i64) -> Result<Vec<StoredFilter>, FeatureRepositoryError> {
+        let layer = self.layer(collection_id).await?;
+
+        let result = sqlx::query("SELECT name, filter FROM geoxidated_collection_filter WHERE table_name = ? ORDER BY name")
+        .bind(&layer.table_name)
+        .fetch_all(&self.pool).await;
+
+        match result {
+            Ok(rows) => Ok(rows.iter().map(StoredFilter::from).collect()),
+            Err(err) => Err(database_error(err))
+        }
+    }
+
+    async fn delete_filter(&mut self, collection_id: i64, name: &str) -> Result<(), FeatureRepositoryError> {
+        let layer = self.layer(collection_id).await?;
+
+        let result = sqlx::query("DELETE FROM geoxidated_collection_filter WHERE table_name = ? AND name = ?")
+        .bind(&layer.table_name)
+        .bind(name)
+        .execute(&self.pool).await;
+
+        match result {
+            Ok(_) => Ok(()),
+            Err(err) => Err(database_error(err))
+        }
+    }
+}
+
+impl LayerRow {
+    //the feature model only holds points
+    fn to_model(&self, layer: &Layer) -> Result<Feature, FeatureRepositoryError> {
+        if self.geometry["type"] != "Point" {
+            return Err(FeatureRepositoryError::unsupported(format!("Feature {} of {} is a {}, only points are supported",
+                self.id, layer.table_name, self.geometry["type"])));
+        }
+
+        Ok(Feature::from(&json!({"id": self.id, "geometry": self.geometry, "properties": self.properties})))
+    }
+}
+
+fn to_row(row: &SqliteRow) -> Result<LayerRow, FeatureRepositoryError> {
+    let id: i64 = row.try_get(0).map_err(database_error)?;
+    let geometry: Option<Vec<u8>> = row.try_get(1).map_err(database_error)?;
+    let properties: String = row.try_get(2).unwrap_or("{}".to_string());
+
+    let geometry = match geometry {
+        Some(binary) => from_geopackage_binary(&binary).map_err(|err| FeatureRepositoryError::new(err.to_string()))?,
+        None => Value::Null
+    };
+
+    //an empty column is a property the feature does not have
+    let mut properties = parse_json(&properties)?;
+    if let Value::Object(members) = &mut properties {
+        members.retain(|_, value| !value.is_null());
+    }
+
+    Ok(LayerRow { id, geometry, properties })
+}
+
+//adds a column for every property the table does not have yet
+async fn add_columns(connection: &mut SqliteConnection, layer: &mut Layer, properties: &Value) -> Result<(), FeatureRepositoryError> {
+    for (name, value) in properties.as_object().into_iter().flatten() {
+        if name == &layer.primary_key || name == &layer.geometry_column
+            || layer.columns.iter().any(|column| &column.name == name) {
+            continue;
+        }
+
+        let declared_type = match value {
+            Value::Bool(_) => "BOOLEAN",
+            Value::Number(number) if number.is_i64() => "INTEGER",
+            Value::Number(_) => "REAL",
+            _ => "TEXT"
+        };
+
+        let query = format!("ALTER TABLE {} ADD COLUMN {} {declared_type}",
+            quote_identifier(&layer.table_name), quote_identifier(name));
+        sqlx::query(&query).execute(&mut *connection).await.map_err(database_error)?;
+
+        layer.columns.push(Column { name: name.clone(), declared_type: declared_type.to_string() });
+    }
+
+    Ok(())
+}
+
+async fn insert_row(connection: &mut SqliteConnection, layer: &mut Layer, feature: &Feature) -> Result<i64, FeatureRepositoryError> {
+    let properties = parse_json(&feature.properties.to_geo_json())?;
+    let geometry = encode(&feature.geometry.to_geo_json(), layer.srs_id)?;
+    add_columns(connection, layer, &properties).await?;
+
+    let mut names = vec![quote_identifier(&layer.geometry_column)];
+    let mut values = vec!["?1".to_string()];
+    for column in layer.writable_columns() {
+        names.push(quote_identifier(&column.name));
+        values.push(format!("json_extract(?2, {})", json_path(&column.name)));
+    }
+
+    let query = format!("INSERT INTO {}({}) VALUES({}) RETURNING {}", quote_identifier(&layer.table_name),
+        names.join(", "), values.join(", "), quote_identifier(&layer.primary_key));
+
+    sqlx::query(&query)
+    .bind(geometry)
+    .bind(properties.to_string())
+    .fetch_one(&mut *connection).await
+    .and_then(|row| row.try_get(0))
+    .map_err(database_error)
+}
+
+//stops at the first failure, the caller drops the transaction so nothing is kept
+async fn insert_rows(connection: &mut SqliteConnection, layer: &mut Layer, features: &[Feature]) -> Result<Vec<i64>, BulkInsertError> {
+    let mut feature_ids = Vec::with_capacity(features.len());
+
+    for (index, feature) in features.iter().enumerate() {
+        let feature_id = insert_row(connection, layer, feature).await
+            .map_err(|err| BulkInsertError { index: Some(index), message: err.message })?;
+
+        feature_ids.push(feature_id);
+    }
+
+    Ok(feature_ids)
+}
+
+//creates an empty feature table and registers it in gpkg_contents
+async fn create_layer(connection: &mut SqliteConnection, collection: &FeatureCollection, properties: &Value) -> Result<(i64, Layer), sqlx::Error> {
+    let description = properties["description"].as_str().unwrap_or("");
+    let table_name = free_table_name(connection, &collection.label).await?;
+
+    let query = format!("CREATE TABLE {}(fid INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL, geom GEOMETRY)",
+        quote_identifier(&table_name));
+    sqlx::query(&query).execute(&mut *connection).await?;
+
+    let id: i64 = sqlx::query("INSERT INTO gpkg_contents(table_name, data_type, identifier, description, srs_id) \
+        VALUES(?, 'features', ?, ?, 4326) RETURNING rowid")
+    .bind(&table_name)
+    .bind(&collection.label)
+    .bind(description)
+    .fetch_one(&mut *connection).await?
+    .try_get(0)?;
+
+    sqlx::query("INSERT INTO gpkg_geometry_columns(table_name, column_name, geometry_type_name, srs_id, z, m) \
+        VALUES(?, 'geom', 'GEOMETRY', 4326, 0, 0)")
+    .bind(&table_name)
+    .execute(&mut *connection).await?;
+
+    let layer = Layer { table_name, primary_key: "fid".to_string(), geometry_column: "geom".to_string(), srs_id: 4326, columns: vec![] };
+    Ok((id, layer))
+}
+
+fn encode(geo_json: &str, srs_id: i32) -> Result<Vec<u8>, FeatureRepositoryError> {
+    to_geopackage_binary(&parse_json(geo_json)?, srs_id).map_err(|err| FeatureRepositoryError::new(err.to_string()))
+}
+
+//labels that map to a taken table name, like "Parks" and "parks", get a numeric suffix
+async fn free_table_name(connection: &mut SqliteConnection, label: &str) -> Result<String, sqlx::Error> {
+    let base = table_name(label);
+    let mut candidate = base.clone();
+
+    for suffix in 2.. {
+        let taken: bool = sqlx::query("SELECT EXISTS (SELECT 1 FROM sqlite_master WHERE lower(name) = lower(?1)) \
+            OR EXISTS (SELECT 1 FROM gpkg_contents WHERE lower(table_name) = lower(?1))")
+        .bind(&candidate)
+        .fetch_one(&mut *connection).await?
+        .try_get(0)?;
+
+        if !taken {
+            break;
+        }
+
+        candidate = format!("{base}_{suffix}");
+    }
+
+    Ok(candidate)
+}
+
+//table names are kept to letters, digits and underscores
+fn table_name(label: &str) -> String {
+    let name: String = label.chars()
+        .map(|current| if current.is_ascii_alphanumeric() { current.to_ascii_lowercase() } else { '_' })
+        .collect();
+
+    match name.chars().next() {
+        Some(first) if first.is_ascii_alphabetic() => name,
+        _ => format!("collection_{name}")
+    }
+}
+
+fn quote_identifier(name: &str) -> String {
+    format!("\"{}\"", name.replace('"', "\"\""))
+}
+
+fn quote_text(text: &str) -> String {
+    format!("'{}'", text.replace('\'', "''"))
+}
+
+fn json_path(name: &str) -> String {
+    quote_text(&format!("$.\"{}\"", name.replace('"', "\\\"")))
+}
+
+//the tables are shared with other tools, which would not move a version column on
+fn unversioned(version: Option<i64>) -> Result<(), FeatureRepositoryError> {
+    match version {
+        Some(_) => Err(FeatureRepositoryError::conflict(VERSION_UNSUPPORTED.to_string())),
+        None => Ok(())
+    }
+}
+
+fn database_error(err: sqlx::Error) -> FeatureRepositoryError {
+    FeatureRepositoryError::from(err)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use futures::executor::block_on;
+    use serde_json::{json, Value};
+    use sqlx::{sqlite::SqlitePool, Row};
+
+    use crate::model::{feature::Feature, feature_collection::FeatureCollection};
+    use crate::repository::features_repository::FeatureRepository;
+
+    use super::{GeoPackageFeatureRepository, open_geopackage};
+
+    fn geopackage(name: &str) -> (PathBuf, SqlitePool) {
+        let path = std::env::temp_dir().join(format!("geoxidated-{name}-{}.gpkg", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let pool = block_on(open_geopackage(&path.to_string_lossy())).unwrap();
+
+        (path, pool)
+    }
+
+    fn park(label: &str, name: &str) -> FeatureCollection {
+        FeatureCollection::from(&json!({"label": label, "features": [
+            {"type": "Feature", "geometry": {"type": "Point", "coordinates": [-38.5, -12.9]}, "properties": {"name": name}}
+        ]}))
+    }
+
+    fn names(features: &[Feature]) -> Vec<String> {
+        features.iter().map(|feature| {
+            let value: Value = serde_json::from_str(&feature.to_geo_json()).unwrap();
+            value["properties"]["name"].as_str().unwrap().to_string()
+        }).collect()
+    }
+
+    #[test]
+    fn gives_colliding_labels_their_own_table() {
+        let (path, pool) = geopackage("tables");
+        let mut repository = GeoPackageFeatureRepository::new(pool.clone());
+
+        let parks = block_on(repository.create_collection_with_features(&park("Parks", "Abaeté"))).unwrap();
+        let others = block_on(repository.create_collection_with_features(&park("parks", "Pituaçu"))).unwrap();
+        let third = block_on(repository.create_collection_with_features(&park("PARKS", "Jardim"))).unwrap();
+
+        let tables: Vec<String> = block_on(sqlx::query("SELECT table_name FROM gpkg_contents ORDER BY rowid").fetch_all(&pool)).unwrap()
+            .iter().map(|row| row.get(0)).collect();
+        let found = block_on(repository.get_features_in_collection(Option::<i64>::from(&others.id).unwrap(), 0, 10)).unwrap();
+
+        assert_eq!(tables, ["parks", "parks_2", "parks_3"]);
+        assert_ne!(Option::<i64>::from(&parks.id), Option::<i64>::from(&third.id));
+        assert_eq!(names(&found), ["Pituaçu"]);
+        block_on(pool.close());
+        let _ = std::fs::remove_file(&path);
+    }
+}
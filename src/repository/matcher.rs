@@ -0,0 +1,566 @@
+/*
+    Copyright (c)  Abraão Isvi <avraamisvi@users.noreply.github.com>
+
+    Permission is hereby granted, free of charge, to any
+    person obtaining a copy of this software and associated
+    documentation files (the "Software"), to deal in the
+    Software without restriction, including without
+    limitation the rights to use, copy, modify, merge,
+    publish, distribute, sublicense, and/or sell copies of
+    the Software, and to permit persons to whom the Software
+    is furnished to do so, subject to the following
+    conditions:
+
+    The above copyright notice and this permission notice
+    shall be included in all copies or substantial portions
+    of the Software.
+
+    THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF
+    ANY KIND, EXPRESS OR IMPLIED, INCLUDING BUT NOT LIMITED
+    TO THE WARRANTIES OF MERCHANTABILITY, FITNESS FOR A
+    PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT
+    SHALL THE AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY
+    CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER IN AN ACTION
+    OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR
+    IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+    DEALINGS IN THE SOFTWARE.
+ */
+
+/*!
+ * Evaluates filters and text searches in Rust, for the backends that can not compile them
+ * into SQL. The semantics follow the Postgres ones: a missing property is NULL, numbers
+ * only compare with number properties and containment works like jsonb `@>`. Features must
+ * hold points, spatial operators accept every GeoJSON geometry as operand.
+ */
+
+use std::cmp::Ordering;
+
+use serde_json::Value;
+
+use crate::model::filter::{Expression, Element, ElementType, GeometryOperand, LengthComparison};
+
+use super::features_repository::FeatureRepositoryError;
+
+/// Whether the properties (and the geometry, when there is one) pass the expression.
+pub(crate) fn matches(expression: &Expression, properties: &Value, geometry: Option<&Value>) -> Result<bool, FeatureRepositoryError> {
+    if let Some(name) = expression.unbound_parameter() {
+        return Err(unbound(name));
+    }
+
+    Ok(evaluate(expression, properties, geometry)? == Some(true))
+}
+
+/// Matches the words of `text` against the string properties, a leading `-` excludes a word.
+/// Returns the rank and the matching properties with the words marked, or `None` when it does not match.
+pub(crate) fn text_match(text: &str, properties: &Value) -> Option<(f32, String)> {
+    let (required, excluded) = search_terms(text);
+
+    let mut texts = vec![];
+    collect_strings(properties, &mut texts);
+    let words: Vec<String> = texts.iter().flat_map(|text| words(text)).collect();
+
+    let matched = !required.is_empty() && required.iter().all(|term| words.contains(term))
+        && !excluded.iter().any(|term| words.contains(term));
+
+    if !matched {
+        return None;
+    }
+
+    let occurrences = words.iter().filter(|word| required.contains(word)).count();
+    Some((occurrences as f32 / words.len() as f32, highlight(properties, &required)))
+}
+
+pub(crate) fn parse_json(text: &str) -> Result<Value, FeatureRepositoryError> {
+    serde_json::from_str(text).map_err(|err| FeatureRepositoryError::new(err.to_string()))
+}
+
+pub(crate) fn unbound(name: &str) -> FeatureRepositoryError {
+    FeatureRepositoryError::new(format!("the parameter {name} has no value"))
+}
+
+fn unsupported(operation: &str) -> FeatureRepositoryError {
+    FeatureRepositoryError::unsupported(format!("{operation} is not supported by this storage"))
+}
+
+//three-valued like SQL, None stands for NULL (missing properties, failed casts)
+fn evaluate(expression: &Expression, properties: &Value, geometry: Option<&Value>) -> Result<Option<bool>, FeatureRepositoryError> {
+    Ok(match expression {
+        Expression::Equals(exp) => compare(properties, &exp.field, &exp.value).map(|order| order == Ordering::Equal),
+        Expression::NotEquals(exp) => compare(properties, &exp.field, &exp.value).map(|order| order != Ordering::Equal),
+        Expression::LessThan(exp) => compare(properties, &exp.field, &exp.value).map(|order| order == Ordering::Less),
+        Expression::LessThanOrEquals(exp) => compare(properties, &exp.field, &exp.value).map(|order| order != Ordering::Greater),
+        Expression::GreaterThan(exp) => compare(properties, &exp.field, &exp.value).map(|order| order == Ordering::Greater),
+        Expression::GreaterThanOrEquals(exp) => compare(properties, &exp.field, &exp.value).map(|order| order != Ordering::Less),
+        Expression::IsNull(exp) => Some(text(properties, &exp.field).is_none()),
+        Expression::Like(exp) => text(properties, &exp.field).map(|value| like(&value, &exp.pattern)),
+        Expression::ILike(exp) => text(properties, &exp.field).map(|value| like(&value.to_lowercase(), &exp.pattern.to_lowercase())),
+        Expression::StartsWith(exp) => text(properties, &exp.field).map(|value| value.starts_with(&exp.value)),
+        Expression::Intersects(exp) => Some(point_distance(geometry, &exp.geometry)? == 0.0),
+        Expression::Within(exp) => Some(point_distance(geometry, &exp.geometry)? == 0.0),
+        Expression::Disjoint(exp) => Some(point_distance(geometry, &exp.geometry)? > 0.0),
+        Expression::DWithin(exp) => Some(point_distance(geometry, &exp.geometry)? <= exp.distance),
+        Expression::Contains(exp) => {
+            //a point only contains the very same point
+            let operand = parse_json(&exp.geometry.0)?;
+            if operand["type"] != "Point" {
+                Some(false)
+            } else {
+                Some(point_distance(geometry, &exp.geometry)? == 0.0)
+            }
+        },
+        Expression::ContainsValue(exp) => {
+            let value = json_value(&exp.value)?;
+            properties.get(&exp.field).map(|property| json_contains(property, &value))
+        },
+        Expression::ContainedBy(exp) => {
+            let value = json_value(&exp.value)?;
+            properties.get(&exp.field).map(|property| json_contains(&value, property))
+        },
+        Expression::AnyOf(exp) => {
+            let mut matched = Some(false);
+            for value in &exp.values {
+                let wrapped = Value::Array(vec![json_value(value)?]);
+                let contained = properties.get(&exp.field).map(|property| json_contains(property, &wrapped));
+                matched = or(matched, contained);
+            }
+            matched
+        },
+        Expression::ArrayLength(exp) => match properties.get(&exp.field) {
+            Some(Value::Array(values)) => {
+                let order = (values.len() as i64).cmp(&exp.length);
+                Some(match exp.comparison {
+                    LengthComparison::Equals => order == Ordering::Equal,
+                    LengthComparison::NotEquals => order != Ordering::Equal,
+                    LengthComparison::LessThan => order == Ordering::Less,
+                    LengthComparison::LessThanOrEquals => order != Ordering::Greater,
+                    LengthComparison::GreaterThan => order == Ordering::Greater,
+                    LengthComparison::GreaterThanOrEquals => order != Ordering::Less,
+                })
+            },
+            _ => None
+        },
+        Expression::And(exp) => {
+            let mut result = Some(true);
+            for expression in &exp.0 {
+                result = match (result, evaluate(expression, properties, geometry)?) {
+                    (Some(false), _) | (_, Some(false)) => Some(false),
+                    (Some(true), Some(true)) => Some(true),
+                    _ => None
+                };
+            }
+            result
+        },
+        Expression::Or(exp) => {
+            let mut result = Some(false);
+            for expression in &exp.0 {
+                result = or(result, evaluate(expression, properties, geometry)?);
+            }
+            result
+        },
+        Expression::Not(exp) => evaluate(&exp.0, properties, geometry)?.map(|result| !result),
+    })
+}
+
+fn or(left: Option<bool>, right: Option<bool>) -> Option<bool> {
+    match (left, right) {
+        (Some(true), _) | (_, Some(true)) => Some(true),
+        (Some(false), Some(false)) => Some(false),
+        _ => None
+    }
+}
+
+//the text of a property as ->> returns it, JSON null and missing properties are NULL
+fn text(properties: &Value, field: &str) -> Option<String> {
+    match properties.get(field) {
+        None | Some(Value::Null) => None,
+        Some(Value::String(value)) => Some(value.clone()),
+        Some(value) => Some(value.to_string())
+    }
+}
+
+fn compare(properties: &Value, field: &str, value: &Element) -> Option<Ordering> {
+    match value.e_type {
+        ElementType::String => text(properties, field).map(|property| property.as_str().cmp(value.value.as_str())),
+        ElementType::Number => {
+            let property = properties.get(field)?.as_f64()?;
+            let value: f64 = value.value.parse().ok()?;
+            property.partial_cmp(&value)
+        },
+        ElementType::Boolean | ElementType::Array | ElementType::Object => {
+            let property = properties.get(field)?;
+            let value: Value = serde_json::from_str(&value.to_json()).ok()?;
+            json_order(property, &value)
+        },
+        //rejected by matches before evaluating
+        ElementType::Parameter => None
+    }
+}
+
+//the jsonb order within a type, values of different types can not be compared
+fn json_order(left: &Value, right: &Value) -> Option<Ordering> {
+    match (left, right) {
+        (Value::Null, Value::Null) => Some(Ordering::Equal),
+        (Value::Bool(left), Value::Bool(right)) => Some(left.cmp(right)),
+        (Value::Number(left), Value::Number(right)) => left.as_f64()?.partial_cmp(&right.as_f64()?),
+        (Value::String(left), Value::String(right)) => Some(left.cmp(right)),
+        //longer arrays are greater, arrays of the same length compare item by item
+        (Value::Array(left), Value::Array(right)) => {
+            if left.len() != right.len() {
+                return Some(left.len().cmp(&right.len()));
+            }
+            first_difference(left.iter().zip(right.iter()).map(|(left, right)| json_order(left, right)))
+        },
+        //objects with more pairs are greater, then the pairs compare in key order, shorter keys first
+        (Value::Object(left), Value::Object(right)) => {
+            if left.len() != right.len() {
+                return Some(left.len().cmp(&right.len()));
+            }
+            first_difference(sorted_pairs(left).into_iter().zip(sorted_pairs(right)).flat_map(|((left_key, left), (right_key, right))| {
+                [Some(key_order(left_key, right_key)), json_order(left, right)]
+            }))
+        },
+        _ => None
+    }
+}
+
+fn sorted_pairs(object: &serde_json::Map<String, Value>) -> Vec<(&String, &Value)> {
+    let mut pairs: Vec<(&String, &Value)> = object.iter().collect();
+    pairs.sort_by(|(left, _), (right, _)| key_order(left, right));
+    pairs
+}
+
+fn key_order(left: &str, right: &str) -> Ordering {
+    left.len().cmp(&right.len()).then(left.cmp(right))
+}
+
+fn first_difference(orders: impl Iterator<Item = Option<Ordering>>) -> Option<Ordering> {
+    for order in orders {
+        match order? {
+            Ordering::Equal => continue,
+            other => return Some(other)
+        }
+    }
+    Some(Ordering::Equal)
+}
+
+fn json_value(value: &Element) -> Result<Value, FeatureRepositoryError> {
+    match value.e_type {
+        ElementType::Parameter => Err(unbound(&value.name)),
+        _ => parse_json(&value.to_json())
+    }
+}
+
+//the jsonb @> operator
+fn json_contains(container: &Value, contained: &Value) -> bool {
+    match (container, contained) {
+        (Value::Object(container), Value::Object(contained)) => contained.iter().all(|(key, value)| {
+            container.get(key).map(|inner| json_contains(inner, value)).unwrap_or(false)
+        }),
+        (Value::Array(container), Value::Array(contained)) => contained.iter().all(|value| {
+            container.iter().any(|inner| match (inner, value) {
+                (Value::Array(_), Value::Array(_)) | (Value::Object(_), Value::Object(_)) => json_contains(inner, value),
+                _ => inner == value
+            })
+        }),
+        (Value::Array(container), scalar) if !scalar.is_array() && !scalar.is_object() => container.contains(scalar),
+        _ => container == contained
+    }
+}
+
+//filter wildcards: * any text, ? one character, \ escapes the next one
+enum Wildcard {
+    Any,
+    One,
+    Char(char)
+}
+
+//two pointers over the value and the pattern, a mismatch retries from the last `*` with one more character
+fn like(value: &str, pattern: &str) -> bool {
+    let value: Vec<char> = value.chars().collect();
+    let mut tokens = vec![];
+    let mut chars = pattern.chars();
+
+    while let Some(current) = chars.next() {
+        match current {
+            '*' => tokens.push(Wildcard::Any),
+            '?' => tokens.push(Wildcard::One),
+            '\\' => if let Some(escaped) = chars.next() { tokens.push(Wildcard::Char(escaped)) },
+            other => tokens.push(Wildcard::Char(other))
+        }
+    }
+
+    let (mut position, mut token) = (0, 0);
+    let mut retry: Option<(usize, usize)> = None;
+
+    while position < value.len() {
+        match tokens.get(token) {
+            Some(Wildcard::Any) => {
+                retry = Some((token + 1, position));
+                token += 1;
+            },
+            Some(Wildcard::One) => { token += 1; position += 1; },
+            Some(Wildcard::Char(expected)) if *expected == value[position] => { token += 1; position += 1; },
+            _ => match retry {
+                Some((after, from)) => {
+                    retry = Some((after, from + 1));
+                    token = after;
+                    position = from + 1;
+                },
+                None => return false
+            }
+        }
+    }
+
+    tokens[token..].iter().all(|token| matches!(token, Wildcard::Any))
+}
+
+//the features hold points, so every spatial operator comes down to the distance to the operand
+fn point_distance(geometry: Option<&Value>, operand: &GeometryOperand) -> Result<f64, FeatureRepositoryError> {
+    let geometry = geometry.ok_or_else(|| unsupported("A spatial filter on collections"))?;
+    let point = coordinate(&geometry["coordinates"]).filter(|_| geometry["type"] == "Point")
+        .ok_or_else(|| unsupported("A spatial filter on features that are not points"))?;
+    let operand = parse_json(&operand.0)?;
+
+    geometry_distance(point, &operand)
+}
+
+//collections and multi geometries are as close as their closest member
+fn geometry_distance(point: (f64, f64), operand: &Value) -> Result<f64, FeatureRepositoryError> {
+    let coordinates = &operand["coordinates"];
+
+    match operand["type"].as_str() {
+        Some("Point") => {
+            let other = coordinate(coordinates).ok_or_else(|| unsupported("An invalid point"))?;
+            Ok(distance(point, other))
+        },
+        Some("MultiPoint") => Ok(coordinates.as_array().into_iter().flatten().filter_map(coordinate)
+            .map(|other| distance(point, other))
+            .fold(f64::INFINITY, f64::min)),
+        Some("LineString") => Ok(line_distance(point, coordinates)),
+        Some("MultiLineString") => Ok(coordinates.as_array().into_iter().flatten()
+            .map(|line| line_distance(point, line))
+            .fold(f64::INFINITY, f64::min)),
+        Some("Polygon") => Ok(polygon_distance(point, coordinates)),
+        Some("MultiPolygon") => Ok(coordinates.as_array().into_iter().flatten()
+            .map(|polygon| polygon_distance(point, polygon))
+            .fold(f64::INFINITY, f64::min)),
+        Some("GeometryCollection") => operand["geometries"].as_array().into_iter().flatten()
+            .map(|member| geometry_distance(point, member))
+            .try_fold(f64::INFINITY, |closest, distance| Ok(closest.min(distance?))),
+        Some(other) => Err(unsupported(&format!("A spatial filter with a {other}"))),
+        None => Err(unsupported("A spatial filter without a geometry type"))
+    }
+}
+
+fn coordinate(value: &Value) -> Option<(f64, f64)> {
+    Some((value[0].as_f64()?, value[1].as_f64()?))
+}
+
+fn distance(from: (f64, f64), to: (f64, f64)) -> f64 {
+    ((from.0 - to.0).powi(2) + (from.1 - to.1).powi(2)).sqrt()
+}
+
+fn line_distance(point: (f64, f64), line: &Value) -> f64 {
+    let positions: Vec<(f64, f64)> = line.as_array().into_iter().flatten().filter_map(coordinate).collect();
+
+    positions.windows(2)
+        .map(|segment| segment_distance(point, segment[0], segment[1]))
+        .fold(f64::INFINITY, f64::min)
+}
+
+//zero inside the shell and outside the holes, otherwise the distance to the closest edge
+fn polygon_distance(point: (f64, f64), rings: &Value) -> f64 {
+    let rings: Vec<Vec<(f64, f64)>> = rings.as_array().into_iter().flatten()
+        .map(|ring| ring.as_array().into_iter().flatten().filter_map(coordinate).collect())
+        .collect();
+
+    let inside = rings.first().map(|shell| ring_contains(shell, point)).unwrap_or(false)
+        && !rings.iter().skip(1).any(|hole| ring_contains(hole, point));
+
+    if inside {
+        return 0.0;
+    }
+
+    rings.iter().flat_map(|ring| ring.windows(2))
+        .map(|edge| segment_distance(point, edge[0], edge[1]))
+        .fold(f64::INFINITY, f64::min)
+}
+
+fn ring_contains(ring: &[(f64, f64)], point: (f64, f64)) -> bool {
+    let mut inside = false;
+
+    for edge in ring.windows(2) {
+        let ((x1, y1), (x2, y2)) = (edge[0], edge[1]);
+        if (y1 > point.1) != (y2 > point.1) && point.0 < (x2 - x1) * (point.1 - y1) / (y2 - y1) + x1 {
+            inside = !inside;
+        }
+    }
+
+    inside
+}
+
+fn segment_distance(point: (f64, f64), start: (f64, f64), end: (f64, f64)) -> f64 {
+    let length = (end.0 - start.0).powi(2) + (end.1 - start.1).powi(2);
+
+    if length == 0.0 {
+        return distance(point, start);
+    }
+
+    let along = (((point.0 - start.0) * (end.0 - start.0) + (point.1 - start.1) * (end.1 - start.1)) / length).clamp(0.0, 1.0);
+    distance(point, (start.0 + along * (end.0 - start.0), start.1 + along * (end.1 - start.1)))
+}
+
+fn search_terms(text: &str) -> (Vec<String>, Vec<String>) {
+    let mut required = vec![];
+    let mut excluded = vec![];
+
+    for term in text.split_whitespace() {
+        match term.strip_prefix('-') {
+            Some(term) => excluded.extend(words(term)),
+            None if term.eq_ignore_ascii_case("or") => {},
+            None => required.extend(words(term))
+        }
+    }
+
+    (required, excluded)
+}
+
+fn words(text: &str) -> Vec<String> {
+    text.split(|current: char| !current.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(|word| word.to_lowercase())
+        .collect()
+}
+
+fn collect_strings(value: &Value, texts: &mut Vec<String>) {
+    match value {
+        Value::String(text) => texts.push(text.clone()),
+        Value::Array(values) => values.iter().for_each(|value| collect_strings(value, texts)),
+        Value::Object(values) => values.values().for_each(|value| collect_strings(value, texts)),
+        _ => {}
+    }
+}
+
+//the string properties with a matching word, words marked as ts_headline does
+fn highlight(properties: &Value, terms: &[String]) -> String {
+    let fragments: Vec<String> = properties.as_object().into_iter().flatten()
+        .filter_map(|(_, value)| value.as_str())
+        .filter(|text| words(text).iter().any(|word| terms.contains(word)))
+        .map(|text| {
+            let mut marked = String::new();
+            let mut word = String::new();
+
+            for current in text.chars().chain(std::iter::once(' ')) {
+                if current.is_alphanumeric() {
+                    word.push(current);
+                    continue;
+                }
+
+                if terms.contains(&word.to_lowercase()) {
+                    marked.push_str(&format!("<mark>{word}</mark>"));
+                } else {
+                    marked.push_str(&word);
+                }
+
+                word.clear();
+                marked.push(current);
+            }
+
+            marked.pop();
+            marked
+        })
+        .collect();
+
+    fragments.join(" ... ")
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::{json, Value};
+
+    use crate::model::filter::{cql2_json, cql2_text};
+
+    use std::cmp::Ordering;
+
+    use super::{matches, like, json_order};
+
+    fn matching(text: &str, properties: Value) -> bool {
+        matches(&cql2_text::parse(text).unwrap(), &properties, None).unwrap()
+    }
+
+    #[test]
+    fn compares_numbers_with_number_properties_only() {
+        assert!(matching("population > 1000", json!({"population": 5000})));
+        assert!(matching("population = 10", json!({"population": 10.0})));
+        assert!(!matching("population > 1000", json!({"population": "5000"})));
+        assert!(!matching("population > 1000", json!({"population": "n/a"})));
+        assert!(!matching("population > 1000", json!({})));
+    }
+
+    #[test]
+    fn refuses_unbound_parameters() {
+        let expression = cql2_json::parse(&json!({"op": "a_overlaps", "args": [{"property": "tags"}, ["river", {"$param": "tag"}]]})).unwrap();
+        let err = matches(&expression, &json!({"tags": ["bridge"]}), None).err().unwrap();
+
+        assert_eq!(err.message, "the parameter tag has no value");
+    }
+
+    #[test]
+    fn matches_like_patterns() {
+        assert!(like("Salvador", "Sal*"));
+        assert!(like("Salvador", "*v?d*"));
+        assert!(like("Sao Paulo", "S*o"));
+        assert!(like("", "*"));
+        assert!(like("100%", "100\\%"));
+        assert!(!like("Salvador", "Sal"));
+        assert!(!like("Salvador", "?alvado"));
+        assert!(!like("100", "100\\*"));
+        assert!(matching("name LIKE 'Rec_fe'", json!({"name": "Recife"})));
+    }
+
+    #[test]
+    fn matches_like_without_backtracking_blowup() {
+        let value = "a".repeat(5000);
+
+        assert!(!like(&value, &format!("{}b", "*a".repeat(50))));
+        assert!(like(&value, &format!("{}a", "*a".repeat(50))));
+    }
+
+    #[test]
+    fn orders_json_within_a_type_only() {
+        assert_eq!(json_order(&json!("apple"), &json!("banana")), Some(Ordering::Less));
+        assert_eq!(json_order(&json!(false), &json!(true)), Some(Ordering::Less));
+        assert_eq!(json_order(&json!([1, 2]), &json!([1, 3])), Some(Ordering::Less));
+        assert_eq!(json_order(&json!([1, 2, 3]), &json!([9])), Some(Ordering::Greater));
+        assert_eq!(json_order(&json!({"b": 1, "a": 2}), &json!({"a": 2, "b": 1})), Some(Ordering::Equal));
+        assert_eq!(json_order(&json!({"a": 1}), &json!({"a": 2})), Some(Ordering::Less));
+        assert_eq!(json_order(&json!(1), &json!("1")), None);
+        assert_eq!(json_order(&json!(["a"]), &json!([1])), None);
+    }
+
+    fn near(text: &str, x: f64, y: f64) -> bool {
+        let geometry = json!({"type": "Point", "coordinates": [x, y]});
+        matches(&cql2_text::parse(text).unwrap(), &json!({}), Some(&geometry)).unwrap()
+    }
+
+    #[test]
+    fn measures_every_geometry_operand() {
+        assert!(near("S_INTERSECTS(geometry, LINESTRING(0 0, 2 2))", 1.0, 1.0));
+        assert!(!near("S_INTERSECTS(geometry, LINESTRING(0 0, 2 2))", 1.0, 0.0));
+        assert!(near("S_DWITHIN(geometry, LINESTRING(0 0, 2 0), 0.5)", 1.0, 0.4));
+        assert!(near("S_INTERSECTS(geometry, MULTIPOINT((5 5), (1 0)))", 1.0, 0.0));
+        assert!(near("S_INTERSECTS(geometry, MULTILINESTRING((5 5, 6 6), (0 0, 0 2)))", 0.0, 1.0));
+        assert!(near("S_DISJOINT(geometry, MULTILINESTRING((5 5, 6 6), (0 0, 0 2)))", 3.0, 1.0));
+        assert!(near("S_INTERSECTS(geometry, GEOMETRYCOLLECTION(POINT(9 9), POLYGON((0 0, 2 0, 2 2, 0 2, 0 0))))", 1.0, 1.0));
+        assert!(!near("S_INTERSECTS(geometry, GEOMETRYCOLLECTION(POINT(9 9), LINESTRING(0 0, 0 2)))", 1.0, 1.0));
+    }
+
+    #[test]
+    fn names_no_particular_storage_when_unsupported() {
+        let expression = cql2_text::parse("S_INTERSECTS(geometry, POINT(0 0))").unwrap();
+        let err = matches(&expression, &json!({}), None).err().unwrap();
+
+        assert!(err.unsupported);
+        assert_eq!(err.message, "A spatial filter on collections is not supported by this storage");
+    }
+}
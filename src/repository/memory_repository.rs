@@ -0,0 +1,727 @@
+/*
+    Copyright (c)  Abraão Isvi <avraamisvi@users.noreply.github.com>
+
+    Permission is hereby granted, free of charge, to any
+    person obtaining a copy of this software and associated
+    documentation files (the "Software"), to deal in the
+    Software without restriction, including without
+    limitation the rights to use, copy, modify, merge,
+    publish, distribute, sublicense, and/or sell copies of
+    the Software, and to permit persons to whom the Software
+    is furnished to do so, subject to the following
+    conditions:
+
+    The above copyright notice and this permission notice
+    shall be included in all copies or substantial portions
+    of the Software.
+
+    THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF
+    ANY KIND, EXPRESS OR IMPLIED, INCLUDING BUT NOT LIMITED
+    TO THE WARRANTIES OF MERCHANTABILITY, FITNESS FOR A
+    PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT
+    SHALL THE AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY
+    CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER IN AN ACTION
+    OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR
+    IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+    DEALINGS IN THE SOFTWARE.
+ */
+
+/*!
+ * Keeps collections, features, stored filters and the feature history in memory. Meant for
+ * tests and demos, everything is lost when the server stops. Trashed collections and features
+ * stay in the maps with a `deleted_at` until they are purged.
+ */
+
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, BTreeSet};
+use std::ops::Bound;
+use std::sync::{Arc, Mutex};
+
+use async_trait::async_trait;
+use futures::TryStreamExt;
+use derive_new::new;
+use serde_json::{json, Value};
+
+use crate::model::{feature::Feature, feature_collection::FeatureCollection, value::ValueTrait,
+    geometry::{Geometry, GeometryTrait}, bbox::Bbox, filter::Filter, stored_filter::StoredFilter,
+    collection_search::CollectionSearch, search_hit::SearchHit, json::Json,
+    feature_revision::FeatureRevision, instant::Instant, trash::{TrashedItem, PurgedTrash}};
+
+use super::features_repository::{FeatureRepository, FeatureRepositoryError, BulkInsertError, FeatureStream, check_current_version,
+    page, detached};
+use super::matcher::{matches, text_match, parse_json};
+
+/// Data shared by every `MemoryFeatureRepository` created from it.
+#[derive(Clone, Default)]
+pub struct MemoryStore(Arc<Mutex<MemoryData>>);
+
+#[derive(Default)]
+struct MemoryData {
+    last_collection_id: i64,
+    last_feature_id: i64,
+    collections: BTreeMap<i64, StoredCollection>,
+    features: BTreeMap<i64, StoredFeature>,
+    filters: BTreeMap<(i64, String), Value>,
+    history: Vec<StoredRevision>
+}
+
+struct StoredCollection {
+    label: String,
+    properties: Value,
+    features: BTreeSet<i64>,
+    deleted_at: Option<Instant>
+}
+
+#[derive(Clone)]
+struct StoredFeature {
+    geometry: Value,
+    properties: Value,
+    version: i64,
+    deleted_at: Option<Instant>
+}
+
+//content operations keep the feature as it was left, membership ones the collection
+struct StoredRevision {
+    feature_id: i64,
+    collection_id: Option<i64>,
+    operation: &'static str,
+    feature: Option<StoredFeature>,
+    changed_by: Option<String>,
+    changed_at: Instant
+}
+
+#[derive(new)]
+pub struct MemoryFeatureRepository {
+    store: MemoryStore,
+    #[new(default)]
+    editor: Option<String>
+}
+
+impl MemoryFeatureRepository {
+    fn data(&self) -> Result<std::sync::MutexGuard<'_, MemoryData>, FeatureRepositoryError> {
+        self.store.0.lock().map_err(|err| FeatureRepositoryError::new(err.to_string()))
+    }
+}
+
+#[async_trait]
+impl FeatureRepository for MemoryFeatureRepository {
+
+    fn set_editor(&mut self, editor: Option<String>) {
+        self.editor = editor;
+    }
+
+    async fn get_features_in_collection(&mut self, id: i64, offset: i64, size: i64) -> Result<Vec<Feature>, FeatureRepositoryError> {
+        let data = self.data()?;
+
+        Ok(page(data.features_of(id), offset, size).map(|(feature_id, feature)| feature.to_model(feature_id)).collect())
+    }
+
+    async fn get_features_in_collection_by_bbox(&mut self, collection_id: i64, bbox: &Bbox, offset: i64, size: i64) -> Result<Vec<Feature>, FeatureRepositoryError> {
+        let data = self.data()?;
+
+        let features = data.features_of(collection_id)
+            .filter(|(_, feature)| Geometry::from(&feature.geometry).intersects(bbox));
+
+        Ok(page(features, offset, size).map(|(feature_id, feature)| feature.to_model(feature_id)).collect())
+    }
+
+    async fn get_features_in_collection_after(&mut self, id: i64, after: i64, size: i64) -> Result<Vec<Feature>, FeatureRepositoryError> {
+        let data = self.data()?;
+
+        let features = data.features_of(id).filter(|(feature_id, _)| *feature_id > after);
+
+        Ok(page(features, 0, size).map(|(feature_id, feature)| feature.to_model(feature_id)).collect())
+    }
+
+    async fn get_features_in_collection_by_bbox_after(&mut self, collection_id: i64, bbox: &Bbox, after: i64, size: i64) -> Result<Vec<Feature>, FeatureRepositoryError> {
+        let data = self.data()?;
+
+        let features = data.features_of(collection_id)
+            .filter(|(feature_id, feature)| *feature_id > after && Geometry::from(&feature.geometry).intersects(bbox));
+
+        Ok(page(features, 0, size).map(|(feature_id, feature)| feature.to_model(feature_id)).collect())
+    }
+
+    async fn get_features_in_collection_by_filter(&mut self, collection_id: i64, filter: &Filter, offset: i64, size: i64) -> Result<Vec<Feature>, FeatureRepositoryError> {
+        let data = self.data()?;
+
+        let mut features = vec![];
+        for (feature_id, feature) in data.features_of(collection_id) {
+            if matches(&filter.expressions, &feature.properties, Some(&feature.geometry))? {
+                features.push((feature_id, feature));
+            }
+        }
+
+        Ok(page(features, offset, size).map(|(feature_id, feature)| feature.to_model(feature_id)).collect())
+    }
+
+    async fn count_features_in_collection(&mut self, id: i64, _estimated: bool) -> Result<i64, FeatureRepositoryError> {
+        let data = self.data()?;
+
+        Ok(data.features_of(id).count() as i64)
+    }
+
+    async fn count_features_in_collection_by_bbox(&mut self, collection_id: i64, bbox: &Bbox, _estimated: bool) -> Result<i64, FeatureRepositoryError> {
+        let data = self.data()?;
+
+        Ok(data.features_of(collection_id)
+            .filter(|(_, feature)| Geometry::from(&feature.geometry).intersects(bbox))
+            .count() as i64)
+    }
+
+    async fn count_features_in_collection_by_filter(&mut self, collection_id: i64, filter: &Filter, _estimated: bool) -> Result<i64, FeatureRepositoryError> {
+        let data = self.data()?;
+
+        let mut count = 0;
+        for (_, feature) in data.features_of(collection_id) {
+            if matches(&filter.expressions, &feature.properties, Some(&feature.geometry))? {
+                count += 1;
+            }
+        }
+
+        Ok(count)
+    }
+
+    async fn search_features_in_collection(&mut self, collection_id: i64, text: &str,
+        filter: Option<&Filter>, offset: i64, size: i64) -> Result<Vec<SearchHit>, FeatureRepositoryError> {
+        let data = self.data()?;
+
+        let mut hits = vec![];
+
+        for (feature_id, feature) in data.features_of(collection_id) {
+            let accepted = match filter {
+                Some(filter) => matches(&filter.expressions, &feature.properties, Some(&feature.geometry))?,
+                None => true
+            };
+
+            if let Some((rank, snippet)) = text_match(text, &feature.properties).filter(|_| accepted) {
+                hits.push((rank, feature_id, feature, snippet));
+            }
+        }
+
+        hits.sort_by(|left, right| right.0.partial_cmp(&left.0).unwrap_or(Ordering::Equal).then(left.1.cmp(&right.1)));
+
+        Ok(page(hits, offset, size)
+            .map(|(rank, feature_id, feature, snippet)| SearchHit::new(feature.to_model(feature_id), rank, snippet))
+            .collect())
+    }
+
+    async fn get_feature_by_id(&mut self, collection_id: i64, feature_id: i64) -> Result<Feature, FeatureRepositoryError> {
+        let data = self.data()?;
+
+        let feature = data.features_of(collection_id).find(|(id, _)| *id == feature_id)
+            .map(|(_, feature)| feature.to_model(feature_id));
+
+        feature.ok_or_else(|| not_found("Feature", feature_id))
+    }
+
+    async fn get_feature_history(&mut self, collection_id: i64, feature_id: i64) -> Result<Vec<FeatureRevision>, FeatureRepositoryError> {
+        let data = self.data()?;
+
+        let revisions: Vec<(usize, &StoredRevision)> = data.history.iter().enumerate()
+            .filter(|(_, revision)| revision.feature_id == feature_id)
+            .collect();
+
+        if !revisions.iter().any(|(_, revision)| revision.collection_id == Some(collection_id)) {
+            return Err(FeatureRepositoryError::not_found(format!("Feature {feature_id} not found in collection {collection_id}")));
+        }
+
+        Ok(revisions.into_iter().map(|(index, revision)| revision.to_model(index as i64 + 1)).collect())
+    }
+
+    async fn get_features_in_collection_as_of(&mut self, collection_id: i64, as_of: &Instant, offset: i64, size: i64) -> Result<Vec<Feature>, FeatureRepositoryError> {
+        let data = self.data()?;
+
+        //replays the history up to the instant
+        let mut members: BTreeSet<i64> = BTreeSet::new();
+        let mut contents: BTreeMap<i64, Option<&StoredFeature>> = BTreeMap::new();
+        for revision in data.history.iter().take_while(|revision| revision.changed_at.as_str() <= as_of.as_str()) {
+            match (revision.collection_id, revision.operation) {
+                (Some(id), "attach") if id == collection_id => { members.insert(revision.feature_id); },
+                (Some(id), _) if id == collection_id => { members.remove(&revision.feature_id); },
+                (Some(_), _) => (),
+                (None, "delete" | "purge") => { contents.insert(revision.feature_id, None); },
+                (None, _) => { contents.insert(revision.feature_id, revision.feature.as_ref()); }
+            }
+        }
+
+        let features = members.into_iter()
+            .filter_map(|feature_id| contents.get(&feature_id).copied().flatten().map(|feature| (feature_id, feature)));
+
+        Ok(page(features, offset, size).map(|(feature_id, feature)| feature.to_model(feature_id)).collect())
+    }
+
+    async fn create_feature(&mut self, collection_id: i64, feature: &Feature) -> Result<Feature, FeatureRepositoryError> {
+        let mut data = self.data()?;
+
+        let stored = StoredFeature::from_model(feature)?;
+        if data.live_collection(collection_id).is_none() {
+            return Err(not_found("Collection", collection_id));
+        }
+
+        data.last_feature_id += 1;
+        let feature_id = data.last_feature_id;
+
+        if let Some(collection) = data.collections.get_mut(&collection_id) {
+            collection.features.insert(feature_id);
+        }
+
+        let created = stored.to_model(feature_id);
+        data.features.insert(feature_id, stored);
+        data.record(&self.editor, feature_id, None, "create");
+        data.record(&self.editor, feature_id, Some(collection_id), "attach");
+
+        Ok(created)
+    }
+
+    async fn create_features(&mut self, collection_id: i64, features: &[Feature]) -> Result<Vec<Feature>, BulkInsertError> {
+        let mut data = self.data()?;
+
+        if data.live_collection(collection_id).is_none() {
+            return Err(not_found("Collection", collection_id).into());
+        }
+
+        //everything is converted before the store is touched
+        let stored = to_stored_features(features)?;
+
+        Ok(data.insert_features(&self.editor, collection_id, stored))
+    }
+
+    //the store is locked only once the whole stream is read
+    async fn import_features(&mut self, collection_id: i64, features: FeatureStream<'_>) -> Result<u64, BulkInsertError> {
+        let features: Vec<Feature> = features.try_collect().await?;
+        let inserted = self.create_features(collection_id, &features).await?;
+
+        Ok(inserted.len() as u64)
+    }
+
+    async fn update_feature(&mut self, collection_id: i64, feature: &Feature) -> Result<Feature, FeatureRepositoryError> {
+        let mut data = self.data()?;
+
+        let feature_id = Option::<i64>::from(&feature.id).unwrap_or_default();
+        let Some(current) = data.features_of(collection_id).find(|(id, _)| *id == feature_id).map(|(_, stored)| stored.version) else {
+            return Err(not_found("Feature", feature_id));
+        };
+
+        let mut stored = StoredFeature::from_model(feature)?;
+
+        check_current_version(feature_id, current, feature.version)?;
+        stored.version = current + 1;
+
+        let updated = stored.to_model(feature_id);
+        data.features.insert(feature_id, stored);
+        data.record(&self.editor, feature_id, None, "update");
+
+        Ok(updated)
+    }
+
+    async fn delete_feature(&mut self, collection_id: i64, feature_id: i64, version: Option<i64>) -> Result<(), FeatureRepositoryError> {
+        let mut data = self.data()?;
+
+        let Some(current) = data.features_of(collection_id).find(|(id, _)| *id == feature_id).map(|(_, stored)| stored.version) else {
+            return Err(not_found("Feature", feature_id));
+        };
+        check_current_version(feature_id, current, version)?;
+
+        //a feature another collection holds is only unlinked, otherwise it keeps the link and goes to the trash
+        let shared = data.collections.iter().any(|(id, collection)| *id != collection_id && collection.features.contains(&feature_id));
+        if shared {
+            if let Some(collection) = data.collections.get_mut(&collection_id) {
+                collection.features.remove(&feature_id);
+            }
+            data.record(&self.editor, feature_id, Some(collection_id), "detach");
+        } else {
+            if let Some(feature) = data.features.get_mut(&feature_id) {
+                feature.deleted_at = Some(Instant::now());
+            }
+            data.record(&self.editor, feature_id, None, "delete");
+        }
+
+        Ok(())
+    }
+
+    async fn attach_feature(&mut self, collection_id: i64, feature_id: i64) -> Result<(), FeatureRepositoryError> {
+        let mut data = self.data()?;
+
+        if data.live_feature(feature_id).is_none() {
+            return Err(not_found("Feature", feature_id));
+        }
+
+        let collection = data.collections.get_mut(&collection_id)
+            .filter(|collection| collection.deleted_at.is_none())
+            .ok_or_else(|| not_found("Collection", collection_id))?;
+        if collection.features.insert(feature_id) {
+            data.record(&self.editor, feature_id, Some(collection_id), "attach");
+        }
+
+        Ok(())
+    }
+
+    async fn detach_feature(&mut self, collection_id: i64, feature_id: i64) -> Result<(), FeatureRepositoryError> {
+        let mut data = self.data()?;
+
+        let member = data.features_of(collection_id).filter(|(id, _)| *id == feature_id).count();
+
+        //the last link is kept, the feature would be left in no collection; links to trashed collections count
+        let holders = data.collections.values().filter(|collection| collection.features.contains(&feature_id)).count();
+        detached(collection_id, feature_id, (member as i64, holders as i64))?;
+
+        if let Some(collection) = data.collections.get_mut(&collection_id) {
+            collection.features.remove(&feature_id);
+        }
+        data.record(&self.editor, feature_id, Some(collection_id), "detach");
+
+        Ok(())
+    }
+
+    async fn get_collections_of_feature(&mut self, feature_id: i64) -> Result<Vec<FeatureCollection>, FeatureRepositoryError> {
+        let data = self.data()?;
+
+        let collections: Vec<FeatureCollection> = data.live_collections()
+            .filter(|(_, collection)| collection.features.contains(&feature_id))
+            .map(|(id, collection)| collection.to_model(*id))
+            .collect();
+
+        //every stored feature is in a collection, a trashed one is in none that can be read
+        if collections.is_empty() || data.live_feature(feature_id).is_none() {
+            return Err(not_found("Feature", feature_id));
+        }
+
+        Ok(collections)
+    }
+
+    async fn create_collection(&mut self, collection: &FeatureCollection) -> Result<FeatureCollection, FeatureRepositoryError> {
+        let mut data = self.data()?;
+
+        let properties = parse_json(&collection.properties.to_geo_json())?;
+
+        data.last_collection_id += 1;
+        let id = data.last_collection_id;
+        data.collections.insert(id, StoredCollection { label: collection.label.clone(), properties, features: BTreeSet::new(), deleted_at: None });
+
+        Ok(data.collections[&id].to_model(id))
+    }
+
+    async fn create_collection_with_features(&mut self, collection: &FeatureCollection) -> Result<FeatureCollection, BulkInsertError> {
+        let mut data = self.data()?;
+
+        let properties = parse_json(&collection.properties.to_geo_json())?;
+        let stored = to_stored_features(&collection.features)?;
+
+        data.last_collection_id += 1;
+        let id = data.last_collection_id;
+        data.collections.insert(id, StoredCollection { label: collection.label.clone(), properties, features: BTreeSet::new(), deleted_at: None });
+
+        let mut created = data.collections[&id].to_model(id);
+        created.features = data.insert_features(&self.editor, id, stored);
+
+        Ok(created)
+    }
+
+    async fn update_collection(&mut self, collection: &FeatureCollection) -> Result<FeatureCollection, FeatureRepositoryError> {
+        let mut data = self.data()?;
+
+        let id = Option::<i64>::from(&collection.id).unwrap_or_default();
+        let properties = parse_json(&collection.properties.to_geo_json())?;
+
+        let stored = data.collections.get_mut(&id)
+            .filter(|stored| stored.deleted_at.is_none())
+            .ok_or_else(|| not_found("Collection", id))?;
+        stored.label = collection.label.clone();
+        stored.properties = properties;
+
+        Ok(stored.to_model(id))
+    }
+
+    async fn delete_collection(&mut self, id: i64) -> Result<(), FeatureRepositoryError> {
+        let mut data = self.data()?;
+
+        let collection = data.collections.get_mut(&id)
+            .filter(|collection| collection.deleted_at.is_none())
+            .ok_or_else(|| not_found("Collection", id))?;
+        collection.deleted_at = Some(Instant::now());
+
+        Ok(())
+    }
+
+    async fn get_trash(&mut self, offset: i64, size: i64) -> Result<Vec<TrashedItem>, FeatureRepositoryError> {
+        let data = self.data()?;
+
+        let collections = data.collections.iter().filter_map(|(id, collection)| collection.deleted_at.as_ref()
+            .map(|deleted_at| TrashedItem::new("collection".to_string(), *id, Some(collection.label.clone()), None, deleted_at.to_string())));
+
+        //a trashed feature keeps the link to the collection it was deleted from
+        let features = data.features.iter().filter_map(|(id, feature)| feature.deleted_at.as_ref()
+            .map(|deleted_at| {
+                let collection_id = data.collections.iter().find(|(_, collection)| collection.features.contains(id)).map(|(id, _)| *id);
+                TrashedItem::new("feature".to_string(), *id, None, collection_id, deleted_at.to_string())
+            }));
+
+        let mut items: Vec<TrashedItem> = collections.chain(features).collect();
+        items.sort_by(|left, right| right.deleted_at.cmp(&left.deleted_at).then(left.kind.cmp(&right.kind)).then(left.id.cmp(&right.id)));
+
+        Ok(page(items, offset, size).collect())
+    }
+
+    async fn restore_feature(&mut self, feature_id: i64) -> Result<(), FeatureRepositoryError> {
+        let mut data = self.data()?;
+
+        let feature = data.features.get_mut(&feature_id)
+            .filter(|feature| feature.deleted_at.is_some())
+            .ok_or_else(|| FeatureRepositoryError::not_found(format!("Feature {feature_id} not found in the trash")))?;
+        feature.deleted_at = None;
+        data.record(&self.editor, feature_id, None, "restore");
+
+        Ok(())
+    }
+
+    async fn restore_collection(&mut self, id: i64) -> Result<(), FeatureRepositoryError> {
+        let mut data = self.data()?;
+
+        let collection = data.collections.get_mut(&id)
+            .filter(|collection| collection.deleted_at.is_some())
+            .ok_or_else(|| FeatureRepositoryError::not_found(format!("Collection {id} not found in the trash")))?;
+        collection.deleted_at = None;
+
+        Ok(())
+    }
+
+    async fn purge_trash(&mut self, before: &Instant) -> Result<PurgedTrash, FeatureRepositoryError> {
+        let mut data = self.data()?;
+
+        let expired = |deleted_at: &Option<Instant>| deleted_at.as_ref().is_some_and(|deleted_at| deleted_at.as_str() <= before.as_str());
+        let collections: Vec<i64> = data.collections.iter().filter(|(_, collection)| expired(&collection.deleted_at)).map(|(id, _)| *id).collect();
+        let features: BTreeSet<i64> = data.features.iter().filter(|(_, feature)| expired(&feature.deleted_at)).map(|(id, _)| *id).collect();
+
+        //the features of the purged collections go too when no other collection holds them
+        let mut candidates = features.clone();
+        for id in collections.iter() {
+            if let Some(collection) = data.collections.remove(id) {
+                for feature_id in collection.features {
+                    data.record(&self.editor, feature_id, Some(*id), "detach");
+                    candidates.insert(feature_id);
+                }
+            }
+        }
+        data.filters.retain(|(collection_id, _), _| !collections.contains(collection_id));
+
+        let links: Vec<(i64, i64)> = data.collections.iter()
+            .flat_map(|(id, collection)| collection.features.intersection(&features).map(move |feature_id| (*id, *feature_id)))
+            .collect();
+        for (collection_id, feature_id) in links {
+            if let Some(collection) = data.collections.get_mut(&collection_id) {
+                collection.features.remove(&feature_id);
+            }
+            data.record(&self.editor, feature_id, Some(collection_id), "detach");
+        }
+
+        let purged_features = candidates.into_iter().filter(|feature_id| data.remove_orphan(&self.editor, *feature_id)).count();
+
+        Ok(PurgedTrash::new(purged_features as i64, collections.len() as i64))
+    }
+
+    async fn get_collections(&mut self, offset: i64, size: i64) -> Result<Vec<FeatureCollection>, FeatureRepositoryError> {
+        let data = self.data()?;
+
+        Ok(page(data.live_collections(), offset, size).map(|(id, collection)| collection.to_model(*id)).collect())
+    }
+
+    async fn get_collections_after(&mut self, after: i64, size: i64) -> Result<Vec<FeatureCollection>, FeatureRepositoryError> {
+        let data = self.data()?;
+
+        let collections = data.collections.range((Bound::Excluded(after), Bound::Unbounded))
+            .filter(|(_, collection)| collection.deleted_at.is_none());
+
+        Ok(page(collections, 0, size).map(|(id, collection)| collection.to_model(*id)).collect())
+    }
+
+    async fn search_collections(&mut self, search: &CollectionSearch, offset: i64, size: i64) -> Result<Vec<FeatureCollection>, FeatureRepositoryError> {
+        let data = self.data()?;
+
+        let mut collections = vec![];
+        for (id, collection) in data.live_collections() {
+            let label_matches = match &search.label {
+                Some(label) => collection.label.to_lowercase().contains(&label.to_lowercase()),
+                None => true
+            };
+
+            let properties_match = match &search.expression {
+                Some(expression) => matches(expression, &collection.properties, None)?,
+                None => true
+            };
+
+            let extent_matches = match &search.bbox {
+                Some(bbox) => data.features_of(*id).any(|(_, feature)| Geometry::from(&feature.geometry).intersects(bbox)),
+                None => true
+            };
+
+            if label_matches && properties_match && extent_matches {
+                collections.push((id, collection));
+            }
+        }
+
+        Ok(page(collections, offset, size).map(|(id, collection)| collection.to_model(*id)).collect())
+    }
+
+    async fn get_collection_by_id(&mut self, id: i64) -> Result<FeatureCollection, FeatureRepositoryError> {
+        let data = self.data()?;
+
+        match data.live_collection(id) {
+            Some(collection) => Ok(collection.to_model(id)),
+            None => Err(not_found("Collection", id))
+        }
+    }
+
+    async fn save_filter(&mut self, collection_id: i64, name: &str, filter: &Filter) -> Result<StoredFilter, FeatureRepositoryError> {
+        let mut data = self.data()?;
+
+        if data.live_collection(collection_id).is_none() {
+            return Err(not_found("Collection", collection_id));
+        }
+
+        let filter = parse_json(&filter.to_json())?;
+        let stored = to_stored_filter(name, &filter)?;
+        data.filters.insert((collection_id, name.to_string()), filter);
+
+        Ok(stored)
+    }
+
+    async fn get_filter(&mut self, collection_id: i64, name: &str) -> Result<StoredFilter, FeatureRepositoryError> {
+        let data = self.data()?;
+
+        match data.filters.get(&(collection_id, name.to_string())).filter(|_| data.live_collection(collection_id).is_some()) {
+            Some(filter) => to_stored_filter(name, filter),
+            None => Err(FeatureRepositoryError::not_found(format!("Filter {name} not found in collection {collection_id}")))
+        }
+    }
+
+    async fn get_filters(&mut self, collection_id: i64) -> Result<Vec<StoredFilter>, FeatureRepositoryError> {
+        let data = self.data()?;
+
+        data.filters.iter()
+            .filter(|((id, _), _)| *id == collection_id && data.live_collection(collection_id).is_some())
+            .map(|((_, name), filter)| to_stored_filter(name, filter))
+            .collect()
+    }
+
+    async fn delete_filter(&mut self, collection_id: i64, name: &str) -> Result<(), FeatureRepositoryError> {
+        let mut data = self.data()?;
+
+        data.filters.remove(&(collection_id, name.to_string()));
+
+        Ok(())
+    }
+}
+
+impl MemoryData {
+    fn insert_features(&mut self, editor: &Option<String>, collection_id: i64, features: Vec<StoredFeature>) -> Vec<Feature> {
+        let mut inserted = Vec::with_capacity(features.len());
+
+        for feature in features {
+            self.last_feature_id += 1;
+            let feature_id = self.last_feature_id;
+
+            if let Some(collection) = self.collections.get_mut(&collection_id) {
+                collection.features.insert(feature_id);
+            }
+
+            inserted.push(feature.to_model(feature_id));
+            self.features.insert(feature_id, feature);
+            self.record(editor, feature_id, None, "create");
+            self.record(editor, feature_id, Some(collection_id), "attach");
+        }
+
+        inserted
+    }
+
+    //purges the feature once no collection holds it
+    fn remove_orphan(&mut self, editor: &Option<String>, feature_id: i64) -> bool {
+        let orphan = !self.collections.values().any(|collection| collection.features.contains(&feature_id));
+        if orphan {
+            self.record(editor, feature_id, None, "purge");
+            self.features.remove(&feature_id);
+        }
+        orphan
+    }
+
+    //content revisions copy the stored feature, so a delete is recorded before the feature is removed
+    fn record(&mut self, editor: &Option<String>, feature_id: i64, collection_id: Option<i64>, operation: &'static str) {
+        let feature = match collection_id {
+            Some(_) => None,
+            None => self.features.get(&feature_id).cloned()
+        };
+
+        self.history.push(StoredRevision {
+            feature_id, collection_id, operation, feature, changed_by: editor.clone(), changed_at: Instant::now()
+        });
+    }
+
+    //the reads only see what is not in the trash
+    fn features_of(&self, collection_id: i64) -> impl Iterator<Item = (i64, &StoredFeature)> {
+        self.live_collection(collection_id).into_iter()
+            .flat_map(|collection| collection.features.iter())
+            .filter_map(|feature_id| self.live_feature(*feature_id).map(|feature| (*feature_id, feature)))
+    }
+
+    fn live_feature(&self, feature_id: i64) -> Option<&StoredFeature> {
+        self.features.get(&feature_id).filter(|feature| feature.deleted_at.is_none())
+    }
+
+    fn live_collection(&self, collection_id: i64) -> Option<&StoredCollection> {
+        self.collections.get(&collection_id).filter(|collection| collection.deleted_at.is_none())
+    }
+
+    fn live_collections(&self) -> impl Iterator<Item = (&i64, &StoredCollection)> {
+        self.collections.iter().filter(|(_, collection)| collection.deleted_at.is_none())
+    }
+}
+
+impl StoredFeature {
+    fn from_model(feature: &Feature) -> Result<Self, FeatureRepositoryError> {
+        Ok(StoredFeature {
+            geometry: parse_json(&feature.geometry.to_geo_json())?,
+            properties: parse_json(&feature.properties.to_geo_json())?,
+            version: 1,
+            deleted_at: None
+        })
+    }
+
+    fn to_model(&self, id: i64) -> Feature {
+        let mut feature = Feature::from(&json!({"id": id, "geometry": self.geometry, "properties": self.properties}));
+        feature.version = Some(self.version);
+        feature
+    }
+}
+
+impl StoredRevision {
+    fn to_model(&self, revision: i64) -> FeatureRevision {
+        FeatureRevision::new(revision, self.collection_id, self.operation.to_string(),
+            self.feature.as_ref().map(|feature| feature.geometry.clone()),
+            self.feature.as_ref().map(|feature| feature.properties.clone()),
+            self.changed_by.clone(), self.changed_at.to_string())
+    }
+}
+
+impl StoredCollection {
+    fn to_model(&self, id: i64) -> FeatureCollection {
+        FeatureCollection::from(&json!({"id": id, "label": self.label, "properties": self.properties}))
+    }
+}
+
+fn to_stored_features(features: &[Feature]) -> Result<Vec<StoredFeature>, BulkInsertError> {
+    features.iter().enumerate()
+        .map(|(index, feature)| StoredFeature::from_model(feature)
+            .map_err(|err| BulkInsertError { index: Some(index), message: err.message }))
+        .collect()
+}
+
+fn to_stored_filter(name: &str, filter: &Value) -> Result<StoredFilter, FeatureRepositoryError> {
+    match Filter::try_from(Json::new(filter.to_string())) {
+        Ok(filter) => Ok(StoredFilter::new(name.to_string(), filter)),
+        Err(err) => Err(FeatureRepositoryError::new(err.to_string()))
+    }
+}
+
+fn not_found(kind: &str, id: i64) -> FeatureRepositoryError {
+    FeatureRepositoryError::not_found(format!("{kind} {id} not found"))
+}
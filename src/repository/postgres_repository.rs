@@ -0,0 +1,1188 @@
+/*
+    Copyright (c)  Abraão Isvi <avraamisvi@users.noreply.github.com>
+
+    Permission is hereby granted, free of charge, to any
+    person obtaining a copy of this software and associated
+    documentation files (the "Software"), to deal in the
+    Software without restriction, including without
+    limitation the rights to use, copy, modify, merge,
+    publish, distribute, sublicense, and/or sell copies of
+    the Software, and to permit persons to whom the Software
+    is furnished to do so, subject to the following
+    conditions:
+
+    The above copyright notice and this permission notice
+    shall be included in all copies or substantial portions
+    of the Software.
+
+    THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF
+    ANY KIND, EXPRESS OR IMPLIED, INCLUDING BUT NOT LIMITED
+    TO THE WARRANTIES OF MERCHANTABILITY, FITNESS FOR A
+    PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT
+    SHALL THE AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY
+    CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER IN AN ACTION
+    OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR
+    IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+    DEALINGS IN THE SOFTWARE.
+ */
+
+use async_trait::async_trait;
+use derive_new::new;
+use futures::TryStreamExt;
+use serde_json::Value;
+use sqlx::{QueryBuilder, Postgres, Row, Transaction};
+
+use crate::data::filter_data::{ToSQLQuery, escape_like};
+use crate::model::{feature::Feature, feature_collection::FeatureCollection, value::ValueTrait, geometry::GeometryTrait, bbox::Bbox, filter::{Filter, Expression},
+    stored_filter::StoredFilter, collection_search::CollectionSearch,
+    search_hit::SearchHit, feature_revision::FeatureRevision, instant::Instant,
+    trash::{TrashedItem, PurgedTrash}};
+
+use super::features_repository::{FeatureRepository, FeatureRepositoryError, BulkInsertError, FeatureStream, check_current_version,
+    detached, bulk_error};
+use super::matcher::unbound;
+
+static GEOXIDATED_SCHEMA: &str = "geoxidated";
+static FEATURE_TABLE: &str = "feature";
+static COLLECTION_TABLE: &str = "features_collection";
+static FEATURES_IN_COLLECTION: &str = "features_in_collection";
+static COLLECTION_FILTER_TABLE: &str = "collection_filter";
+static IMPORT_TABLE: &str = "feature_import";
+static FEATURE_HISTORY_TABLE: &str = "feature_history";
+//views hiding the trash, see migrations/20240101000004_geoxidated_trash.sql
+static LIVE_COLLECTION_VIEW: &str = "live_features_collection";
+static LIVE_FEATURES_IN_COLLECTION_VIEW: &str = "live_features_in_collection";
+//bytes sent to COPY at a time
+static COPY_BUFFER_SIZE: usize = 1 << 20;
+
+#[derive(new)]
+pub struct PgFeatureRepository {
+    pool: sqlx::postgres::PgPool,
+    #[new(default)]
+    editor: Option<String>
+}
+
+impl PgFeatureRepository {
+
+    //the history triggers read the editor from a setting local to the transaction
+    async fn begin_edit(&self) -> Result<Transaction<'static, Postgres>, sqlx::Error> {
+        let mut transaction = self.pool.begin().await?;
+
+        sqlx::query("SELECT set_config('geoxidated.editor', $1, true)")
+        .bind(self.editor.clone().unwrap_or_default())
+        .execute(&mut *transaction).await?;
+
+        Ok(transaction)
+    }
+
+    //an estimate is the row count the planner expects, read from EXPLAIN
+    async fn count(&self, mut query: QueryBuilder<'_, Postgres>, estimated: bool) -> Result<i64, FeatureRepositoryError> {
+        let result = query.build().fetch_one(&self.pool).await;
+
+        let count = if estimated {
+            result.and_then(|row| row.try_get::<Value, _>(0))
+                .map(|plan| plan[0]["Plan"]["Plan Rows"].as_f64().unwrap_or(0.0) as i64)
+        } else {
+            result.and_then(|row| row.try_get::<i64, _>(0))
+        };
+
+        count.map_err(FeatureRepositoryError::from)
+    }
+}
+
+#[async_trait]
+impl FeatureRepository for PgFeatureRepository {
+
+    fn set_editor(&mut self, editor: Option<String>) {
+        self.editor = editor;
+    }
+   
+    async fn get_features_in_collection(&mut self, id: i64, offset: i64, size: i64) -> Result<Vec<Feature>, FeatureRepositoryError> {
+        
+        let db = &self.pool;
+
+        let query = format!("SELECT id, \
+                                     properties::text,\
+                                     ST_AsGeoJSON(geometry) \
+                                     FROM {GEOXIDATED_SCHEMA}.{FEATURE_TABLE} fa \
+                                     INNER JOIN {GEOXIDATED_SCHEMA}.{LIVE_FEATURES_IN_COLLECTION_VIEW} fi \
+                                     ON fi.feature_id = fa.id AND fi.collection_id = $1 \
+                                     ORDER BY fi.feature_id LIMIT $2 OFFSET $3");
+
+        let result = sqlx::query(&query)
+        .bind(id)
+        .bind(size)
+        .bind(offset)
+        .fetch_all(db).await;
+
+        match result {
+            Ok(rows) => {
+                let collections: Vec<Feature> = rows.iter().map(|row| {
+                    Feature::from(row)
+                }).collect();
+
+                Ok(collections)
+            },
+            Err(err) => {
+                println!("DB Error {}", err.to_string());
+                Err(FeatureRepositoryError::from(err))
+            }
+        }
+    }
+
+    async fn get_features_in_collection_by_bbox(&mut self, collection_id: i64, bbox: &Bbox, offset: i64, size: i64) -> Result<Vec<Feature>, FeatureRepositoryError> {
+        
+        let db = &self.pool;
+
+        let bbox_geom = bbox.to_wkt();
+        let query = format!(r#"SELECT id,
+                                     properties::text,
+                                     ST_AsGeoJSON(geometry)
+                                     FROM {GEOXIDATED_SCHEMA}.{FEATURE_TABLE} fa
+                                     INNER JOIN {GEOXIDATED_SCHEMA}.{LIVE_FEATURES_IN_COLLECTION_VIEW} fi
+                                     ON fi.feature_id = fa.id AND fi.collection_id = $1
+                                     WHERE ST_Intersects(ST_GeomFromText($2, 4326), fa.geometry) 
+                                     ORDER BY fi.feature_id LIMIT $3 OFFSET $4"#);
+
+        let result = sqlx::query(&query)
+        .bind(collection_id)
+        .bind(bbox_geom)
+        .bind(size)
+        .bind(offset)
+        .fetch_all(db).await;
+
+        match result {
+            Ok(rows) => {
+                let collections: Vec<Feature> = rows.iter().map(|row| {
+                    Feature::from(row)
+                }).collect();
+
+                Ok(collections)
+            },
+            Err(err) => {
+                println!("DB Error {}", err.to_string());
+                Err(FeatureRepositoryError::from(err))
+            }
+        }
+    }
+
+    async fn get_features_in_collection_after(&mut self, id: i64, after: i64, size: i64) -> Result<Vec<Feature>, FeatureRepositoryError> {
+
+        let db = &self.pool;
+
+        let query = format!("SELECT id, \
+                                     properties::text,\
+                                     ST_AsGeoJSON(geometry) \
+                                     FROM {GEOXIDATED_SCHEMA}.{LIVE_FEATURES_IN_COLLECTION_VIEW} fi \
+                                     INNER JOIN {GEOXIDATED_SCHEMA}.{FEATURE_TABLE} fa ON fa.id = fi.feature_id \
+                                     WHERE fi.collection_id = $1 AND fi.feature_id > $2 \
+                                     ORDER BY fi.feature_id LIMIT $3");
+
+        let result = sqlx::query(&query)
+        .bind(id)
+        .bind(after)
+        .bind(size)
+        .fetch_all(db).await;
+
+        match result {
+            Ok(rows) => Ok(rows.iter().map(Feature::from).collect()),
+            Err(err) => Err(FeatureRepositoryError::from(err))
+        }
+    }
+
+    async fn get_features_in_collection_by_bbox_after(&mut self, collection_id: i64, bbox: &Bbox, after: i64, size: i64) -> Result<Vec<Feature>, FeatureRepositoryError> {
+
+        let db = &self.pool;
+
+        let query = format!("SELECT id, \
+                                     properties::text,\
+                                     ST_AsGeoJSON(geometry) \
+                                     FROM {GEOXIDATED_SCHEMA}.{LIVE_FEATURES_IN_COLLECTION_VIEW} fi \
+                                     INNER JOIN {GEOXIDATED_SCHEMA}.{FEATURE_TABLE} fa ON fa.id = fi.feature_id \
+                                     WHERE fi.collection_id = $1 AND fi.feature_id > $2 \
+                                     AND ST_Intersects(ST_GeomFromText($3, 4326), fa.geometry) \
+                                     ORDER BY fi.feature_id LIMIT $4");
+
+        let result = sqlx::query(&query)
+        .bind(collection_id)
+        .bind(after)
+        .bind(bbox.to_wkt())
+        .bind(size)
+        .fetch_all(db).await;
+
+        match result {
+            Ok(rows) => Ok(rows.iter().map(Feature::from).collect()),
+            Err(err) => Err(FeatureRepositoryError::from(err))
+        }
+    }
+
+    async fn get_features_in_collection_by_filter(&mut self, collection_id: i64, 
+        filter: &Filter, offset: i64, size: i64) -> Result<Vec<Feature>, FeatureRepositoryError> {
+        
+        let db = &self.pool;
+
+        let mut query: QueryBuilder<Postgres> = QueryBuilder::new(format!(r#"SELECT id,
+                                     properties::text,
+                                     ST_AsGeoJSON(geometry)
+                                     FROM {GEOXIDATED_SCHEMA}.{FEATURE_TABLE} fa
+                                     INNER JOIN {GEOXIDATED_SCHEMA}.{LIVE_FEATURES_IN_COLLECTION_VIEW} fi
+                                     ON fi.feature_id = fa.id AND fi.collection_id = "#));
+        query.push_bind(collection_id);
+        query.push(" WHERE ");
+        bound(&filter.expressions)?;
+        filter.to_sql_query(&mut query);
+        query.push(" ORDER BY fa.id LIMIT ").push_bind(size).push(" OFFSET ").push_bind(offset);
+
+        let result = query.build()
+        .fetch_all(db).await;
+
+        match result {
+            Ok(rows) => {
+                let features: Vec<Feature> = rows.iter().map(|row| {
+                    Feature::from(row)
+                }).collect();
+
+                Ok(features)
+            },
+            Err(err) => {
+                println!("DB Error {}", err.to_string());
+                Err(FeatureRepositoryError::from(err))
+            }
+        }
+    }
+
+    async fn count_features_in_collection(&mut self, id: i64, estimated: bool) -> Result<i64, FeatureRepositoryError> {
+
+        let mut query = count_query(estimated);
+        query.push(format!(" FROM {GEOXIDATED_SCHEMA}.{LIVE_FEATURES_IN_COLLECTION_VIEW} fi WHERE fi.collection_id = "));
+        query.push_bind(id);
+
+        self.count(query, estimated).await
+    }
+
+    async fn count_features_in_collection_by_bbox(&mut self, collection_id: i64, bbox: &Bbox, estimated: bool) -> Result<i64, FeatureRepositoryError> {
+
+        let mut query = count_query(estimated);
+        query.push(format!(" FROM {GEOXIDATED_SCHEMA}.{FEATURE_TABLE} fa \
+            INNER JOIN {GEOXIDATED_SCHEMA}.{LIVE_FEATURES_IN_COLLECTION_VIEW} fi \
+            ON fi.feature_id = fa.id AND fi.collection_id = "));
+        query.push_bind(collection_id);
+        query.push(" WHERE ST_Intersects(ST_GeomFromText(").push_bind(bbox.to_wkt()).push(", 4326), fa.geometry)");
+
+        self.count(query, estimated).await
+    }
+
+    async fn count_features_in_collection_by_filter(&mut self, collection_id: i64, filter: &Filter, estimated: bool) -> Result<i64, FeatureRepositoryError> {
+
+        let mut query = count_query(estimated);
+        query.push(format!(" FROM {GEOXIDATED_SCHEMA}.{FEATURE_TABLE} fa \
+            INNER JOIN {GEOXIDATED_SCHEMA}.{LIVE_FEATURES_IN_COLLECTION_VIEW} fi \
+            ON fi.feature_id = fa.id AND fi.collection_id = "));
+        query.push_bind(collection_id);
+        query.push(" WHERE ");
+        bound(&filter.expressions)?;
+        filter.to_sql_query(&mut query);
+
+        self.count(query, estimated).await
+    }
+
+    async fn search_features_in_collection(&mut self, collection_id: i64, text: &str,
+        filter: Option<&Filter>, offset: i64, size: i64) -> Result<Vec<SearchHit>, FeatureRepositoryError> {
+        
+        let db = &self.pool;
+
+        //unknown search languages fall back to simple instead of failing every search
+        let config: Option<String> = sqlx::query_scalar(&format!(r#"SELECT COALESCE(
+                                        (SELECT cfgname::text FROM pg_catalog.pg_ts_config WHERE cfgname = properties->>'search_language'),
+                                        'simple')
+                                    FROM {GEOXIDATED_SCHEMA}.{LIVE_COLLECTION_VIEW} WHERE id = $1"#))
+        .bind(collection_id)
+        .fetch_optional(db).await
+        .map_err(FeatureRepositoryError::from)?;
+
+        let config = match config {
+            Some(config) => config,
+            None => return Ok(vec![])
+        };
+
+        //only the simple document is stored and indexed, other languages are built per row
+        let document = if config == "simple" {
+            "fa.search_document"
+        } else {
+            r#"jsonb_to_tsvector(search.config, fa.properties, '["string"]')"#
+        };
+
+        let mut query: QueryBuilder<Postgres> = QueryBuilder::new("WITH search AS (SELECT config, websearch_to_tsquery(config, ");
+        query.push_bind(text.to_string()).push(") AS query FROM CAST(").push_bind(config).push(" AS regconfig) AS config");
+        query.push(format!(r#"), candidate AS (
+                                    SELECT fa.id, fa.properties, fa.geometry, search.config, search.query, {document} AS document
+                                    FROM {GEOXIDATED_SCHEMA}.{FEATURE_TABLE} fa
+                                    INNER JOIN {GEOXIDATED_SCHEMA}.{LIVE_FEATURES_IN_COLLECTION_VIEW} fi
+                                    ON fi.feature_id = fa.id AND fi.collection_id = "#)).push_bind(collection_id);
+        query.push(format!(r#"
+                                    CROSS JOIN search
+                                    WHERE {document} @@ search.query
+                                )
+                                SELECT id,
+                                    properties::text,
+                                    ST_AsGeoJSON(geometry),
+                                    ts_rank(document, query) AS rank,
+                                    ts_headline(config,
+                                        COALESCE((SELECT string_agg(value, ' ... ') FROM jsonb_each_text(properties::jsonb)
+                                            WHERE jsonb_typeof(properties::jsonb->key) = 'string'), ''),
+                                        query, 'StartSel=<mark>, StopSel=</mark>, MaxFragments=3')
+                                FROM candidate
+                                WHERE TRUE"#));
+
+        if let Some(filter) = filter {
+            query.push(" AND (");
+            bound(&filter.expressions)?;
+            filter.to_sql_query(&mut query);
+            query.push(")");
+        }
+
+        query.push(" ORDER BY rank DESC, id LIMIT ").push_bind(size).push(" OFFSET ").push_bind(offset);
+
+        let result = query.build()
+        .fetch_all(db).await;
+
+        match result {
+            Ok(rows) => {
+                let hits: Vec<SearchHit> = rows.iter().map(|row| {
+                    SearchHit::from(row)
+                }).collect();
+
+                Ok(hits)
+            },
+            Err(err) => Err(FeatureRepositoryError::from(err))
+        }
+    }
+
+    async fn get_feature_by_id(&mut self, collection_id: i64, feature_id: i64) -> Result<Feature, FeatureRepositoryError> {
+        
+        let db = &self.pool;
+
+        let query = format!(r#"SELECT id,
+                                     properties::text,
+                                     ST_AsGeoJSON(geometry),
+                                     version
+                                     FROM {GEOXIDATED_SCHEMA}.{FEATURE_TABLE} fa
+                                     INNER JOIN {GEOXIDATED_SCHEMA}.{LIVE_FEATURES_IN_COLLECTION_VIEW} fi
+                                     ON fi.feature_id = fa.id AND fi.collection_id = $1
+                                     WHERE fa.id = $2"#);
+
+        let result = sqlx::query(&query)
+        .bind(collection_id)
+        .bind(feature_id)
+        .fetch_one(db).await;
+
+        match result {
+            Ok(row) => {
+                Ok(Feature::from(&row))
+            },
+            Err(err) => {
+                println!("DB Error {}", err.to_string());
+                Err(FeatureRepositoryError::from_lookup(err, format!("Feature {feature_id} in collection {collection_id}")))
+            }
+        }
+    }    
+
+    async fn get_feature_history(&mut self, collection_id: i64, feature_id: i64) -> Result<Vec<FeatureRevision>, FeatureRepositoryError> {
+
+        let db = &self.pool;
+
+        let query = format!(r#"
+            SELECT revision, collection_id, operation, properties::text, ST_AsGeoJSON(geometry), changed_by,
+                   to_char(changed_at AT TIME ZONE 'UTC', 'YYYY-MM-DD"T"HH24:MI:SS.MS"Z"')
+            FROM   {GEOXIDATED_SCHEMA}.{FEATURE_HISTORY_TABLE}
+            WHERE  feature_id = $1 AND EXISTS (
+                SELECT 1 FROM {GEOXIDATED_SCHEMA}.{FEATURE_HISTORY_TABLE}
+                WHERE feature_id = $1 AND collection_id = $2)
+            ORDER BY revision
+        "#);
+
+        let result = sqlx::query(&query)
+        .bind(feature_id)
+        .bind(collection_id)
+        .fetch_all(db).await;
+
+        match result {
+            Ok(rows) if rows.is_empty() => Err(FeatureRepositoryError::not_found(format!("Feature {feature_id} not found in collection {collection_id}"))),
+            Ok(rows) => Ok(rows.iter().map(FeatureRevision::from).collect()),
+            Err(err) => Err(FeatureRepositoryError::from(err))
+        }
+    }
+
+    //a feature was in the collection when its last membership event up to then is an attach,
+    //and it still existed when its last content event is not a delete or a purge
+    async fn get_features_in_collection_as_of(&mut self, collection_id: i64, as_of: &Instant, offset: i64, size: i64) -> Result<Vec<Feature>, FeatureRepositoryError> {
+
+        let db = &self.pool;
+
+        let query = format!(r#"
+            SELECT membership.feature_id, content.properties::text, ST_AsGeoJSON(content.geometry)
+            FROM (
+                SELECT DISTINCT ON (feature_id) feature_id, operation
+                FROM   {GEOXIDATED_SCHEMA}.{FEATURE_HISTORY_TABLE}
+                WHERE  collection_id = $1 AND changed_at <= $2::timestamptz
+                ORDER BY feature_id, revision DESC
+            ) membership
+            CROSS JOIN LATERAL (
+                SELECT operation, properties, geometry
+                FROM   {GEOXIDATED_SCHEMA}.{FEATURE_HISTORY_TABLE}
+                WHERE  feature_id = membership.feature_id AND collection_id IS NULL AND changed_at <= $2::timestamptz
+                ORDER BY revision DESC
+                LIMIT 1
+            ) content
+            WHERE  membership.operation = 'attach' AND content.operation NOT IN ('delete', 'purge')
+            ORDER BY membership.feature_id
+            LIMIT $3 OFFSET $4
+        "#);
+
+        let result = sqlx::query(&query)
+        .bind(collection_id)
+        .bind(as_of.as_str())
+        .bind(size)
+        .bind(offset)
+        .fetch_all(db).await;
+
+        match result {
+            Ok(rows) => Ok(rows.iter().map(Feature::from).collect()),
+            Err(err) => Err(FeatureRepositoryError::from(err))
+        }
+    }
+
+    async fn create_feature(&mut self, collection_id: i64, feature: &Feature) -> Result<Feature, FeatureRepositoryError> {
+        
+        let mut transaction = self.begin_edit().await?;
+        let geo_json = feature.geometry.to_geo_json();
+        let properties_json = feature.properties.to_geo_json();
+
+        let result = sqlx::query(&insert_feature_query())
+        .bind(properties_json)
+        .bind(geo_json)
+        .bind(collection_id)
+        .fetch_one(&mut *transaction).await;
+
+        match result {
+            Ok(row) => {
+                transaction.commit().await?;
+                Ok(Feature::from(&row))
+            },
+            Err(err) => {
+                println!("DB Error {}", err.to_string());
+                Err(FeatureRepositoryError::from(err))
+            }
+        }
+    }
+
+    async fn create_features(&mut self, collection_id: i64, features: &[Feature]) -> Result<Vec<Feature>, BulkInsertError> {
+
+        let mut transaction = self.begin_edit().await.map_err(|err| bulk_error(None, err))?;
+
+        let inserted = insert_features(&mut transaction, collection_id, features).await?;
+
+        transaction.commit().await.map_err(|err| bulk_error(None, err))?;
+        Ok(inserted)
+    }
+
+    //the features are copied into a temporary table and inserted from there with a single statement
+    async fn import_features(&mut self, collection_id: i64, mut features: FeatureStream<'_>) -> Result<u64, BulkInsertError> {
+
+        let mut transaction = self.begin_edit().await.map_err(|err| bulk_error(None, err))?;
+
+        sqlx::query(&format!("CREATE TEMPORARY TABLE {IMPORT_TABLE}(ordinal BIGINT, properties JSONB, geometry TEXT) ON COMMIT DROP"))
+        .execute(&mut *transaction).await
+        .map_err(|err| bulk_error(None, err))?;
+
+        let mut copy = transaction.copy_in_raw(&format!("COPY {IMPORT_TABLE} FROM STDIN WITH (FORMAT csv)")).await
+            .map_err(|err| bulk_error(None, err))?;
+
+        let mut buffer = String::new();
+        let mut ordinal: i64 = 0;
+        loop {
+            let feature = match features.try_next().await {
+                Ok(Some(feature)) => feature,
+                Ok(None) => break,
+                Err(err) => {
+                    copy.abort(err.message.clone()).await.ok();
+                    return Err(err);
+                }
+            };
+
+            buffer.push_str(&format!("{ordinal},{},{}\n",
+                csv_field(&feature.properties.to_geo_json()), csv_field(&feature.geometry.to_geo_json())));
+            ordinal += 1;
+
+            if buffer.len() >= COPY_BUFFER_SIZE {
+                copy.send(std::mem::take(&mut buffer).into_bytes()).await.map_err(|err| bulk_error(None, err))?;
+            }
+        }
+
+        if !buffer.is_empty() {
+            copy.send(buffer.into_bytes()).await.map_err(|err| bulk_error(None, err))?;
+        }
+        copy.finish().await.map_err(|err| bulk_error(None, err))?;
+
+        let imported = sqlx::query(&format!(r#"
+            WITH imported AS (
+                INSERT INTO {GEOXIDATED_SCHEMA}.{FEATURE_TABLE}(properties, geometry)
+                SELECT properties, ST_GeomFromGeoJSON(geometry)
+                FROM   {IMPORT_TABLE}
+                ORDER BY ordinal
+                RETURNING id
+            )
+            INSERT INTO {GEOXIDATED_SCHEMA}.{FEATURES_IN_COLLECTION}(feature_id, collection_id)
+            SELECT id, $1 FROM imported
+        "#))
+        .bind(collection_id)
+        .execute(&mut *transaction).await
+        .map_err(|err| bulk_error(None, err))?;
+
+        transaction.commit().await.map_err(|err| bulk_error(None, err))?;
+        Ok(imported.rows_affected())
+    }
+
+    async fn update_feature(&mut self, collection_id: i64, feature: &Feature) -> Result<Feature, FeatureRepositoryError> {
+        
+        let mut transaction = self.begin_edit().await?;
+        let geo_json = feature.geometry.to_geo_json();
+        let properties_json = feature.properties.to_geo_json();
+        let feature_id: Option<i64> = (&feature.id).into();
+
+        check_version(&mut transaction, collection_id, feature_id.unwrap_or_default(), feature.version).await?;
+
+
+        let query = format!(r#"
+            WITH update_feature AS (
+                UPDATE {GEOXIDATED_SCHEMA}.{FEATURE_TABLE}
+                    SET properties = $1::json,
+                        geometry = ST_GeomFromGeoJSON($2),
+                        version = version + 1
+                    WHERE id = $3 AND EXISTS (
+                        SELECT 1 FROM {GEOXIDATED_SCHEMA}.{LIVE_FEATURES_IN_COLLECTION_VIEW}
+                        WHERE feature_id = $3 AND collection_id = $4
+                    )
+                RETURNING id, properties, geometry, version
+                )
+            SELECT update_feature.id, update_feature.properties::text, ST_AsGeoJSON(update_feature.geometry), update_feature.version
+            FROM   update_feature
+        "#);
+
+        let result = sqlx::query(&query)
+        .bind(properties_json)
+        .bind(geo_json)
+        .bind(feature_id)
+        .bind(collection_id)
+        .fetch_one(&mut *transaction).await;
+
+        match result {
+            Ok(row) => {
+                transaction.commit().await?;
+                Ok(Feature::from(&row))
+            },
+            Err(err) => {
+                println!("DB Error {}", err.to_string());
+                Err(FeatureRepositoryError::from_lookup(err, format!("Feature {} in collection {collection_id}", feature.id)))
+            }
+        }
+    }
+
+    async fn delete_feature(&mut self, collection_id: i64, feature_id: i64, version: Option<i64>) -> Result<(), FeatureRepositoryError> {
+
+        let mut transaction = self.begin_edit().await?;
+
+        check_version(&mut transaction, collection_id, feature_id, version).await?;
+
+        //a feature another collection holds is only unlinked, otherwise it keeps the link and goes to the trash
+        let query = format!(r#"
+            WITH target AS (
+                SELECT feature_id,
+                       EXISTS (SELECT 1 FROM {GEOXIDATED_SCHEMA}.{FEATURES_IN_COLLECTION} other
+                               WHERE other.feature_id = $2 AND other.collection_id <> $1) AS shared
+                FROM   {GEOXIDATED_SCHEMA}.{LIVE_FEATURES_IN_COLLECTION_VIEW}
+                WHERE  collection_id = $1 AND feature_id = $2
+            )
+            , unlinked AS (
+                DELETE FROM {GEOXIDATED_SCHEMA}.{FEATURES_IN_COLLECTION} fi
+                USING target
+                WHERE fi.collection_id = $1 AND fi.feature_id = target.feature_id AND target.shared
+            )
+            , trashed AS (
+                UPDATE {GEOXIDATED_SCHEMA}.{FEATURE_TABLE} fa
+                SET    deleted_at = now()
+                FROM   target
+                WHERE  fa.id = target.feature_id AND NOT target.shared
+            )
+            SELECT count(*) FROM target
+        "#);
+
+        let result = sqlx::query(&query)
+        .bind(collection_id)
+        .bind(feature_id)
+        .fetch_one(&mut *transaction).await;
+
+        match result.and_then(|row| row.try_get::<i64, _>(0)) {
+            Ok(0) => Err(FeatureRepositoryError::not_found(format!("Feature {feature_id} not found in collection {collection_id}"))),
+            Ok(_) => Ok(transaction.commit().await?),
+            Err(err) => Err(FeatureRepositoryError::from(err))
+        }
+    }
+
+    async fn attach_feature(&mut self, collection_id: i64, feature_id: i64) -> Result<(), FeatureRepositoryError> {
+
+        let mut transaction = self.begin_edit().await?;
+
+        let query = format!(r#"
+            WITH link AS (
+                INSERT INTO {GEOXIDATED_SCHEMA}.{FEATURES_IN_COLLECTION}(feature_id, collection_id)
+                SELECT feature.id, collection.id
+                FROM   {GEOXIDATED_SCHEMA}.{FEATURE_TABLE} feature, {GEOXIDATED_SCHEMA}.{LIVE_COLLECTION_VIEW} collection
+                WHERE  feature.id = $1 AND feature.deleted_at IS NULL AND collection.id = $2
+                ON CONFLICT (feature_id, collection_id) DO NOTHING
+            )
+            SELECT EXISTS (SELECT 1 FROM {GEOXIDATED_SCHEMA}.{FEATURE_TABLE} WHERE id = $1 AND deleted_at IS NULL),
+                   EXISTS (SELECT 1 FROM {GEOXIDATED_SCHEMA}.{LIVE_COLLECTION_VIEW} WHERE id = $2)
+        "#);
+
+        let result = sqlx::query(&query)
+        .bind(feature_id)
+        .bind(collection_id)
+        .fetch_one(&mut *transaction).await
+        .and_then(|row| Ok((row.try_get::<bool, _>(0)?, row.try_get::<bool, _>(1)?)));
+
+        match result {
+            Ok((_, false)) => Err(FeatureRepositoryError::not_found(format!("Collection {collection_id} not found"))),
+            Ok((false, _)) => Err(FeatureRepositoryError::not_found(format!("Feature {feature_id} not found"))),
+            Ok(_) => Ok(transaction.commit().await?),
+            Err(err) => Err(FeatureRepositoryError::from(err))
+        }
+    }
+
+    async fn detach_feature(&mut self, collection_id: i64, feature_id: i64) -> Result<(), FeatureRepositoryError> {
+
+        let mut transaction = self.begin_edit().await?;
+
+        //the last link is kept, the feature would be left in no collection; links to trashed collections count
+        let query = format!(r#"
+            WITH membership AS (
+                SELECT (SELECT count(*) FROM {GEOXIDATED_SCHEMA}.{LIVE_FEATURES_IN_COLLECTION_VIEW}
+                        WHERE feature_id = $2 AND collection_id = $1) AS member,
+                       count(*) AS total
+                FROM   {GEOXIDATED_SCHEMA}.{FEATURES_IN_COLLECTION}
+                WHERE  feature_id = $2
+            )
+            , unlink AS (
+                DELETE FROM {GEOXIDATED_SCHEMA}.{FEATURES_IN_COLLECTION}
+                WHERE  collection_id = $1 AND feature_id = $2 AND (SELECT total FROM membership) > 1
+            )
+            SELECT member, total FROM membership
+        "#);
+
+        let result = sqlx::query(&query)
+        .bind(collection_id)
+        .bind(feature_id)
+        .fetch_one(&mut *transaction).await
+        .and_then(|row| Ok((row.try_get::<i64, _>(0)?, row.try_get::<i64, _>(1)?)));
+
+        match result {
+            Ok(membership) => {
+                detached(collection_id, feature_id, membership)?;
+                Ok(transaction.commit().await?)
+            },
+            Err(err) => Err(FeatureRepositoryError::from(err))
+        }
+    }
+
+    async fn get_collections_of_feature(&mut self, feature_id: i64) -> Result<Vec<FeatureCollection>, FeatureRepositoryError> {
+
+        let db = &self.pool;
+
+        let query = format!(r#"
+            SELECT collection.id, collection.label, collection.properties::text
+            FROM   {GEOXIDATED_SCHEMA}.{LIVE_COLLECTION_VIEW} collection
+            INNER JOIN {GEOXIDATED_SCHEMA}.{LIVE_FEATURES_IN_COLLECTION_VIEW} link ON link.collection_id = collection.id
+            WHERE  link.feature_id = $1
+            ORDER BY collection.id
+        "#);
+
+        let result = sqlx::query(&query)
+        .bind(feature_id)
+        .fetch_all(db).await;
+
+        //every stored feature is in a collection, a trashed one is in none that can be read
+        match result {
+            Ok(rows) if rows.is_empty() => Err(FeatureRepositoryError::not_found(format!("Feature {feature_id} not found"))),
+            Ok(rows) => Ok(rows.iter().map(FeatureCollection::from).collect()),
+            Err(err) => Err(FeatureRepositoryError::from(err))
+        }
+    }
+
+    async fn create_collection(&mut self, collection: &FeatureCollection) -> Result<FeatureCollection, FeatureRepositoryError> {
+        
+        let db = &self.pool;
+        let label = &collection.label;
+        let properties = &collection.properties.to_geo_json();
+
+        let result = sqlx::query(&insert_collection_query())
+        .bind(label)
+        .bind(properties)
+        .fetch_one(db).await;
+
+        match result {
+            Ok(row) => Ok(FeatureCollection::from(&row)),
+            Err(err) => {
+                println!("DB Error {}", err.to_string());
+                Err(FeatureRepositoryError::from(err))
+            }
+        }
+    }
+
+    async fn create_collection_with_features(&mut self, collection: &FeatureCollection) -> Result<FeatureCollection, BulkInsertError> {
+
+        let mut transaction = self.begin_edit().await.map_err(|err| bulk_error(None, err))?;
+
+        let row = sqlx::query(&insert_collection_query())
+        .bind(&collection.label)
+        .bind(collection.properties.to_geo_json())
+        .fetch_one(&mut *transaction).await
+        .map_err(|err| bulk_error(None, err))?;
+
+        let mut created = FeatureCollection::from(&row);
+        let id: Option<i64> = (&created.id).into();
+        created.features = insert_features(&mut transaction, id.unwrap_or_default(), &collection.features).await?;
+
+        transaction.commit().await.map_err(|err| bulk_error(None, err))?;
+        Ok(created)
+    }
+
+    async fn update_collection(&mut self, collection: &FeatureCollection) -> Result<FeatureCollection, FeatureRepositoryError> {
+        
+        let db = &self.pool;
+        let label = &collection.label;
+        let properties = &collection.properties.to_geo_json();
+        let id: Option<i64> = (&collection.id).into();
+
+        let query = format!(r#"UPDATE {GEOXIDATED_SCHEMA}.{COLLECTION_TABLE}
+        SET label = $1, properties = $2::json
+        WHERE id = $3 AND deleted_at IS NULL RETURNING id, label, properties::text"#);
+
+        let result = sqlx::query(&query)
+        .bind(label)
+        .bind(properties)
+        .bind(id)
+        .fetch_one(db).await;
+
+        match result {
+            Ok(row) => Ok(FeatureCollection::from(&row)),
+            Err(err) => {
+                println!("DB Error {}", err.to_string());
+                Err(FeatureRepositoryError::from_lookup(err, format!("Collection {}", collection.id)))
+            }
+        }
+    }    
+
+    async fn delete_collection(&mut self, id: i64) -> Result<(), FeatureRepositoryError> {
+
+        let db = &self.pool;
+
+        let query = format!("UPDATE {GEOXIDATED_SCHEMA}.{COLLECTION_TABLE} SET deleted_at = now() \
+            WHERE id = $1 AND deleted_at IS NULL");
+
+        let result = sqlx::query(&query)
+        .bind(id)
+        .execute(db).await;
+
+        match result {
+            Ok(done) if done.rows_affected() == 0 => Err(FeatureRepositoryError::not_found(format!("Collection {id} not found"))),
+            Ok(_) => Ok(()),
+            Err(err) => Err(FeatureRepositoryError::from(err))
+        }
+    }
+
+    async fn get_trash(&mut self, offset: i64, size: i64) -> Result<Vec<TrashedItem>, FeatureRepositoryError> {
+
+        let db = &self.pool;
+
+        //a trashed feature keeps the link to the collection it was deleted from
+        let query = format!(r#"
+            SELECT kind, id, label, collection_id, to_char(deleted_at AT TIME ZONE 'UTC', 'YYYY-MM-DD"T"HH24:MI:SS.MS"Z"')
+            FROM (
+                SELECT 'collection' AS kind, id, label, NULL::bigint AS collection_id, deleted_at
+                FROM   {GEOXIDATED_SCHEMA}.{COLLECTION_TABLE}
+                WHERE  deleted_at IS NOT NULL
+                UNION ALL
+                SELECT 'feature', fa.id, NULL,
+                       (SELECT min(fi.collection_id) FROM {GEOXIDATED_SCHEMA}.{FEATURES_IN_COLLECTION} fi WHERE fi.feature_id = fa.id),
+                       fa.deleted_at
+                FROM   {GEOXIDATED_SCHEMA}.{FEATURE_TABLE} fa
+                WHERE  fa.deleted_at IS NOT NULL
+            ) trash
+            ORDER BY deleted_at DESC, kind, id
+            LIMIT $1 OFFSET $2
+        "#);
+
+        let result = sqlx::query(&query)
+        .bind(size)
+        .bind(offset)
+        .fetch_all(db).await;
+
+        match result {
+            Ok(rows) => Ok(rows.iter().map(TrashedItem::from).collect()),
+            Err(err) => Err(FeatureRepositoryError::from(err))
+        }
+    }
+
+    async fn restore_feature(&mut self, feature_id: i64) -> Result<(), FeatureRepositoryError> {
+
+        let mut transaction = self.begin_edit().await?;
+
+        let query = format!("UPDATE {GEOXIDATED_SCHEMA}.{FEATURE_TABLE} SET deleted_at = NULL \
+            WHERE id = $1 AND deleted_at IS NOT NULL");
+
+        let result = sqlx::query(&query)
+        .bind(feature_id)
+        .execute(&mut *transaction).await;
+
+        match result {
+            Ok(done) if done.rows_affected() == 0 => Err(FeatureRepositoryError::not_found(format!("Feature {feature_id} not found in the trash"))),
+            Ok(_) => Ok(transaction.commit().await?),
+            Err(err) => Err(FeatureRepositoryError::from(err))
+        }
+    }
+
+    async fn restore_collection(&mut self, id: i64) -> Result<(), FeatureRepositoryError> {
+
+        let db = &self.pool;
+
+        let query = format!("UPDATE {GEOXIDATED_SCHEMA}.{COLLECTION_TABLE} SET deleted_at = NULL \
+            WHERE id = $1 AND deleted_at IS NOT NULL");
+
+        let result = sqlx::query(&query)
+        .bind(id)
+        .execute(db).await;
+
+        match result {
+            Ok(done) if done.rows_affected() == 0 => Err(FeatureRepositoryError::not_found(format!("Collection {id} not found in the trash"))),
+            Ok(_) => Ok(()),
+            Err(err) => Err(FeatureRepositoryError::from(err))
+        }
+    }
+
+    async fn purge_trash(&mut self, before: &Instant) -> Result<PurgedTrash, FeatureRepositoryError> {
+
+        let mut transaction = self.begin_edit().await?;
+
+        //links and stored filters go with the rows they reference, on delete cascade
+        let query = format!(r#"
+            WITH expired AS (
+                SELECT id FROM {GEOXIDATED_SCHEMA}.{COLLECTION_TABLE}
+                WHERE  deleted_at <= $1::timestamptz
+            )
+            , purge_features AS (
+                DELETE FROM {GEOXIDATED_SCHEMA}.{FEATURE_TABLE} fa
+                WHERE  fa.deleted_at <= $1::timestamptz
+                OR     (EXISTS (SELECT 1 FROM {GEOXIDATED_SCHEMA}.{FEATURES_IN_COLLECTION} fi
+                                WHERE fi.feature_id = fa.id AND fi.collection_id IN (SELECT id FROM expired))
+                        AND NOT EXISTS (SELECT 1 FROM {GEOXIDATED_SCHEMA}.{FEATURES_IN_COLLECTION} fi
+                                WHERE fi.feature_id = fa.id AND fi.collection_id NOT IN (SELECT id FROM expired)))
+                RETURNING fa.id
+            )
+            , purge_collections AS (
+                DELETE FROM {GEOXIDATED_SCHEMA}.{COLLECTION_TABLE} c
+                USING  expired
+                WHERE  c.id = expired.id
+                RETURNING c.id
+            )
+            SELECT (SELECT count(*) FROM purge_features), (SELECT count(*) FROM purge_collections)
+        "#);
+
+        let result = sqlx::query(&query)
+        .bind(before.as_str())
+        .fetch_one(&mut *transaction).await
+        .and_then(|row| Ok(PurgedTrash::new(row.try_get(0)?, row.try_get(1)?)));
+
+        match result {
+            Ok(purged) => {
+                transaction.commit().await?;
+                Ok(purged)
+            },
+            Err(err) => Err(FeatureRepositoryError::from(err))
+        }
+    }
+
+    async fn get_collections(&mut self, offset: i64, size: i64) -> Result<Vec<FeatureCollection>, FeatureRepositoryError> {
+        
+        let db = &self.pool;
+
+        //NOTE: using deferred join to improve pagination
+        let query = format!("SELECT id, label, properties::text FROM {GEOXIDATED_SCHEMA}.{LIVE_COLLECTION_VIEW} \
+            INNER JOIN ( SELECT id FROM {GEOXIDATED_SCHEMA}.{LIVE_COLLECTION_VIEW} LIMIT $1 OFFSET $2 \
+        ) AS tmp USING(id) ORDER BY id, label");
+
+        let result = sqlx::query(&query)
+        .bind(size)
+        .bind(offset)
+        .fetch_all(db).await;
+
+        match result {
+            Ok(rows) => {
+                let collections: Vec<FeatureCollection> = rows.iter().map(|row| {
+                    FeatureCollection::from(row)
+                }).collect();
+
+                Ok(collections)
+            },
+            Err(err) => Err(FeatureRepositoryError::from(err))
+        } 
+    }
+
+    async fn get_collections_after(&mut self, after: i64, size: i64) -> Result<Vec<FeatureCollection>, FeatureRepositoryError> {
+
+        let db = &self.pool;
+
+        let query = format!("SELECT id, label, properties::text FROM {GEOXIDATED_SCHEMA}.{LIVE_COLLECTION_VIEW} \
+            WHERE id > $1 ORDER BY id LIMIT $2");
+
+        let result = sqlx::query(&query)
+        .bind(after)
+        .bind(size)
+        .fetch_all(db).await;
+
+        match result {
+            Ok(rows) => Ok(rows.iter().map(FeatureCollection::from).collect()),
+            Err(err) => Err(FeatureRepositoryError::from(err))
+        }
+    }
+
+    async fn search_collections(&mut self, search: &CollectionSearch, offset: i64, size: i64) -> Result<Vec<FeatureCollection>, FeatureRepositoryError> {
+        
+        let db = &self.pool;
+
+        //properties is exposed as jsonb so the filter operators apply
+        let mut query: QueryBuilder<Postgres> = QueryBuilder::new(format!(r#"SELECT c.id, c.label, c.properties::text FROM
+            (SELECT id, label, properties::jsonb AS properties FROM {GEOXIDATED_SCHEMA}.{LIVE_COLLECTION_VIEW}) c
+            WHERE TRUE"#));
+
+        if let Some(label) = &search.label {
+            query.push(" AND c.label ILIKE ").push_bind(format!("%{}%", escape_like(label))).push(r" ESCAPE '\'");
+        }
+
+        if let Some(expression) = &search.expression {
+            query.push(" AND (");
+            bound(expression)?;
+            expression.to_sql_query(&mut query);
+            query.push(")");
+        }
+
+        //a collection is in the bbox when any of its features is
+        if let Some(bbox) = &search.bbox {
+            query.push(format!(r#" AND EXISTS (SELECT 1 FROM {GEOXIDATED_SCHEMA}.{FEATURE_TABLE} fa
+                INNER JOIN {GEOXIDATED_SCHEMA}.{LIVE_FEATURES_IN_COLLECTION_VIEW} fi ON fi.feature_id = fa.id
+                WHERE fi.collection_id = c.id AND ST_Intersects(fa.geometry, ST_GeomFromText("#));
+            query.push_bind(bbox.to_wkt()).push(", 4326)))");
+        }
+
+        query.push(" ORDER BY c.id LIMIT ").push_bind(size).push(" OFFSET ").push_bind(offset);
+
+        let result = query.build()
+        .fetch_all(db).await;
+
+        match result {
+            Ok(rows) => {
+                let collections: Vec<FeatureCollection> = rows.iter().map(|row| {
+                    FeatureCollection::from(row)
+                }).collect();
+
+                Ok(collections)
+            },
+            Err(err) => Err(FeatureRepositoryError::from(err))
+        } 
+    }
+
+    async fn get_collection_by_id(&mut self, id: i64) -> Result<FeatureCollection, FeatureRepositoryError> {
+        
+        let db = &self.pool;
+
+        let query = format!("SELECT id, label, properties::text FROM {GEOXIDATED_SCHEMA}.{LIVE_COLLECTION_VIEW} WHERE id = $1");
+
+        let result = sqlx::query(&query)
+        .bind(id)
+        .fetch_one(db).await;
+
+        match result {
+            Ok(row) => { Ok(FeatureCollection::from(&row))},
+            Err(err) => Err(FeatureRepositoryError::from_lookup(err, format!("Collection {id}")))
+        } 
+    }
+
+    async fn save_filter(&mut self, collection_id: i64, name: &str, filter: &Filter) -> Result<StoredFilter, FeatureRepositoryError> {
+        
+        let db = &self.pool;
+
+        let query = format!(r#"INSERT INTO {GEOXIDATED_SCHEMA}.{COLLECTION_FILTER_TABLE}(collection_id, name, filter)
+            VALUES($1, $2, $3::jsonb)
+            ON CONFLICT (collection_id, name) DO UPDATE SET filter = EXCLUDED.filter
+            RETURNING name, filter::text"#);
+
+        let result = sqlx::query(&query)
+        .bind(collection_id)
+        .bind(name)
+        .bind(filter.to_json())
+        .fetch_one(db).await;
+
+        match result {
+            Ok(row) => Ok(StoredFilter::from(&row)),
+            Err(err) => Err(FeatureRepositoryError::from(err))
+        }
+    }
+
+    async fn get_filter(&mut self, collection_id: i64, name: &str) -> Result<StoredFilter, FeatureRepositoryError> {
+        
+        let db = &self.pool;
+
+        let query = format!("SELECT name, filter::text FROM {GEOXIDATED_SCHEMA}.{COLLECTION_FILTER_TABLE} \
+            WHERE collection_id = $1 AND name = $2 \
+            AND collection_id IN (SELECT id FROM {GEOXIDATED_SCHEMA}.{LIVE_COLLECTION_VIEW})");
+
+        let result = sqlx::query(&query)
+        .bind(collection_id)
+        .bind(name)
+        .fetch_one(db).await;
+
+        match result {
+            Ok(row) => Ok(StoredFilter::from(&row)),
+            Err(err) => Err(FeatureRepositoryError::from_lookup(err, format!("Filter {name} in collection {collection_id}")))
+        }
+    }
+
+    async fn get_filters(&mut self, collection_id: i64) -> Result<Vec<StoredFilter>, FeatureRepositoryError> {
+        
+        let db = &self.pool;
+
+        let query = format!("SELECT name, filter::text FROM {GEOXIDATED_SCHEMA}.{COLLECTION_FILTER_TABLE} \
+            WHERE collection_id = $1 AND collection_id IN (SELECT id FROM {GEOXIDATED_SCHEMA}.{LIVE_COLLECTION_VIEW}) \
+            ORDER BY name");
+
+        let result = sqlx::query(&query)
+        .bind(collection_id)
+        .fetch_all(db).await;
+
+        match result {
+            Ok(rows) => {
+                let filters: Vec<StoredFilter> = rows.iter().map(|row| {
+                    StoredFilter::from(row)
+                }).collect();
+
+                Ok(filters)
+            },
+            Err(err) => Err(FeatureRepositoryError::from(err))
+        }
+    }
+
+    async fn delete_filter(&mut self, collection_id: i64, name: &str) -> Result<(), FeatureRepositoryError> {
+        
+        let db = &self.pool;
+
+        let query = format!("DELETE FROM {GEOXIDATED_SCHEMA}.{COLLECTION_FILTER_TABLE} \
+            WHERE collection_id = $1 AND name = $2");
+
+        let result = sqlx::query(&query)
+        .bind(collection_id)
+        .bind(name)
+        .execute(db).await;
+
+        match result {
+            Ok(_) => Ok(()),
+            Err(err) => Err(FeatureRepositoryError::from(err))
+        }
+    }
+}
+
+fn count_query<'a>(estimated: bool) -> QueryBuilder<'a, Postgres> {
+    QueryBuilder::new(if estimated { "EXPLAIN (FORMAT JSON) SELECT 1" } else { "SELECT count(*)" })
+}
+
+fn insert_feature_query() -> String {
+    format!(r#"
+            WITH data(data_properties, data_geometry) AS (
+                VALUES($1::json, ST_GeomFromGeoJSON($2))              
+            )
+            , insert_feature AS (
+                INSERT INTO {GEOXIDATED_SCHEMA}.{FEATURE_TABLE}(properties, geometry)
+                SELECT data_properties, data_geometry
+                FROM   data
+                RETURNING properties, geometry, version, id AS insert_feature_id
+                )
+            , insert_features_in_col AS (
+                INSERT INTO {GEOXIDATED_SCHEMA}.{FEATURES_IN_COLLECTION}(feature_id, collection_id)
+                SELECT insert_feature.insert_feature_id, $3 FROM insert_feature
+            )
+            SELECT insert_feature.insert_feature_id, insert_feature.properties::text, ST_AsGeoJSON(insert_feature.geometry),
+                   insert_feature.version
+            FROM   insert_feature
+        "#)
+}
+
+//locks the feature so its version can not move on before the transaction ends, nothing is checked without a version
+async fn check_version(transaction: &mut Transaction<'_, Postgres>, collection_id: i64, feature_id: i64, version: Option<i64>) -> Result<(), FeatureRepositoryError> {
+    let Some(version) = version else {
+        return Ok(());
+    };
+
+    let query = format!(r#"
+        SELECT fa.version
+        FROM   {GEOXIDATED_SCHEMA}.{FEATURE_TABLE} fa
+        INNER JOIN {GEOXIDATED_SCHEMA}.{LIVE_FEATURES_IN_COLLECTION_VIEW} fi ON fi.feature_id = fa.id AND fi.collection_id = $1
+        WHERE  fa.id = $2
+        FOR UPDATE OF fa
+    "#);
+
+    let result = sqlx::query(&query)
+    .bind(collection_id)
+    .bind(feature_id)
+    .fetch_one(&mut **transaction).await
+    .and_then(|row| row.try_get::<i64, _>(0));
+
+    match result {
+        Ok(current) => check_current_version(feature_id, current, Some(version)),
+        Err(err) => Err(FeatureRepositoryError::from_lookup(err, format!("Feature {feature_id} in collection {collection_id}")))
+    }
+}
+
+fn insert_collection_query() -> String {
+    format!("INSERT INTO {GEOXIDATED_SCHEMA}.{COLLECTION_TABLE}(label, properties)
+         VALUES($1, $2::json) RETURNING id, label, properties::text")
+}
+
+//stops at the first failure, the caller drops the transaction so nothing is kept, only that feature is reported
+//since an aborted transaction runs no further statements
+async fn insert_features(transaction: &mut Transaction<'_, Postgres>, collection_id: i64, features: &[Feature]) -> Result<Vec<Feature>, BulkInsertError> {
+    let query = insert_feature_query();
+    let mut inserted = Vec::with_capacity(features.len());
+
+    for (index, feature) in features.iter().enumerate() {
+        let row = sqlx::query(&query)
+        .bind(feature.properties.to_geo_json())
+        .bind(feature.geometry.to_geo_json())
+        .bind(collection_id)
+        .fetch_one(&mut **transaction).await
+        .map_err(|err| bulk_error(Some(index), err))?;
+
+        inserted.push(Feature::from(&row));
+    }
+
+    Ok(inserted)
+}
+
+//an unbound placeholder would compile to NULL and silently match nothing
+fn bound(expression: &Expression) -> Result<(), FeatureRepositoryError> {
+    match expression.unbound_parameter() {
+        Some(name) => Err(unbound(name)),
+        None => Ok(())
+    }
+}
+
+fn csv_field(text: &str) -> String {
+    format!("\"{}\"", text.replace('"', "\"\""))
+}
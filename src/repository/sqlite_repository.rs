@@ -0,0 +1,1171 @@
+/*
+    Copyright (c)  Abraão Isvi <avraamisvi@users.noreply.github.com>
+
+    Permission is hereby granted, free of charge, to any
+    person obtaining a copy of this software and associated
+    documentation files (the "Software"), to deal in the
+    Software without restriction, including without
+    limitation the rights to use, copy, modify, merge,
+    publish, distribute, sublicense, and/or sell copies of
+    the Software, and to permit persons to whom the Software
+    is furnished to do so, subject to the following
+    conditions:
+
+    The above copyright notice and this permission notice
+    shall be included in all copies or substantial portions
+    of the Software.
+
+    THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF
+    ANY KIND, EXPRESS OR IMPLIED, INCLUDING BUT NOT LIMITED
+    TO THE WARRANTIES OF MERCHANTABILITY, FITNESS FOR A
+    PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT
+    SHALL THE AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY
+    CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER IN AN ACTION
+    OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR
+    IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+    DEALINGS IN THE SOFTWARE.
+ */
+
+/*!
+ * SQLite storage for running without Postgres. Feature extents are kept in an R-tree for
+ * the bbox queries, filters and text searches are evaluated with `matcher` over the
+ * features of the collection. Triggers record every feature revision in `feature_history`,
+ * attributed to the editor the write transaction stores in `history_editor`. Trashed rows
+ * have a `deleted_at` and the reads go through the `live_` views that leave them out.
+ */
+
+use async_trait::async_trait;
+use derive_new::new;
+use futures::TryStreamExt;
+use serde_json::Value;
+use sqlx::{sqlite::{SqlitePool, SqliteRow}, Row, Sqlite, Transaction};
+
+use crate::data::filter_data::escape_like;
+use crate::model::{feature::Feature, feature_collection::FeatureCollection, value::ValueTrait, geometry::GeometryTrait,
+    bbox::Bbox, filter::Filter, stored_filter::StoredFilter, collection_search::CollectionSearch, search_hit::SearchHit,
+    feature_revision::FeatureRevision, instant::Instant, trash::{TrashedItem, PurgedTrash}};
+
+use super::features_repository::{FeatureRepository, FeatureRepositoryError, BulkInsertError, FeatureStream, check_current_version,
+    page, detached, bulk_error};
+use super::matcher::{matches, text_match, parse_json};
+
+static SCHEMA: &str = r#"
+    CREATE TABLE IF NOT EXISTS features_collection(
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        label TEXT NOT NULL,
+        properties TEXT NOT NULL DEFAULT '{}',
+        deleted_at TEXT
+    );
+
+    CREATE TABLE IF NOT EXISTS feature(
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        properties TEXT NOT NULL DEFAULT '{}',
+        geometry TEXT NOT NULL,
+        version INTEGER NOT NULL DEFAULT 1,
+        deleted_at TEXT
+    );
+
+    CREATE TABLE IF NOT EXISTS features_in_collection(
+        feature_id INTEGER NOT NULL REFERENCES feature(id),
+        collection_id INTEGER NOT NULL REFERENCES features_collection(id),
+        UNIQUE(feature_id, collection_id)
+    );
+
+    CREATE VIRTUAL TABLE IF NOT EXISTS feature_extent USING rtree(id, min_lng, max_lng, min_lat, max_lat);
+
+    CREATE TABLE IF NOT EXISTS collection_filter(
+        collection_id INTEGER NOT NULL REFERENCES features_collection(id),
+        name TEXT NOT NULL,
+        filter TEXT NOT NULL,
+        PRIMARY KEY(collection_id, name)
+    );
+
+    CREATE TABLE IF NOT EXISTS feature_history(
+        revision INTEGER PRIMARY KEY AUTOINCREMENT,
+        feature_id INTEGER NOT NULL,
+        collection_id INTEGER,
+        operation TEXT NOT NULL,
+        properties TEXT,
+        geometry TEXT,
+        changed_by TEXT,
+        changed_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ', 'now'))
+    );
+
+    CREATE INDEX IF NOT EXISTS feature_history_feature_idx ON feature_history(feature_id, revision);
+    CREATE INDEX IF NOT EXISTS feature_history_collection_idx ON feature_history(collection_id, feature_id, revision);
+
+    CREATE TABLE IF NOT EXISTS history_editor(
+        id INTEGER PRIMARY KEY CHECK (id = 1),
+        name TEXT
+    );
+
+    INSERT OR IGNORE INTO history_editor(id, name) VALUES(1, NULL);
+
+    CREATE TRIGGER IF NOT EXISTS feature_created AFTER INSERT ON feature BEGIN
+        INSERT INTO feature_history(feature_id, operation, properties, geometry, changed_by)
+        VALUES(NEW.id, 'create', NEW.properties, NEW.geometry, (SELECT name FROM history_editor));
+    END;
+
+    CREATE TRIGGER IF NOT EXISTS feature_attached AFTER INSERT ON features_in_collection BEGIN
+        INSERT INTO feature_history(feature_id, collection_id, operation, changed_by)
+        VALUES(NEW.feature_id, NEW.collection_id, 'attach', (SELECT name FROM history_editor));
+    END;
+
+    CREATE TRIGGER IF NOT EXISTS feature_detached AFTER DELETE ON features_in_collection BEGIN
+        INSERT INTO feature_history(feature_id, collection_id, operation, changed_by)
+        VALUES(OLD.feature_id, OLD.collection_id, 'detach', (SELECT name FROM history_editor));
+    END;
+"#;
+
+//run once the trash columns exist; the triggers are replaced as files from before the trash recorded hard deletes
+static TRASH_SCHEMA: &str = r#"
+    DROP TRIGGER IF EXISTS feature_updated;
+    CREATE TRIGGER feature_updated AFTER UPDATE ON feature BEGIN
+        INSERT INTO feature_history(feature_id, operation, properties, geometry, changed_by)
+        VALUES(NEW.id,
+            CASE WHEN NEW.deleted_at IS NOT NULL THEN 'delete' WHEN OLD.deleted_at IS NOT NULL THEN 'restore' ELSE 'update' END,
+            NEW.properties, NEW.geometry, (SELECT name FROM history_editor));
+    END;
+
+    DROP TRIGGER IF EXISTS feature_deleted;
+    CREATE TRIGGER feature_deleted AFTER DELETE ON feature BEGIN
+        INSERT INTO feature_history(feature_id, operation, properties, geometry, changed_by)
+        VALUES(OLD.id, 'purge', OLD.properties, OLD.geometry, (SELECT name FROM history_editor));
+    END;
+
+    CREATE VIEW IF NOT EXISTS live_features_collection AS
+        SELECT id, label, properties FROM features_collection WHERE deleted_at IS NULL;
+
+    CREATE VIEW IF NOT EXISTS live_features_in_collection AS
+        SELECT fi.feature_id, fi.collection_id FROM features_in_collection fi
+        INNER JOIN feature fa ON fa.id = fi.feature_id AND fa.deleted_at IS NULL
+        INNER JOIN features_collection c ON c.id = fi.collection_id AND c.deleted_at IS NULL;
+"#;
+
+//features stored before the history existed start with the state they had when it was created
+static HISTORY_BACKFILL: &str = r#"
+    INSERT INTO feature_history(feature_id, operation, properties, geometry)
+    SELECT id, 'create', properties, geometry FROM feature
+    WHERE NOT EXISTS (SELECT 1 FROM feature_history) ORDER BY id;
+
+    INSERT INTO feature_history(feature_id, collection_id, operation)
+    SELECT feature_id, collection_id, 'attach' FROM features_in_collection
+    WHERE NOT EXISTS (SELECT 1 FROM feature_history WHERE collection_id IS NOT NULL) ORDER BY feature_id;
+"#;
+
+static FEATURE_COLUMNS: &str = "fa.id, fa.properties, fa.geometry, fa.version";
+
+/// Creates the tables on a new database file, existing ones are kept.
+pub async fn create_schema(pool: &SqlitePool) -> Result<(), FeatureRepositoryError> {
+    let result = async {
+        sqlx::query(SCHEMA).execute(pool).await?;
+
+        //files created before features had a version or anything could be trashed
+        add_column(pool, "feature", "version", "INTEGER NOT NULL DEFAULT 1").await?;
+        add_column(pool, "feature", "deleted_at", "TEXT").await?;
+        add_column(pool, "features_collection", "deleted_at", "TEXT").await?;
+
+        sqlx::query(TRASH_SCHEMA).execute(pool).await?;
+        sqlx::query(HISTORY_BACKFILL).execute(pool).await
+    }.await;
+
+    match result {
+        Ok(_) => Ok(()),
+        Err(err) => Err(FeatureRepositoryError::from(err))
+    }
+}
+
+async fn add_column(pool: &SqlitePool, table: &str, column: &str, definition: &str) -> Result<(), sqlx::Error> {
+    let exists: bool = sqlx::query("SELECT EXISTS (SELECT 1 FROM pragma_table_info(?) WHERE name = ?)")
+    .bind(table)
+    .bind(column)
+    .fetch_one(pool).await?
+    .try_get(0)?;
+
+    if !exists {
+        sqlx::query(&format!("ALTER TABLE {table} ADD COLUMN {column} {definition}")).execute(pool).await?;
+    }
+
+    Ok(())
+}
+
+#[derive(new)]
+pub struct SqliteFeatureRepository {
+    pool: SqlitePool,
+    #[new(default)]
+    editor: Option<String>
+}
+
+impl SqliteFeatureRepository {
+
+    //writers are serialized, so the editor stored by this transaction is the one its triggers read
+    async fn begin_edit(&self) -> Result<Transaction<'static, Sqlite>, sqlx::Error> {
+        let mut transaction = self.pool.begin().await?;
+
+        sqlx::query("UPDATE history_editor SET name = ?")
+        .bind(&self.editor)
+        .execute(&mut *transaction).await?;
+
+        Ok(transaction)
+    }
+
+    async fn all_features_in_collection(&self, collection_id: i64) -> Result<Vec<SqliteRow>, FeatureRepositoryError> {
+        let query = format!("SELECT {FEATURE_COLUMNS} FROM feature fa \
+            INNER JOIN live_features_in_collection fi ON fi.feature_id = fa.id AND fi.collection_id = ? \
+            ORDER BY fa.id");
+
+        sqlx::query(&query)
+        .bind(collection_id)
+        .fetch_all(&self.pool).await
+        .map_err(FeatureRepositoryError::from)
+    }
+}
+
+#[async_trait]
+impl FeatureRepository for SqliteFeatureRepository {
+
+    fn set_editor(&mut self, editor: Option<String>) {
+        self.editor = editor;
+    }
+
+    async fn get_features_in_collection(&mut self, id: i64, offset: i64, size: i64) -> Result<Vec<Feature>, FeatureRepositoryError> {
+        
+        let query = format!("SELECT {FEATURE_COLUMNS} FROM feature fa \
+            INNER JOIN live_features_in_collection fi ON fi.feature_id = fa.id AND fi.collection_id = ? \
+            ORDER BY fa.id LIMIT ? OFFSET ?");
+
+        let result = sqlx::query(&query)
+        .bind(id)
+        .bind(size)
+        .bind(offset)
+        .fetch_all(&self.pool).await;
+
+        match result {
+            Ok(rows) => Ok(rows.iter().map(Feature::from).collect()),
+            Err(err) => Err(FeatureRepositoryError::from(err))
+        }
+    }
+
+    async fn get_features_in_collection_by_bbox(&mut self, collection_id: i64, bbox: &Bbox, offset: i64, size: i64) -> Result<Vec<Feature>, FeatureRepositoryError> {
+        
+        let [min_lng, min_lat, max_lng, max_lat] = bbox.bounds();
+        let query = format!("SELECT {FEATURE_COLUMNS} FROM feature fa \
+            INNER JOIN live_features_in_collection fi ON fi.feature_id = fa.id AND fi.collection_id = ? \
+            INNER JOIN feature_extent fe ON fe.id = fa.id \
+            WHERE fe.max_lng >= ? AND fe.min_lng <= ? AND fe.max_lat >= ? AND fe.min_lat <= ? \
+            ORDER BY fa.id LIMIT ? OFFSET ?");
+
+        let result = sqlx::query(&query)
+        .bind(collection_id)
+        .bind(min_lng)
+        .bind(max_lng)
+        .bind(min_lat)
+        .bind(max_lat)
+        .bind(size)
+        .bind(offset)
+        .fetch_all(&self.pool).await;
+
+        match result {
+            Ok(rows) => Ok(rows.iter().map(Feature::from).collect()),
+            Err(err) => Err(FeatureRepositoryError::from(err))
+        }
+    }
+
+    async fn get_features_in_collection_after(&mut self, id: i64, after: i64, size: i64) -> Result<Vec<Feature>, FeatureRepositoryError> {
+
+        let query = format!("SELECT {FEATURE_COLUMNS} FROM live_features_in_collection fi \
+            INNER JOIN feature fa ON fa.id = fi.feature_id \
+            WHERE fi.collection_id = ? AND fi.feature_id > ? \
+            ORDER BY fi.feature_id LIMIT ?");
+
+        let result = sqlx::query(&query)
+        .bind(id)
+        .bind(after)
+        .bind(size)
+        .fetch_all(&self.pool).await;
+
+        match result {
+            Ok(rows) => Ok(rows.iter().map(Feature::from).collect()),
+            Err(err) => Err(FeatureRepositoryError::from(err))
+        }
+    }
+
+    async fn get_features_in_collection_by_bbox_after(&mut self, collection_id: i64, bbox: &Bbox, after: i64, size: i64) -> Result<Vec<Feature>, FeatureRepositoryError> {
+
+        let [min_lng, min_lat, max_lng, max_lat] = bbox.bounds();
+        let query = format!("SELECT {FEATURE_COLUMNS} FROM live_features_in_collection fi \
+            INNER JOIN feature fa ON fa.id = fi.feature_id \
+            INNER JOIN feature_extent fe ON fe.id = fa.id \
+            WHERE fi.collection_id = ? AND fi.feature_id > ? \
+            AND fe.max_lng >= ? AND fe.min_lng <= ? AND fe.max_lat >= ? AND fe.min_lat <= ? \
+            ORDER BY fi.feature_id LIMIT ?");
+
+        let result = sqlx::query(&query)
+        .bind(collection_id)
+        .bind(after)
+        .bind(min_lng)
+        .bind(max_lng)
+        .bind(min_lat)
+        .bind(max_lat)
+        .bind(size)
+        .fetch_all(&self.pool).await;
+
+        match result {
+            Ok(rows) => Ok(rows.iter().map(Feature::from).collect()),
+            Err(err) => Err(FeatureRepositoryError::from(err))
+        }
+    }
+
+    async fn get_features_in_collection_by_filter(&mut self, collection_id: i64, filter: &Filter, offset: i64, size: i64) -> Result<Vec<Feature>, FeatureRepositoryError> {
+        
+        let rows = self.all_features_in_collection(collection_id).await?;
+
+        let mut features = vec![];
+        for row in rows.iter() {
+            let (properties, geometry) = row_values(row)?;
+
+            if matches(&filter.expressions, &properties, Some(&geometry))? {
+                features.push(Feature::from(row));
+            }
+        }
+
+        Ok(page(features, offset, size).collect())
+    }
+
+    //SQLite has no cheaper estimate, counts are always exact
+    async fn count_features_in_collection(&mut self, id: i64, _estimated: bool) -> Result<i64, FeatureRepositoryError> {
+
+        let result = sqlx::query("SELECT count(*) FROM live_features_in_collection WHERE collection_id = ?")
+        .bind(id)
+        .fetch_one(&self.pool).await;
+
+        result.and_then(|row| row.try_get(0)).map_err(FeatureRepositoryError::from)
+    }
+
+    async fn count_features_in_collection_by_bbox(&mut self, collection_id: i64, bbox: &Bbox, _estimated: bool) -> Result<i64, FeatureRepositoryError> {
+
+        let [min_lng, min_lat, max_lng, max_lat] = bbox.bounds();
+        let result = sqlx::query("SELECT count(*) FROM live_features_in_collection fi \
+            INNER JOIN feature_extent fe ON fe.id = fi.feature_id \
+            WHERE fi.collection_id = ? \
+            AND fe.max_lng >= ? AND fe.min_lng <= ? AND fe.max_lat >= ? AND fe.min_lat <= ?")
+        .bind(collection_id)
+        .bind(min_lng)
+        .bind(max_lng)
+        .bind(min_lat)
+        .bind(max_lat)
+        .fetch_one(&self.pool).await;
+
+        result.and_then(|row| row.try_get(0)).map_err(FeatureRepositoryError::from)
+    }
+
+    async fn count_features_in_collection_by_filter(&mut self, collection_id: i64, filter: &Filter, _estimated: bool) -> Result<i64, FeatureRepositoryError> {
+
+        let mut count = 0;
+        for row in self.all_features_in_collection(collection_id).await?.iter() {
+            let (properties, geometry) = row_values(row)?;
+
+            if matches(&filter.expressions, &properties, Some(&geometry))? {
+                count += 1;
+            }
+        }
+
+        Ok(count)
+    }
+
+    async fn search_features_in_collection(&mut self, collection_id: i64, text: &str,
+        filter: Option<&Filter>, offset: i64, size: i64) -> Result<Vec<SearchHit>, FeatureRepositoryError> {
+        
+        let rows = self.all_features_in_collection(collection_id).await?;
+
+        let mut hits = vec![];
+        for row in rows.iter() {
+            let (properties, geometry) = row_values(row)?;
+
+            let accepted = match filter {
+                Some(filter) => matches(&filter.expressions, &properties, Some(&geometry))?,
+                None => true
+            };
+
+            if let Some((rank, snippet)) = text_match(text, &properties).filter(|_| accepted) {
+                hits.push(SearchHit::new(Feature::from(row), rank, snippet));
+            }
+        }
+
+        //the sort is stable, so equal ranks keep the id order
+        hits.sort_by(|left, right| right.rank.total_cmp(&left.rank));
+
+        Ok(page(hits, offset, size).collect())
+    }
+
+    async fn get_feature_by_id(&mut self, collection_id: i64, feature_id: i64) -> Result<Feature, FeatureRepositoryError> {
+        
+        let query = format!("SELECT {FEATURE_COLUMNS} FROM feature fa \
+            INNER JOIN live_features_in_collection fi ON fi.feature_id = fa.id AND fi.collection_id = ? \
+            WHERE fa.id = ?");
+
+        let result = sqlx::query(&query)
+        .bind(collection_id)
+        .bind(feature_id)
+        .fetch_one(&self.pool).await;
+
+        match result {
+            Ok(row) => Ok(Feature::from(&row)),
+            Err(err) => Err(FeatureRepositoryError::from_lookup(err, format!("Feature {feature_id} in collection {collection_id}")))
+        }
+    }
+
+    async fn get_feature_history(&mut self, collection_id: i64, feature_id: i64) -> Result<Vec<FeatureRevision>, FeatureRepositoryError> {
+
+        let result = sqlx::query("SELECT revision, collection_id, operation, properties, geometry, changed_by, changed_at \
+            FROM feature_history WHERE feature_id = ?1 \
+            AND EXISTS (SELECT 1 FROM feature_history WHERE feature_id = ?1 AND collection_id = ?2) \
+            ORDER BY revision")
+        .bind(feature_id)
+        .bind(collection_id)
+        .fetch_all(&self.pool).await;
+
+        match result {
+            Ok(rows) if rows.is_empty() => Err(FeatureRepositoryError::not_found(format!("Feature {feature_id} not found in collection {collection_id}"))),
+            Ok(rows) => Ok(rows.iter().map(FeatureRevision::from).collect()),
+            Err(err) => Err(FeatureRepositoryError::from(err))
+        }
+    }
+
+    //the last membership event of each feature up to then must be an attach and its last content event not a delete or a purge,
+    //the instants are stored in the same format so they compare as text
+    async fn get_features_in_collection_as_of(&mut self, collection_id: i64, as_of: &Instant, offset: i64, size: i64) -> Result<Vec<Feature>, FeatureRepositoryError> {
+
+        let result = sqlx::query("SELECT state.feature_id, content.properties, content.geometry FROM ( \
+                SELECT member.feature_id, \
+                    (SELECT operation FROM feature_history WHERE collection_id = ?1 AND feature_id = member.feature_id \
+                        AND changed_at <= ?2 ORDER BY revision DESC LIMIT 1) AS membership, \
+                    (SELECT revision FROM feature_history WHERE collection_id IS NULL AND feature_id = member.feature_id \
+                        AND changed_at <= ?2 ORDER BY revision DESC LIMIT 1) AS content_revision \
+                FROM (SELECT DISTINCT feature_id FROM feature_history WHERE collection_id = ?1 AND changed_at <= ?2) member \
+            ) state \
+            INNER JOIN feature_history content ON content.revision = state.content_revision \
+            WHERE state.membership = 'attach' AND content.operation NOT IN ('delete', 'purge') \
+            ORDER BY state.feature_id LIMIT ?3 OFFSET ?4")
+        .bind(collection_id)
+        .bind(as_of.as_str())
+        .bind(size)
+        .bind(offset)
+        .fetch_all(&self.pool).await;
+
+        match result {
+            Ok(rows) => Ok(rows.iter().map(Feature::from).collect()),
+            Err(err) => Err(FeatureRepositoryError::from(err))
+        }
+    }
+
+    async fn create_feature(&mut self, collection_id: i64, feature: &Feature) -> Result<Feature, FeatureRepositoryError> {
+
+        let mut transaction = self.begin_edit().await.map_err(FeatureRepositoryError::from)?;
+
+        let created = insert_feature(&mut transaction, collection_id, feature).await?;
+
+        match transaction.commit().await {
+            Ok(()) => Ok(created),
+            Err(err) => Err(FeatureRepositoryError::from(err))
+        }
+    }
+
+    async fn create_features(&mut self, collection_id: i64, features: &[Feature]) -> Result<Vec<Feature>, BulkInsertError> {
+
+        let mut transaction = self.begin_edit().await.map_err(|err| bulk_error(None, err))?;
+
+        let inserted = insert_features(&mut transaction, collection_id, features).await?;
+
+        transaction.commit().await.map_err(|err| bulk_error(None, err))?;
+        Ok(inserted)
+    }
+
+    async fn import_features(&mut self, collection_id: i64, mut features: FeatureStream<'_>) -> Result<u64, BulkInsertError> {
+
+        let mut transaction = self.begin_edit().await.map_err(|err| bulk_error(None, err))?;
+
+        let mut index = 0;
+        while let Some(feature) = features.try_next().await? {
+            insert_feature(&mut transaction, collection_id, &feature).await
+                .map_err(|err| BulkInsertError { index: Some(index), message: err.message })?;
+            index += 1;
+        }
+
+        transaction.commit().await.map_err(|err| bulk_error(None, err))?;
+        Ok(index as u64)
+    }
+
+    async fn update_feature(&mut self, collection_id: i64, feature: &Feature) -> Result<Feature, FeatureRepositoryError> {
+        
+        let geo_json = feature.geometry.to_geo_json();
+        let properties_json = feature.properties.to_geo_json();
+        let feature_id: Option<i64> = (&feature.id).into();
+        let extent = extent(&parse_json(&geo_json)?)?;
+
+        let result: Result<SqliteRow, FeatureRepositoryError> = async {
+            let mut transaction = self.begin_edit().await?;
+
+            let current = current_version(&mut transaction, collection_id, feature_id.unwrap_or_default()).await?
+                .ok_or_else(|| FeatureRepositoryError::not_found(format!("Feature {} in collection {collection_id} not found", feature.id)))?;
+            check_current_version(feature_id.unwrap_or_default(), current, feature.version)?;
+
+            let row = sqlx::query("UPDATE feature SET properties = json(?), geometry = json(?), version = version + 1 WHERE id = ? \
+                RETURNING id, properties, geometry, version")
+            .bind(&properties_json)
+            .bind(&geo_json)
+            .bind(feature_id)
+            .fetch_one(&mut *transaction).await?;
+
+            let feature_id: i64 = row.try_get(0)?;
+            save_extent(&mut transaction, feature_id, extent).await?;
+
+            transaction.commit().await?;
+            Ok(row)
+        }.await;
+
+        result.map(|row| Feature::from(&row))
+    }
+
+    async fn delete_feature(&mut self, collection_id: i64, feature_id: i64, version: Option<i64>) -> Result<(), FeatureRepositoryError> {
+
+        let result: Result<bool, FeatureRepositoryError> = async {
+            let mut transaction = self.begin_edit().await?;
+
+            //a missing or trashed feature is reported below
+            let Some(current) = current_version(&mut transaction, collection_id, feature_id).await? else {
+                return Ok(false);
+            };
+            check_current_version(feature_id, current, version)?;
+
+            let shared: bool = sqlx::query("SELECT EXISTS (SELECT 1 FROM features_in_collection WHERE feature_id = ? AND collection_id <> ?)")
+            .bind(feature_id)
+            .bind(collection_id)
+            .fetch_one(&mut *transaction).await?
+            .try_get(0)?;
+
+            //a feature another collection holds is only unlinked, otherwise it keeps the link and goes to the trash
+            let query = if shared {
+                "DELETE FROM features_in_collection WHERE feature_id = ?1 AND collection_id = ?2"
+            } else {
+                "UPDATE feature SET deleted_at = strftime('%Y-%m-%dT%H:%M:%fZ', 'now') WHERE id = ?1"
+            };
+
+            sqlx::query(query)
+            .bind(feature_id)
+            .bind(collection_id)
+            .execute(&mut *transaction).await?;
+
+            transaction.commit().await?;
+            Ok(true)
+        }.await;
+
+        match result {
+            Ok(false) => Err(FeatureRepositoryError::not_found(format!("Feature {feature_id} not found in collection {collection_id}"))),
+            Ok(true) => Ok(()),
+            Err(err) => Err(err)
+        }
+    }
+
+    async fn attach_feature(&mut self, collection_id: i64, feature_id: i64) -> Result<(), FeatureRepositoryError> {
+
+        let result: Result<(bool, bool), sqlx::Error> = async {
+            let mut transaction = self.begin_edit().await?;
+
+            let row = sqlx::query("SELECT EXISTS (SELECT 1 FROM feature WHERE id = ? AND deleted_at IS NULL), \
+                EXISTS (SELECT 1 FROM live_features_collection WHERE id = ?)")
+            .bind(feature_id)
+            .bind(collection_id)
+            .fetch_one(&mut *transaction).await?;
+
+            let found: (bool, bool) = (row.try_get(0)?, row.try_get(1)?);
+            if found == (true, true) {
+                sqlx::query("INSERT OR IGNORE INTO features_in_collection(feature_id, collection_id) VALUES(?, ?)")
+                .bind(feature_id)
+                .bind(collection_id)
+                .execute(&mut *transaction).await?;
+            }
+
+            transaction.commit().await?;
+            Ok(found)
+        }.await;
+
+        match result {
+            Ok((_, false)) => Err(FeatureRepositoryError::not_found(format!("Collection {collection_id} not found"))),
+            Ok((false, _)) => Err(FeatureRepositoryError::not_found(format!("Feature {feature_id} not found"))),
+            Ok(_) => Ok(()),
+            Err(err) => Err(FeatureRepositoryError::from(err))
+        }
+    }
+
+    async fn detach_feature(&mut self, collection_id: i64, feature_id: i64) -> Result<(), FeatureRepositoryError> {
+
+        let result: Result<(i64, i64), sqlx::Error> = async {
+            let mut transaction = self.begin_edit().await?;
+
+            let row = sqlx::query("SELECT (SELECT count(*) FROM live_features_in_collection WHERE collection_id = ?1 AND feature_id = ?2), \
+                count(*) FROM features_in_collection WHERE feature_id = ?2")
+            .bind(collection_id)
+            .bind(feature_id)
+            .fetch_one(&mut *transaction).await?;
+
+            //the last link is kept, the feature would be left in no collection; links to trashed collections count
+            let membership: (i64, i64) = (row.try_get(0)?, row.try_get(1)?);
+            if membership.0 > 0 && membership.1 > 1 {
+                sqlx::query("DELETE FROM features_in_collection WHERE collection_id = ? AND feature_id = ?")
+                .bind(collection_id)
+                .bind(feature_id)
+                .execute(&mut *transaction).await?;
+            }
+
+            transaction.commit().await?;
+            Ok(membership)
+        }.await;
+
+        match result {
+            Ok(membership) => detached(collection_id, feature_id, membership),
+            Err(err) => Err(FeatureRepositoryError::from(err))
+        }
+    }
+
+    async fn get_collections_of_feature(&mut self, feature_id: i64) -> Result<Vec<FeatureCollection>, FeatureRepositoryError> {
+
+        let result = sqlx::query("SELECT c.id, c.label, c.properties FROM live_features_collection c \
+            INNER JOIN live_features_in_collection link ON link.collection_id = c.id \
+            WHERE link.feature_id = ? ORDER BY c.id")
+        .bind(feature_id)
+        .fetch_all(&self.pool).await;
+
+        //every stored feature is in a collection, a trashed one is in none that can be read
+        match result {
+            Ok(rows) if rows.is_empty() => Err(FeatureRepositoryError::not_found(format!("Feature {feature_id} not found"))),
+            Ok(rows) => Ok(rows.iter().map(FeatureCollection::from).collect()),
+            Err(err) => Err(FeatureRepositoryError::from(err))
+        }
+    }
+
+    async fn create_collection(&mut self, collection: &FeatureCollection) -> Result<FeatureCollection, FeatureRepositoryError> {
+        
+        let result = sqlx::query("INSERT INTO features_collection(label, properties) VALUES(?, json(?)) \
+            RETURNING id, label, properties")
+        .bind(&collection.label)
+        .bind(collection.properties.to_geo_json())
+        .fetch_one(&self.pool).await;
+
+        match result {
+            Ok(row) => Ok(FeatureCollection::from(&row)),
+            Err(err) => Err(FeatureRepositoryError::from(err))
+        }
+    }
+
+    async fn create_collection_with_features(&mut self, collection: &FeatureCollection) -> Result<FeatureCollection, BulkInsertError> {
+
+        let mut transaction = self.begin_edit().await.map_err(|err| bulk_error(None, err))?;
+
+        let row = sqlx::query("INSERT INTO features_collection(label, properties) VALUES(?, json(?)) \
+            RETURNING id, label, properties")
+        .bind(&collection.label)
+        .bind(collection.properties.to_geo_json())
+        .fetch_one(&mut *transaction).await
+        .map_err(|err| bulk_error(None, err))?;
+
+        let mut created = FeatureCollection::from(&row);
+        let id: Option<i64> = (&created.id).into();
+        created.features = insert_features(&mut transaction, id.unwrap_or_default(), &collection.features).await?;
+
+        transaction.commit().await.map_err(|err| bulk_error(None, err))?;
+        Ok(created)
+    }
+
+    async fn update_collection(&mut self, collection: &FeatureCollection) -> Result<FeatureCollection, FeatureRepositoryError> {
+        
+        let id: Option<i64> = (&collection.id).into();
+
+        let result = sqlx::query("UPDATE features_collection SET label = ?, properties = json(?) WHERE id = ? AND deleted_at IS NULL \
+            RETURNING id, label, properties")
+        .bind(&collection.label)
+        .bind(collection.properties.to_geo_json())
+        .bind(id)
+        .fetch_one(&self.pool).await;
+
+        match result {
+            Ok(row) => Ok(FeatureCollection::from(&row)),
+            Err(err) => Err(FeatureRepositoryError::from_lookup(err, format!("Collection {}", collection.id)))
+        }
+    }
+
+    async fn delete_collection(&mut self, id: i64) -> Result<(), FeatureRepositoryError> {
+
+        let result = sqlx::query("UPDATE features_collection SET deleted_at = strftime('%Y-%m-%dT%H:%M:%fZ', 'now') \
+            WHERE id = ? AND deleted_at IS NULL")
+        .bind(id)
+        .execute(&self.pool).await;
+
+        match result {
+            Ok(done) if done.rows_affected() == 0 => Err(FeatureRepositoryError::not_found(format!("Collection {id} not found"))),
+            Ok(_) => Ok(()),
+            Err(err) => Err(FeatureRepositoryError::from(err))
+        }
+    }
+
+    async fn get_trash(&mut self, offset: i64, size: i64) -> Result<Vec<TrashedItem>, FeatureRepositoryError> {
+
+        //a trashed feature keeps the link to the collection it was deleted from
+        let result = sqlx::query("SELECT kind, id, label, collection_id, deleted_at FROM ( \
+                SELECT 'collection' AS kind, id, label, NULL AS collection_id, deleted_at \
+                FROM features_collection WHERE deleted_at IS NOT NULL \
+                UNION ALL \
+                SELECT 'feature', fa.id, NULL, \
+                    (SELECT min(fi.collection_id) FROM features_in_collection fi WHERE fi.feature_id = fa.id), fa.deleted_at \
+                FROM feature fa WHERE fa.deleted_at IS NOT NULL \
+            ) ORDER BY deleted_at DESC, kind, id LIMIT ? OFFSET ?")
+        .bind(size)
+        .bind(offset)
+        .fetch_all(&self.pool).await;
+
+        match result {
+            Ok(rows) => Ok(rows.iter().map(TrashedItem::from).collect()),
+            Err(err) => Err(FeatureRepositoryError::from(err))
+        }
+    }
+
+    async fn restore_feature(&mut self, feature_id: i64) -> Result<(), FeatureRepositoryError> {
+
+        let result: Result<u64, sqlx::Error> = async {
+            let mut transaction = self.begin_edit().await?;
+
+            let restored = sqlx::query("UPDATE feature SET deleted_at = NULL WHERE id = ? AND deleted_at IS NOT NULL")
+            .bind(feature_id)
+            .execute(&mut *transaction).await?
+            .rows_affected();
+
+            transaction.commit().await?;
+            Ok(restored)
+        }.await;
+
+        match result {
+            Ok(0) => Err(FeatureRepositoryError::not_found(format!("Feature {feature_id} not found in the trash"))),
+            Ok(_) => Ok(()),
+            Err(err) => Err(FeatureRepositoryError::from(err))
+        }
+    }
+
+    async fn restore_collection(&mut self, id: i64) -> Result<(), FeatureRepositoryError> {
+
+        let result = sqlx::query("UPDATE features_collection SET deleted_at = NULL WHERE id = ? AND deleted_at IS NOT NULL")
+        .bind(id)
+        .execute(&self.pool).await;
+
+        match result {
+            Ok(done) if done.rows_affected() == 0 => Err(FeatureRepositoryError::not_found(format!("Collection {id} not found in the trash"))),
+            Ok(_) => Ok(()),
+            Err(err) => Err(FeatureRepositoryError::from(err))
+        }
+    }
+
+    async fn purge_trash(&mut self, before: &Instant) -> Result<PurgedTrash, FeatureRepositoryError> {
+
+        let result: Result<PurgedTrash, sqlx::Error> = async {
+            let mut transaction = self.begin_edit().await?;
+
+            let row = sqlx::query("SELECT \
+                (SELECT json_group_array(id) FROM feature WHERE deleted_at <= ?1), \
+                (SELECT json_group_array(id) FROM features_collection WHERE deleted_at <= ?1)")
+            .bind(before.as_str())
+            .fetch_one(&mut *transaction).await?;
+
+            let (features, collections): (String, String) = (row.try_get(0)?, row.try_get(1)?);
+
+            //the features of the purged collections go too when no other collection holds them
+            let held: String = sqlx::query("SELECT json_group_array(DISTINCT feature_id) FROM features_in_collection \
+                WHERE collection_id IN (SELECT value FROM json_each(?))")
+            .bind(&collections)
+            .fetch_one(&mut *transaction).await?
+            .try_get(0)?;
+
+            sqlx::query("DELETE FROM features_in_collection \
+                WHERE feature_id IN (SELECT value FROM json_each(?1)) OR collection_id IN (SELECT value FROM json_each(?2))")
+            .bind(&features)
+            .bind(&collections)
+            .execute(&mut *transaction).await?;
+
+            let purged_features = delete_orphans(&mut transaction, &features).await?
+                + delete_orphans(&mut transaction, &held).await?;
+
+            sqlx::query("DELETE FROM collection_filter WHERE collection_id IN (SELECT value FROM json_each(?))")
+            .bind(&collections)
+            .execute(&mut *transaction).await?;
+
+            let purged_collections = sqlx::query("DELETE FROM features_collection WHERE id IN (SELECT value FROM json_each(?))")
+            .bind(&collections)
+            .execute(&mut *transaction).await?
+            .rows_affected();
+
+            transaction.commit().await?;
+            Ok(PurgedTrash::new(purged_features as i64, purged_collections as i64))
+        }.await;
+
+        result.map_err(FeatureRepositoryError::from)
+    }
+
+    async fn get_collections(&mut self, offset: i64, size: i64) -> Result<Vec<FeatureCollection>, FeatureRepositoryError> {
+        
+        let result = sqlx::query("SELECT id, label, properties FROM live_features_collection ORDER BY id LIMIT ? OFFSET ?")
+        .bind(size)
+        .bind(offset)
+        .fetch_all(&self.pool).await;
+
+        match result {
+            Ok(rows) => Ok(rows.iter().map(FeatureCollection::from).collect()),
+            Err(err) => Err(FeatureRepositoryError::from(err))
+        }
+    }
+
+    async fn get_collections_after(&mut self, after: i64, size: i64) -> Result<Vec<FeatureCollection>, FeatureRepositoryError> {
+
+        let result = sqlx::query("SELECT id, label, properties FROM live_features_collection WHERE id > ? ORDER BY id LIMIT ?")
+        .bind(after)
+        .bind(size)
+        .fetch_all(&self.pool).await;
+
+        match result {
+            Ok(rows) => Ok(rows.iter().map(FeatureCollection::from).collect()),
+            Err(err) => Err(FeatureRepositoryError::from(err))
+        }
+    }
+
+    async fn search_collections(&mut self, search: &CollectionSearch, offset: i64, size: i64) -> Result<Vec<FeatureCollection>, FeatureRepositoryError> {
+        
+        //the label and extent are narrowed in SQL, the properties filter in Rust
+        let label = search.label.as_ref().map(|label| format!("%{}%", escape_like(label)));
+        let bounds = search.bbox.as_ref().map(|bbox| bbox.bounds());
+
+        let result = sqlx::query(r#"SELECT c.id, c.label, c.properties FROM live_features_collection c
+            WHERE (?1 IS NULL OR c.label LIKE ?1 ESCAPE '\')
+            AND (?2 IS NULL OR EXISTS (SELECT 1 FROM live_features_in_collection fi
+                INNER JOIN feature_extent fe ON fe.id = fi.feature_id
+                WHERE fi.collection_id = c.id
+                AND fe.max_lng >= ?2 AND fe.min_lng <= ?4 AND fe.max_lat >= ?3 AND fe.min_lat <= ?5))
+            ORDER BY c.id"#)
+        .bind(label)
+        .bind(bounds.map(|bounds| bounds[0]))
+        .bind(bounds.map(|bounds| bounds[1]))
+        .bind(bounds.map(|bounds| bounds[2]))
+        .bind(bounds.map(|bounds| bounds[3]))
+        .fetch_all(&self.pool).await;
+
+        let rows = result.map_err(FeatureRepositoryError::from)?;
+
+        let mut collections = vec![];
+        for row in rows.iter() {
+            let accepted = match &search.expression {
+                Some(expression) => {
+                    let properties: String = row.try_get(2).unwrap_or("{}".to_string());
+                    matches(expression, &parse_json(&properties)?, None)?
+                },
+                None => true
+            };
+
+            if accepted {
+                collections.push(FeatureCollection::from(row));
+            }
+        }
+
+        Ok(page(collections, offset, size).collect())
+    }
+
+    async fn get_collection_by_id(&mut self, id: i64) -> Result<FeatureCollection, FeatureRepositoryError> {
+        
+        let result = sqlx::query("SELECT id, label, properties FROM live_features_collection WHERE id = ?")
+        .bind(id)
+        .fetch_one(&self.pool).await;
+
+        match result {
+            Ok(row) => Ok(FeatureCollection::from(&row)),
+            Err(err) => Err(FeatureRepositoryError::from_lookup(err, format!("Collection {id}")))
+        }
+    }
+
+    async fn save_filter(&mut self, collection_id: i64, name: &str, filter: &Filter) -> Result<StoredFilter, FeatureRepositoryError> {
+        
+        let result = sqlx::query("INSERT INTO collection_filter(collection_id, name, filter) VALUES(?, ?, json(?)) \
+            ON CONFLICT (collection_id, name) DO UPDATE SET filter = excluded.filter \
+            RETURNING name, filter")
+        .bind(collection_id)
+        .bind(name)
+        .bind(filter.to_json())
+        .fetch_one(&self.pool).await;
+
+        match result {
+            Ok(row) => Ok(StoredFilter::from(&row)),
+            Err(err) => Err(FeatureRepositoryError::from(err))
+        }
+    }
+
+    async fn get_filter(&mut self, collection_id: i64, name: &str) -> Result<StoredFilter, FeatureRepositoryError> {
+        
+        let result = sqlx::query("SELECT name, filter FROM collection_filter WHERE collection_id = ? AND name = ? \
+            AND collection_id IN (SELECT id FROM live_features_collection)")
+        .bind(collection_id)
+        .bind(name)
+        .fetch_one(&self.pool).await;
+
+        match result {
+            Ok(row) => Ok(StoredFilter::from(&row)),
+            Err(err) => Err(FeatureRepositoryError::from_lookup(err, format!("Filter {name} in collection {collection_id}")))
+        }
+    }
+
+    async fn get_filters(&mut self, collection_id: i64) -> Result<Vec<StoredFilter>, FeatureRepositoryError> {
+        
+        let result = sqlx::query("SELECT name, filter FROM collection_filter WHERE collection_id = ? \
+            AND collection_id IN (SELECT id FROM live_features_collection) ORDER BY name")
+        .bind(collection_id)
+        .fetch_all(&self.pool).await;
+
+        match result {
+            Ok(rows) => Ok(rows.iter().map(StoredFilter::from).collect()),
+            Err(err) => Err(FeatureRepositoryError::from(err))
+        }
+    }
+
+    async fn delete_filter(&mut self, collection_id: i64, name: &str) -> Result<(), FeatureRepositoryError> {
+        
+        let result = sqlx::query("DELETE FROM collection_filter WHERE collection_id = ? AND name = ?")
+        .bind(collection_id)
+        .bind(name)
+        .execute(&self.pool).await;
+
+        match result {
+            Ok(_) => Ok(()),
+            Err(err) => Err(FeatureRepositoryError::from(err))
+        }
+    }
+}
+
+fn row_values(row: &SqliteRow) -> Result<(Value, Value), FeatureRepositoryError> {
+    let properties: String = row.try_get(1).unwrap_or("{}".to_string());
+    let geometry: String = row.try_get(2).unwrap_or("null".to_string());
+
+    Ok((parse_json(&properties)?, parse_json(&geometry)?))
+}
+
+//[min_lng, min_lat, max_lng, max_lat] of every coordinate in the geometry
+fn extent(geometry: &Value) -> Result<[f64; 4], FeatureRepositoryError> {
+    let mut bounds = [f64::INFINITY, f64::INFINITY, f64::NEG_INFINITY, f64::NEG_INFINITY];
+    expand(&geometry["coordinates"], &mut bounds);
+
+    if bounds[0] > bounds[2] {
+        return Err(FeatureRepositoryError::new("The geometry has no coordinates".to_string()));
+    }
+
+    Ok(bounds)
+}
+
+fn expand(coordinates: &Value, bounds: &mut [f64; 4]) {
+    match (coordinates[0].as_f64(), coordinates[1].as_f64()) {
+        (Some(lng), Some(lat)) => {
+            bounds[0] = bounds[0].min(lng);
+            bounds[1] = bounds[1].min(lat);
+            bounds[2] = bounds[2].max(lng);
+            bounds[3] = bounds[3].max(lat);
+        },
+        _ => coordinates.as_array().into_iter().flatten().for_each(|inner| expand(inner, bounds))
+    }
+}
+
+async fn save_extent(transaction: &mut sqlx::Transaction<'_, sqlx::Sqlite>, feature_id: i64, extent: [f64; 4]) -> Result<(), sqlx::Error> {
+    sqlx::query("INSERT OR REPLACE INTO feature_extent(id, min_lng, max_lng, min_lat, max_lat) VALUES(?, ?, ?, ?, ?)")
+    .bind(feature_id)
+    .bind(extent[0])
+    .bind(extent[2])
+    .bind(extent[1])
+    .bind(extent[3])
+    .execute(&mut **transaction).await?;
+
+    Ok(())
+}
+
+//inserts the feature, its extent and its link to the collection
+async fn insert_feature(transaction: &mut sqlx::Transaction<'_, sqlx::Sqlite>, collection_id: i64, feature: &Feature) -> Result<Feature, FeatureRepositoryError> {
+    let geo_json = feature.geometry.to_geo_json();
+    let properties_json = feature.properties.to_geo_json();
+    let extent = extent(&parse_json(&geo_json)?)?;
+
+    let result: Result<SqliteRow, sqlx::Error> = async {
+        let row = sqlx::query("INSERT INTO feature(properties, geometry) VALUES(json(?), json(?)) \
+            RETURNING id, properties, geometry, version")
+        .bind(&properties_json)
+        .bind(&geo_json)
+        .fetch_one(&mut **transaction).await?;
+
+        let feature_id: i64 = row.try_get(0)?;
+        save_extent(transaction, feature_id, extent).await?;
+
+        sqlx::query("INSERT INTO features_in_collection(feature_id, collection_id) VALUES(?, ?)")
+        .bind(feature_id)
+        .bind(collection_id)
+        .execute(&mut **transaction).await?;
+
+        Ok(row)
+    }.await;
+
+    match result {
+        Ok(row) => Ok(Feature::from(&row)),
+        Err(err) => Err(FeatureRepositoryError::from(err))
+    }
+}
+
+//stops at the first failure, the caller drops the transaction so nothing is kept
+async fn insert_features(transaction: &mut sqlx::Transaction<'_, sqlx::Sqlite>, collection_id: i64, features: &[Feature]) -> Result<Vec<Feature>, BulkInsertError> {
+    let mut inserted = Vec::with_capacity(features.len());
+
+    for (index, feature) in features.iter().enumerate() {
+        let created = insert_feature(transaction, collection_id, feature).await
+            .map_err(|err| BulkInsertError { index: Some(index), message: err.message })?;
+
+        inserted.push(created);
+    }
+
+    Ok(inserted)
+}
+
+//version of the feature when the collection holds it, the write transaction keeps it from moving on
+async fn current_version(transaction: &mut sqlx::Transaction<'_, sqlx::Sqlite>, collection_id: i64, feature_id: i64) -> Result<Option<i64>, sqlx::Error> {
+    sqlx::query("SELECT fa.version FROM feature fa \
+        INNER JOIN live_features_in_collection fi ON fi.feature_id = fa.id AND fi.collection_id = ? \
+        WHERE fa.id = ?")
+    .bind(collection_id)
+    .bind(feature_id)
+    .fetch_optional(&mut **transaction).await?
+    .map(|row| row.try_get(0))
+    .transpose()
+}
+
+//maps how many links the feature has, to the collection and in total, to the outcome of a detach
+//deletes the features of the JSON array of ids that are no longer in any collection, returns how many
+async fn delete_orphans(transaction: &mut sqlx::Transaction<'_, sqlx::Sqlite>, feature_ids: &str) -> Result<u64, sqlx::Error> {
+    let orphans = "SELECT value FROM json_each(?) \
+        WHERE NOT EXISTS (SELECT 1 FROM features_in_collection WHERE feature_id = value)";
+
+    sqlx::query(&format!("DELETE FROM feature_extent WHERE id IN ({orphans})"))
+    .bind(feature_ids)
+    .execute(&mut **transaction).await?;
+
+    let deleted = sqlx::query(&format!("DELETE FROM feature WHERE id IN ({orphans})"))
+    .bind(feature_ids)
+    .execute(&mut **transaction).await?
+    .rows_affected();
+
+    Ok(deleted)
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::executor::block_on;
+    use serde_json::{json, Value};
+    use sqlx::sqlite::SqlitePoolOptions;
+
+    use crate::model::{feature::Feature, feature_collection::FeatureCollection, bbox::Bbox, filter::{Filter, cql2_text}};
+    use crate::repository::features_repository::FeatureRepository;
+
+    use super::{SqliteFeatureRepository, create_schema};
+
+    //every connection to sqlite::memory: opens its own database, so the pool keeps a single one
+    fn repository() -> SqliteFeatureRepository {
+        let pool = block_on(SqlitePoolOptions::new().max_connections(1).connect("sqlite::memory:")).unwrap();
+        block_on(create_schema(&pool)).unwrap();
+
+        SqliteFeatureRepository::new(pool)
+    }
+
+    fn point(name: &str, x: f64, y: f64, population: i64) -> Value {
+        json!({"type": "Feature", "geometry": {"type": "Point", "coordinates": [x, y]},
+            "properties": {"name": name, "population": population}})
+    }
+
+    //a collection of Salvador, Recife and São Paulo, returns its id and the ids of the features
+    fn create_cities(repository: &mut SqliteFeatureRepository) -> (i64, Vec<i64>) {
+        let collection = FeatureCollection::from(&json!({"label": "cities", "features": [
+            point("Salvador", -38.5, -12.9, 2900000),
+            point("Recife", -34.9, -8.0, 1600000),
+            point("Sao Paulo", -46.6, -23.5, 12300000)
+        ]}));
+
+        let created = block_on(repository.create_collection_with_features(&collection)).unwrap();
+        let ids = created.features.iter().map(|feature| Option::<i64>::from(&feature.id).unwrap()).collect();
+
+        (Option::<i64>::from(&created.id).unwrap(), ids)
+    }
+
+    fn names(features: &[Feature]) -> Vec<String> {
+        features.iter().map(|feature| {
+            let value: Value = serde_json::from_str(&feature.to_geo_json()).unwrap();
+            value["properties"]["name"].as_str().unwrap().to_string()
+        }).collect()
+    }
+
+    #[test]
+    fn finds_features_in_a_bbox_through_the_rtree() {
+        let mut repository = repository();
+        let (id, _) = create_cities(&mut repository);
+
+        let northeast = Bbox::new(-40.0, -14.0, -34.0, -7.0);
+        let found = block_on(repository.get_features_in_collection_by_bbox(id, &northeast, 0, 10)).unwrap();
+        let count = block_on(repository.count_features_in_collection_by_bbox(id, &northeast, false)).unwrap();
+
+        assert_eq!(names(&found), ["Salvador", "Recife"]);
+        assert_eq!(count, 2);
+    }
+
+    #[test]
+    fn filters_features() {
+        let mut repository = repository();
+        let (id, _) = create_cities(&mut repository);
+
+        let filter = Filter::new(id, cql2_text::parse("name LIKE 'S%' AND NOT population > 5000000").unwrap());
+        let found = block_on(repository.get_features_in_collection_by_filter(id, &filter, 0, 10)).unwrap();
+
+        assert_eq!(names(&found), ["Salvador"]);
+    }
+
+    #[test]
+    fn pages_features_by_keyset() {
+        let mut repository = repository();
+        let (id, ids) = create_cities(&mut repository);
+
+        let first = block_on(repository.get_features_in_collection_after(id, 0, 2)).unwrap();
+        let second = block_on(repository.get_features_in_collection_after(id, ids[1], 2)).unwrap();
+
+        assert_eq!(names(&first), ["Salvador", "Recife"]);
+        assert_eq!(names(&second), ["Sao Paulo"]);
+    }
+
+    #[test]
+    fn records_the_history_and_hides_trashed_features() {
+        let mut repository = repository();
+        let (id, ids) = create_cities(&mut repository);
+
+        repository.set_editor(Some("ana".to_string()));
+        let feature = Feature::from(&json!({"id": ids[0], "geometry": {"type": "Point", "coordinates": [-38.5, -12.9]},
+            "properties": {"name": "Salvador da Bahia"}}));
+        block_on(repository.update_feature(id, &feature)).unwrap();
+        block_on(repository.delete_feature(id, ids[0], None)).unwrap();
+
+        let visible = block_on(repository.get_features_in_collection(id, 0, 10)).unwrap();
+        assert_eq!(names(&visible), ["Recife", "Sao Paulo"]);
+
+        block_on(repository.restore_feature(ids[0])).unwrap();
+
+        let restored = block_on(repository.get_features_in_collection(id, 0, 10)).unwrap();
+        assert_eq!(names(&restored), ["Salvador da Bahia", "Recife", "Sao Paulo"]);
+
+        let history = block_on(repository.get_feature_history(id, ids[0])).unwrap();
+        let operations: Vec<&str> = history.iter().map(|revision| revision.operation.as_str()).collect();
+
+        assert_eq!(operations, ["create", "attach", "update", "delete", "restore"]);
+        assert_eq!(history[0].changed_by, None);
+        assert_eq!(history[2].changed_by.as_deref(), Some("ana"));
+        assert_eq!(history[2].properties, Some(json!({"name": "Salvador da Bahia"})));
+    }
+}
@@ -0,0 +1,54 @@
+/*
+    Copyright (c)  Abraão Isvi <avraamisvi@users.noreply.github.com>
+
+    Permission is hereby granted, free of charge, to any
+    person obtaining a copy of this software and associated
+    documentation files (the "Software"), to deal in the
+    Software without restriction, including without
+    limitation the rights to use, copy, modify, merge,
+    publish, distribute, sublicense, and/or sell copies of
+    the Software, and to permit persons to whom the Software
+    is furnished to do so, subject to the following
+    conditions:
+
+    The above copyright notice and this permission notice
+    shall be included in all copies or substantial portions
+    of the Software.
+
+    THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF
+    ANY KIND, EXPRESS OR IMPLIED, INCLUDING BUT NOT LIMITED
+    TO THE WARRANTIES OF MERCHANTABILITY, FITNESS FOR A
+    PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT
+    SHALL THE AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY
+    CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER IN AN ACTION
+    OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR
+    IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+    DEALINGS IN THE SOFTWARE.
+ */
+
+use sqlx::{PgPool, SqlitePool};
+
+use super::features_repository::FeatureRepository;
+use super::memory_repository::{MemoryFeatureRepository, MemoryStore};
+use super::postgres_repository::PgFeatureRepository;
+use super::sqlite_repository::SqliteFeatureRepository;
+use super::geopackage_repository::GeoPackageFeatureRepository;
+
+/// The backend behind the routes, selected by `backend` in the `[storage]` section of `Config.toml`.
+pub enum Storage {
+    Postgres(PgPool),
+    Sqlite(SqlitePool),
+    GeoPackage(SqlitePool),
+    Memory(MemoryStore)
+}
+
+impl Storage {
+    pub fn repository(&self) -> Box<dyn FeatureRepository> {
+        match self {
+            Storage::Postgres(pool) => Box::new(PgFeatureRepository::new(pool.clone())),
+            Storage::Sqlite(pool) => Box::new(SqliteFeatureRepository::new(pool.clone())),
+            Storage::GeoPackage(pool) => Box::new(GeoPackageFeatureRepository::new(pool.clone())),
+            Storage::Memory(store) => Box::new(MemoryFeatureRepository::new(store.clone())),
+        }
+    }
+}
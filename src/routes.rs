@@ -26,33 +26,117 @@
     DEALINGS IN THE SOFTWARE.
  */
 
+use std::collections::HashMap;
 use std::io::Cursor;
 
 use rocket::{State};
-use sqlx::PgPool;
-use rocket::request::Request;
+use futures::StreamExt;
+use rocket::data::{ByteUnit, Data, Limits, ToByteUnit};
+use rocket::tokio::io::{AsyncBufReadExt, BufReader};
+use rocket::http::Status;
+use rocket::http::uri::{Origin, Segments, fmt::Path};
+use rocket::request::{self, FromRequest, FromSegments, Request};
 use rocket::response::{self, Response, Responder};
 
-use crate::{services::feature_service::FeatureService, repository::features_repository::FeatureRepository, model::{feature_collection::FeatureCollection, json::Json, feature::Feature, bbox::Bbox, filter::Filter}};
+use serde_json::Value;
+
+use crate::config::TrashPolicy;
+use crate::{services::feature_service::{FeatureService, FeatureServiceError}, repository::{storage::Storage, features_repository::{BulkInsertError, FeatureStream}, geopackage_repository::{self, GeoPackageFeatureRepository}}, model::{feature_collection::FeatureCollection, json::Json, feature::Feature, bbox::Bbox, filter::{Filter, FilterError, cql2_text, cql2_json}, conformance::Conformance, collection_search::CollectionSearch, cursor::Cursor as PageCursor, paging::Link, instant::Instant}};
 
 // #[derive(Responder)]
 pub enum CollectionResponse {
     // #[response(status = 200, content_type = "json")]
     Ok(String),
+    /// Ok with the `Link` header to the next keyset page, when there may be one.
+    Page(String, Option<String>),
+    /// Ok with the `ETag` of the feature version, when the backend keeps one.
+    Versioned(String, Option<i64>),
     // #[response(status = 201, content_type = "json")]
     Created(String),
-    // #[response(status = 500, content_type = "json")]
+    // #[response(status = 204)]
+    NoContent,
+    // #[response(status = 400, content_type = "json")]
+    BadRequest(String),
+    /// OGC exception for a feature, collection or filter that does not exist.
+    NotFound(String),
+    /// The `If-Match` version of a write is no longer the current one.
+    PreconditionFailed(String),
+    /// A write that would break a rule of the data, like detaching a feature from its last collection.
+    Conflict(String),
+    /// A write that must name the version it is based on has no `If-Match`.
+    PreconditionRequired(String),
+    /// An admin request without the admin token.
+    Forbidden(String),
+    /// OGC exception for what the configured backend can not do.
+    NotImplemented(String),
+    GeoPackage(Vec<u8>),
+    /// OGC exception for a failure of the backend.
     SystemError(String)
 }
 
 struct CorsResponse;
 
+/// Who makes a change, taken from the standard `From` header and recorded in the feature history.
+pub struct Editor(Option<String>);
+
+/// The `If-Match` header of a write, the `ETag` of the feature version the change is based on.
+pub struct IfMatch(Option<String>);
+
+/// The bearer token of the `Authorization` header, compared with `admin_token` of the `[trash]` section.
+pub struct AdminToken(Option<String>);
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for AdminToken {
+    type Error = ();
+
+    async fn from_request(request: &'r Request<'_>) -> request::Outcome<Self, Self::Error> {
+        let token = request.headers().get_one("Authorization").and_then(|value| value.strip_prefix("Bearer "));
+        request::Outcome::Success(AdminToken(token.map(|token| token.trim().to_string())))
+    }
+}
+
+/// The `<min_lng>/<min_lat>/<max_lng>/<max_lat>` segments of the bbox items.
+pub struct BboxSegments(Bbox);
+
+impl<'r> FromSegments<'r> for BboxSegments {
+    type Error = String;
+
+    fn from_segments(segments: Segments<'r, Path>) -> Result<Self, Self::Error> {
+        let values: Vec<f64> = segments.map(str::parse).collect::<Result<_, _>>()
+            .map_err(|err: std::num::ParseFloatError| err.to_string())?;
+
+        match values[..] {
+            [min_lng, min_lat, max_lng, max_lat] => Ok(BboxSegments(Bbox::new(min_lng, min_lat, max_lng, max_lat))),
+            _ => Err("a bbox has 4 coordinates".to_string())
+        }
+    }
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for IfMatch {
+    type Error = ();
+
+    async fn from_request(request: &'r Request<'_>) -> request::Outcome<Self, Self::Error> {
+        request::Outcome::Success(IfMatch(request.headers().get_one("If-Match").map(|tag| tag.trim().to_string())))
+    }
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for Editor {
+    type Error = ();
+
+    async fn from_request(request: &'r Request<'_>) -> request::Outcome<Self, Self::Error> {
+        let editor = request.headers().get_one("From").map(str::trim).filter(|from| !from.is_empty());
+        request::Outcome::Success(Editor(editor.map(str::to_string)))
+    }
+}
+
 impl<'r> Responder<'r, 'static> for CorsResponse {
     fn respond_to(self, req: &'r Request<'_>) -> response::Result<'static> {
         let mut resp = Response::new();
 
         resp.set_raw_header("Access-Control-Allow-Origin", "*");
-        resp.set_raw_header("Access-Control-Allow-Methods", "POST, GET, PATCH, OPTIONS");
+        resp.set_raw_header("Access-Control-Allow-Methods", "POST, GET, PATCH, DELETE, OPTIONS");
         resp.set_raw_header("Access-Control-Allow-Headers", "*");
         resp.set_raw_header("Access-Control-Allow-Credentials", "true");
         
@@ -66,12 +150,61 @@ impl<'r> Responder<'r, 'static> for CollectionResponse {
 
         match self {//TODO improve this code, too much repetition
             CollectionResponse::Ok(data) => resp.set_sized_body(data.len(), Cursor::new(data)),
+            CollectionResponse::Page(data, link) => {
+                if let Some(link) = link {
+                    resp.set_raw_header("Link", link);
+                }
+                resp.set_sized_body(data.len(), Cursor::new(data))
+            },
+            CollectionResponse::Versioned(data, version) => {
+                if let Some(version) = version {
+                    resp.set_raw_header("ETag", etag(version));
+                }
+                resp.set_sized_body(data.len(), Cursor::new(data))
+            },
             CollectionResponse::Created(data) => resp.set_sized_body(data.len(), Cursor::new(data)),
-            CollectionResponse::SystemError(data) => resp.set_sized_body(data.len(), Cursor::new(data)),
+            CollectionResponse::NoContent => resp.set_status(Status::NoContent),
+            CollectionResponse::BadRequest(data) => {
+                resp.set_status(Status::BadRequest);
+                resp.set_sized_body(data.len(), Cursor::new(data))
+            },
+            CollectionResponse::NotFound(data) => {
+                resp.set_status(Status::NotFound);
+                resp.set_sized_body(data.len(), Cursor::new(data))
+            },
+            CollectionResponse::PreconditionFailed(data) => {
+                resp.set_status(Status::PreconditionFailed);
+                resp.set_sized_body(data.len(), Cursor::new(data))
+            },
+            CollectionResponse::Conflict(data) => {
+                resp.set_status(Status::Conflict);
+                resp.set_sized_body(data.len(), Cursor::new(data))
+            },
+            CollectionResponse::PreconditionRequired(data) => {
+                resp.set_status(Status::PreconditionRequired);
+                resp.set_sized_body(data.len(), Cursor::new(data))
+            },
+            CollectionResponse::Forbidden(data) => {
+                resp.set_status(Status::Forbidden);
+                resp.set_sized_body(data.len(), Cursor::new(data))
+            },
+            CollectionResponse::GeoPackage(data) => {
+                resp.set_raw_header("Content-Type", "application/geopackage+sqlite3");
+                resp.set_raw_header("Content-Disposition", "attachment; filename=\"collection.gpkg\"");
+                resp.set_sized_body(data.len(), Cursor::new(data))
+            },
+            CollectionResponse::NotImplemented(data) => {
+                resp.set_status(Status::NotImplemented);
+                resp.set_sized_body(data.len(), Cursor::new(data))
+            },
+            CollectionResponse::SystemError(data) => {
+                resp.set_status(Status::InternalServerError);
+                resp.set_sized_body(data.len(), Cursor::new(data))
+            },
         }
 
         resp.set_raw_header("Access-Control-Allow-Origin", "*");
-        resp.set_raw_header("Access-Control-Allow-Methods", "POST, GET, PATCH, OPTIONS");
+        resp.set_raw_header("Access-Control-Allow-Methods", "POST, GET, PATCH, DELETE, OPTIONS");
         resp.set_raw_header("Access-Control-Allow-Headers", "*");
         resp.set_raw_header("Access-Control-Allow-Credentials", "true");
         
@@ -79,6 +212,12 @@ impl<'r> Responder<'r, 'static> for CollectionResponse {
     }
 }
 
+/// OGC exception instead of the default page for paths that match no route.
+#[catch(404)]
+pub fn not_found(request: &Request<'_>) -> CollectionResponse {
+    CollectionResponse::NotFound(exception("NotFound", &format!("{} not found", request.uri())))
+}
+
 //TODO find a way to intercept and enable CORS for all endpoints
 #[options("/collections/<collection_id>/item")]
 pub fn options_collections(collection_id: i64) -> CorsResponse {
@@ -86,9 +225,9 @@ pub fn options_collections(collection_id: i64) -> CorsResponse {
 }
 
 #[put("/collections", data = "<body>", format = "json")]
-pub fn put_collections(body: String, pg_pool: &State<PgPool>) -> CollectionResponse {
+pub fn put_collections(body: String, storage: &State<Storage>) -> CollectionResponse {
 
-    let mut feature_service = create_features_service(pg_pool);
+    let mut feature_service = create_features_service(storage);
     
     let feature_collection = FeatureCollection::from(Json::new(body));
 
@@ -98,16 +237,25 @@ pub fn put_collections(body: String, pg_pool: &State<PgPool>) -> CollectionRespo
     
     match result {
         Ok(collection) => CollectionResponse::Created(collection.to_geo_json()),
-        Err(err) => CollectionResponse::SystemError(err.message)
+        Err(err) => service_exception(err)
     }
 }
 
-#[post("/collections", data = "<body>", format = "json")]
-pub fn post_collections(body: String, pg_pool: &State<PgPool>) -> CollectionResponse {
+#[post("/collections", data = "<data>", format = "json")]
+pub async fn post_collections(data: Data<'_>, limits: &Limits, storage: &State<Storage>, editor: Editor) -> CollectionResponse {
 
-    let mut feature_service = create_features_service(pg_pool);
-    
-    let feature_collection = FeatureCollection::from(Json::new(body));
+    let mut feature_service = create_editing_service(storage, editor);
+
+    let value = match read_bulk(data, limits).await {
+        Ok(value) => value,
+        Err(response) => return response
+    };
+
+    if let Err(response) = check_features(&value) {
+        return response;
+    }
+
+    let feature_collection = FeatureCollection::from(&value);
 
     let result = futures::executor::block_on(async {
         feature_service.create_collection(&feature_collection).await
@@ -115,14 +263,60 @@ pub fn post_collections(body: String, pg_pool: &State<PgPool>) -> CollectionResp
     
     match result {
         Ok(collection) => CollectionResponse::Created(collection.to_geo_json()),
-        Err(err) => CollectionResponse::SystemError(err.message)
+        Err(err) => bulk_exception(err)
+    }
+}
+
+#[post("/collections/<collection_id>/items", data = "<data>", format = "json")]
+pub async fn post_features(storage: &State<Storage>, collection_id: i64, data: Data<'_>, limits: &Limits, editor: Editor) -> CollectionResponse {
+
+    let mut feature_service = create_editing_service(storage, editor);
+
+    let value = match read_bulk(data, limits).await {
+        Ok(value) => value,
+        Err(response) => return response
+    };
+
+    if !value["features"].is_array() {
+        return CollectionResponse::BadRequest(exception("InvalidParameterValue", "The body must be a FeatureCollection with a features array"));
+    }
+
+    if let Err(response) = check_features(&value) {
+        return response;
+    }
+
+    let features: Vec<Feature> = value["features"].as_array().into_iter().flatten().map(Feature::from).collect();
+
+    let result = futures::executor::block_on(async {
+        feature_service.create_features(collection_id, &features).await
+    });
+
+    match result {
+        Ok(collection) => CollectionResponse::Created(collection.to_geo_json()),
+        Err(err) => bulk_exception(err)
+    }
+}
+
+#[post("/collections/<collection_id>/import", data = "<data>")]
+pub async fn import_features(storage: &State<Storage>, collection_id: i64, data: Data<'_>, limits: &Limits, editor: Editor) -> CollectionResponse {
+
+    let mut feature_service = create_editing_service(storage, editor);
+
+    let limit = limits.get("import").unwrap_or(4.gibibytes());
+
+    //awaited rather than blocked on, the body is read while the features are written
+    let result = feature_service.import_features(collection_id, read_features(data, limit)).await;
+
+    match result {
+        Ok(imported) => CollectionResponse::Created(serde_json::json!({"id": collection_id, "numberImported": imported}).to_string()),
+        Err(err) => bulk_exception(err)
     }
 }
 
 #[post("/collections/<collection_id>/item", data = "<body>", format = "json")]
-pub fn post_feature(pg_pool: &State<PgPool>, collection_id: i64, body: String) -> CollectionResponse {
+pub fn post_feature(storage: &State<Storage>, collection_id: i64, body: String, editor: Editor) -> CollectionResponse {
 
-    let mut feature_service = create_features_service(pg_pool);
+    let mut feature_service = create_editing_service(storage, editor);
     
     let feature = Feature::from(Json::new(body));
 
@@ -132,113 +326,1102 @@ pub fn post_feature(pg_pool: &State<PgPool>, collection_id: i64, body: String) -
     
     match result {
         Ok(collection) => CollectionResponse::Created(collection.to_geo_json()),
-        Err(err) => CollectionResponse::SystemError(err.message)
+        Err(err) => service_exception(err)
     }
 }
 
 #[put("/collections/<collection_id>/item", data = "<body>", format = "json")]
-pub fn put_feature(pg_pool: &State<PgPool>, collection_id: i64, body: String) -> CollectionResponse {
+pub fn put_feature(storage: &State<Storage>, collection_id: i64, body: String, editor: Editor, if_match: IfMatch) -> CollectionResponse {
 
-    let mut feature_service = create_features_service(pg_pool);
+    let mut feature_service = create_editing_service(storage, editor);
     
-    let feature = Feature::from(Json::new(body));
+    let mut feature = Feature::from(Json::new(body));
+    feature.version = match expected_version(&if_match) {
+        Ok(version) => version,
+        Err(response) => return response
+    };
 
     let result = futures::executor::block_on(async {
         feature_service.update_feature(collection_id, &feature).await
     });
     
     match result {
-        Ok(collection) => CollectionResponse::Created(collection.to_geo_json()),
-        Err(err) => CollectionResponse::SystemError(err.message)
+        Ok(collection) => {
+            let version = collection.features.first().and_then(|feature| feature.version);
+            CollectionResponse::Versioned(collection.to_geo_json(), version)
+        },
+        Err(err) => service_exception(err)
     }
 }
 
-#[get("/collections?<page>&<size>")]
-pub fn get_collections(pg_pool: &State<PgPool>, size: i64, page: i64) -> CollectionResponse {
+/// Catalogue search, `q` matches the label, `filter` the collection properties
+/// and `bbox` (min_lng,min_lat,max_lng,max_lat) the extent of the features.
+#[derive(FromForm)]
+pub struct CollectionQuery {
+    q: Option<String>,
+    filter: Option<String>,
+    #[field(name = "filter-lang")]
+    filter_lang: Option<String>,
+    bbox: Option<String>
+}
 
-    let mut feature_service = create_features_service(pg_pool);
-    
+/// Without `page` the collections are listed in keyset pages, the `Link` header carries
+/// the `cursor` of the next one.
+#[get("/collections?<page>&<size>&<cursor>&<collection_query..>")]
+pub fn get_collections(storage: &State<Storage>, size: i64, page: Option<i64>, cursor: Option<&str>, collection_query: CollectionQuery) -> CollectionResponse {
+
+    let mut feature_service = create_features_service(storage);
+
+    let search = match parse_collection_query(collection_query) {
+        Ok(search) => search,
+        Err(response) => return response
+    };
+
+    if search.is_empty() && page.is_none() {
+        let cursor = match parse_cursor(cursor) {
+            Ok(cursor) => cursor,
+            Err(response) => return response
+        };
+
+        let result = futures::executor::block_on(async {
+            feature_service.get_collections_after(&cursor, size).await
+        });
+
+        return match result {
+            Ok(collections) => {
+                let link = collections.next(size)
+                    .map(|next| format!(r#"</collections?size={size}&cursor={}>; rel="next""#, next.to_token()));
+                CollectionResponse::Page(collections.to_json(), link)
+            },
+            Err(err) => service_exception(err)
+        };
+    }
+
+    if cursor.is_some() {
+        return CollectionResponse::BadRequest(exception("InvalidParameterValue", "cursor can not be combined with page or a search"));
+    }
+
+    let page = page.unwrap_or(0);
     let result = futures::executor::block_on(async{
-        feature_service.get_collections(page, size).await
+        if search.is_empty() {
+            feature_service.get_collections(page, size).await
+        } else {
+            feature_service.search_collections(&search, page, size).await
+        }
     });
     
     match result {
         Ok(collection) => CollectionResponse::Ok(collection.to_json()),
-        Err(err) => CollectionResponse::SystemError(err.message)
+        Err(err) => service_exception(err)
     }
 }
 
-#[get("/collections/<id>/items?<page>&<size>")]
-pub fn get_collections_features(pg_pool: &State<PgPool>, id: i64, size: i64, page: i64) -> CollectionResponse {
+/// `filter-name` runs a stored filter, its placeholders are bound from `params.<name>=<value>`,
+/// `q` ranks the features by full-text search over their string properties,
+/// `count=estimated` allows an approximate `numberMatched`.
+#[derive(FromForm)]
+pub struct FilterQuery {
+    q: Option<String>,
+    filter: Option<String>,
+    #[field(name = "filter-lang")]
+    filter_lang: Option<String>,
+    #[field(name = "filter-name")]
+    filter_name: Option<String>,
+    params: HashMap<String, String>,
+    count: Option<String>,
+    /// RFC 3339 instant to read the collection as it was then.
+    #[field(name = "asOf")]
+    as_of: Option<String>
+}
+
+#[get("/conformance")]
+pub fn get_conformance() -> CollectionResponse {
+    CollectionResponse::Ok(Conformance.to_json())
+}
+
+/// Without `page`, `filter` or `q` the features are listed in keyset pages, `next` in the
+/// response is the `cursor` of the following one. `asOf` lists the features of a past instant by page.
+#[get("/collections/<id>/items?<page>&<size>&<cursor>&<filter_query..>")]
+pub fn get_collections_features(storage: &State<Storage>, origin: &Origin<'_>, id: i64, size: i64, page: Option<i64>,
+    cursor: Option<&str>, filter_query: FilterQuery) -> CollectionResponse {
     
-    let mut feature_service = create_features_service(pg_pool);
+    let mut feature_service = create_features_service(storage);
+    let count = filter_query.count.as_deref();
+
+    if let Some(as_of) = &filter_query.as_of {
+        if cursor.is_some() || filter_query.q.is_some() || filter_query.filter.is_some() || filter_query.filter_name.is_some() {
+            return CollectionResponse::BadRequest(exception("InvalidParameterValue", "asOf can not be combined with cursor, filter or q"));
+        }
+
+        let as_of = match Instant::try_from(as_of.as_str()) {
+            Ok(as_of) => as_of,
+            Err(err) => return CollectionResponse::BadRequest(exception("InvalidParameterValue", &err))
+        };
+
+        let result = futures::executor::block_on(async {
+            feature_service.get_features_in_collection_as_of(id, &as_of, page.unwrap_or(0), size).await
+        });
+
+        return match result {
+            Ok(collection) => CollectionResponse::Ok(collection.to_geo_json()),
+            Err(err) => service_exception(err)
+        };
+    }
+
+    let listing = filter_query.q.is_none() && filter_query.filter.is_none() && filter_query.filter_name.is_none();
+    if listing && page.is_none() {
+        let cursor = match parse_cursor(cursor) {
+            Ok(cursor) => cursor,
+            Err(response) => return response
+        };
+
+        let result = futures::executor::block_on(async {
+            feature_service.get_features_in_collection_after(id, &cursor, size, is_estimated(count)).await
+        });
+
+        return match result {
+            Ok(mut collection) => {
+                add_links(&mut collection, origin, id, None, size, is_estimated(count));
+                CollectionResponse::Ok(collection.to_geo_json())
+            },
+            Err(err) => service_exception(err)
+        };
+    }
+
+    if cursor.is_some() {
+        return CollectionResponse::BadRequest(exception("InvalidParameterValue", "cursor can not be combined with page, filter or q"));
+    }
+
+    let page = page.unwrap_or(0);
+
+    let filter = match (&filter_query.filter_name, &filter_query.filter) {
+        (Some(_), Some(_)) => return CollectionResponse::BadRequest(exception("InvalidParameterValue", "Use either filter or filter-name")),
+        (Some(name), None) => {
+            let stored = futures::executor::block_on(async {
+                feature_service.get_filter(id, name).await
+            });
+
+            match stored {
+                Ok(stored) => Some(stored.filter),
+                Err(err) => return service_exception(err)
+            }
+        },
+        (None, _) => match parse_filter_query(id, &filter_query) {
+            Ok(filter) => filter,
+            Err(response) => return response
+        }
+    };
+
+    let filter = match filter.map(|mut filter| filter.bind(&filter_query.params).map(|_| filter)) {
+        Some(Err(err)) => return filter_exception(&err),
+        Some(Ok(filter)) => Some(filter),
+        None => None
+    };
     
+    if let Some(text) = &filter_query.q {
+        let result = futures::executor::block_on(async {
+            feature_service.search_features_in_collection(id, text, filter.as_ref(), page, size).await
+        });
+
+        return match result {
+            Ok(search_result) => CollectionResponse::Ok(search_result.to_geo_json()),
+            Err(err) => service_exception(err)
+        };
+    }
+
     let result = futures::executor::block_on(async{
-        feature_service.get_features_in_collection(id, page, size).await
+        match &filter {
+            Some(filter) => feature_service.filter_features_in_collection(id, filter, page, size, is_estimated(count)).await,
+            None => feature_service.get_features_in_collection(id, page, size, is_estimated(count)).await
+        }
     });
 
     match result {
-        Ok(collection) => CollectionResponse::Ok(collection.to_geo_json()),
-        Err(err) => CollectionResponse::SystemError(err.message)
+        Ok(mut collection) => {
+            add_links(&mut collection, origin, id, Some(page), size, is_estimated(count));
+            CollectionResponse::Ok(collection.to_geo_json())
+        },
+        Err(err) => service_exception(err)
     }
 }
 
-#[get("/collections/<id>/items/<min_lng>/<min_lat>/<max_lng>/<max_lat>?<page>&<size>")]
-pub fn get_features_by_bbox(pg_pool: &State<PgPool>,
-    id: i64, 
-    min_lng: f64, 
-    min_lat: f64,
-    max_lng: f64,
-    max_lat: f64,
-    size: i64, 
-    page: i64) -> CollectionResponse {
+/// Paging of the bbox items, keyset pages by `cursor` unless `page` is given.
+#[derive(FromForm)]
+pub struct PageQuery {
+    size: i64,
+    page: Option<i64>,
+    cursor: Option<String>,
+    count: Option<String>
+}
+
+/// Keyset pages like the items listing when `page` is left out. Ranked after the feature
+/// routes, which the trailing segments would match too.
+#[get("/collections/<id>/items/<bbox..>?<page_query..>", rank = 2)]
+pub fn get_features_by_bbox(storage: &State<Storage>, origin: &Origin<'_>, id: i64, bbox: BboxSegments, page_query: PageQuery) -> CollectionResponse {
+
+    let PageQuery { size, page, cursor, count } = page_query;
+    let count = count.as_deref();
+
+    let BboxSegments(bbox) = bbox;
+    let mut feature_service = create_features_service(storage);
 
-    let bbox = Bbox::new(min_lng, min_lat, max_lng, max_lat);
-    let mut feature_service = create_features_service(pg_pool);
+    let cursor = match (page, cursor) {
+        (Some(_), Some(_)) => return CollectionResponse::BadRequest(exception("InvalidParameterValue", "Use either page or cursor")),
+        (Some(_), None) => None,
+        (None, cursor) => match parse_cursor(cursor.as_deref()) {
+            Ok(cursor) => Some(cursor),
+            Err(response) => return response
+        }
+    };
     
     let result = futures::executor::block_on(async{
-        feature_service.get_features_in_collection_by_bbox(id, &bbox, page, size).await
+        match &cursor {
+            Some(cursor) => feature_service.get_features_in_collection_by_bbox_after(id, &bbox, cursor, size, is_estimated(count)).await,
+            None => feature_service.get_features_in_collection_by_bbox(id, &bbox, page.unwrap_or(0), size, is_estimated(count)).await
+        }
+    });
+
+    match result {
+        Ok(mut collection) => {
+            add_links(&mut collection, origin, id, page, size, is_estimated(count));
+            CollectionResponse::Ok(collection.to_geo_json())
+        },
+        Err(err) => service_exception(err)
+    }
+}
+
+#[get("/collections/<id>")]
+pub fn get_collection(storage: &State<Storage>, id: i64) -> CollectionResponse {
+
+    let mut feature_service = create_features_service(storage);
+
+    let result = futures::executor::block_on(async {
+        feature_service.get_collection(id).await
     });
 
     match result {
         Ok(collection) => CollectionResponse::Ok(collection.to_geo_json()),
-        Err(err) => CollectionResponse::SystemError(err.message)
+        Err(err) => service_exception(err)
     }
 }
 
 #[get("/collections/<collection_id>/items/<feature_id>")]
-pub fn get_collections_feature(pg_pool: &State<PgPool>, collection_id: i64, feature_id: i64) -> CollectionResponse {
+pub fn get_collections_feature(storage: &State<Storage>, collection_id: i64, feature_id: i64) -> CollectionResponse {
 
-    let mut feature_service = create_features_service(pg_pool);
+    let mut feature_service = create_features_service(storage);
     
     let result = futures::executor::block_on(async{
         feature_service.get_features_in_collection_by_id(collection_id, feature_id).await
     });
 
+    match result {
+        Ok(collection) => {
+            let version = collection.features.first().and_then(|feature| feature.version);
+            CollectionResponse::Versioned(collection.to_geo_json(), version)
+        },
+        Err(err) => service_exception(err)
+    }
+}
+
+/// Every revision of the feature, the editor is the `From` header of the request that made it.
+#[get("/collections/<collection_id>/items/<feature_id>/history")]
+pub fn get_feature_history(storage: &State<Storage>, collection_id: i64, feature_id: i64) -> CollectionResponse {
+
+    let mut feature_service = create_features_service(storage);
+
+    let result = futures::executor::block_on(async {
+        feature_service.get_feature_history(collection_id, feature_id).await
+    });
+
+    match result {
+        Ok(history) => CollectionResponse::Ok(history.to_json()),
+        Err(err) => service_exception(err)
+    }
+}
+
+/// Removes the feature from the collection, it only goes to the trash when no other collection holds it.
+/// Like updates it requires the `If-Match` of the current feature version.
+#[delete("/collections/<collection_id>/items/<feature_id>")]
+pub fn delete_feature(storage: &State<Storage>, collection_id: i64, feature_id: i64, editor: Editor, if_match: IfMatch) -> CollectionResponse {
+
+    let mut feature_service = create_editing_service(storage, editor);
+
+    let version = match expected_version(&if_match) {
+        Ok(version) => version,
+        Err(response) => return response
+    };
+
+    let result = futures::executor::block_on(async {
+        feature_service.delete_feature(collection_id, feature_id, version).await
+    });
+
+    match result {
+        Ok(()) => CollectionResponse::NoContent,
+        Err(err) => service_exception(err)
+    }
+}
+
+/// The collections a feature belongs to.
+#[get("/features/<feature_id>/collections")]
+pub fn get_feature_collections(storage: &State<Storage>, feature_id: i64) -> CollectionResponse {
+
+    let mut feature_service = create_features_service(storage);
+
+    let result = futures::executor::block_on(async {
+        feature_service.get_collections_of_feature(feature_id).await
+    });
+
+    match result {
+        Ok(collections) => CollectionResponse::Ok(collections.to_json()),
+        Err(err) => service_exception(err)
+    }
+}
+
+/// Adds an existing feature to another collection.
+#[put("/features/<feature_id>/collections/<collection_id>")]
+pub fn attach_feature(storage: &State<Storage>, feature_id: i64, collection_id: i64, editor: Editor) -> CollectionResponse {
+
+    let mut feature_service = create_editing_service(storage, editor);
+
+    let result = futures::executor::block_on(async {
+        feature_service.attach_feature(collection_id, feature_id).await
+    });
+
+    match result {
+        Ok(collections) => CollectionResponse::Ok(collections.to_json()),
+        Err(err) => service_exception(err)
+    }
+}
+
+/// Removes a feature from one of its collections, unlike deleting it the feature must stay in another one.
+#[delete("/features/<feature_id>/collections/<collection_id>")]
+pub fn detach_feature(storage: &State<Storage>, feature_id: i64, collection_id: i64, editor: Editor) -> CollectionResponse {
+
+    let mut feature_service = create_editing_service(storage, editor);
+
+    let result = futures::executor::block_on(async {
+        feature_service.detach_feature(collection_id, feature_id).await
+    });
+
+    match result {
+        Ok(()) => CollectionResponse::NoContent,
+        Err(err) => service_exception(err)
+    }
+}
+
+/// Moves the collection to the trash, with it go its stored filters and the features only it holds.
+#[delete("/collections/<id>")]
+pub fn delete_collection(storage: &State<Storage>, id: i64, editor: Editor) -> CollectionResponse {
+
+    let mut feature_service = create_editing_service(storage, editor);
+
+    let result = futures::executor::block_on(async {
+        feature_service.delete_collection(id).await
+    });
+
+    match result {
+        Ok(()) => CollectionResponse::NoContent,
+        Err(err) => service_exception(err)
+    }
+}
+
+/// The deleted features and collections that can still be restored, most recently deleted first.
+#[get("/trash?<page>&<size>")]
+pub fn get_trash(storage: &State<Storage>, page: Option<i64>, size: i64) -> CollectionResponse {
+
+    let mut feature_service = create_features_service(storage);
+
+    let result = futures::executor::block_on(async {
+        feature_service.get_trash(page.unwrap_or(0), size).await
+    });
+
+    match result {
+        Ok(trash) => CollectionResponse::Ok(trash.to_json()),
+        Err(err) => service_exception(err)
+    }
+}
+
+/// Takes the feature out of the trash, back in the collection it was deleted from.
+#[post("/trash/features/<feature_id>/restore")]
+pub fn restore_feature(storage: &State<Storage>, feature_id: i64, editor: Editor) -> CollectionResponse {
+
+    let mut feature_service = create_editing_service(storage, editor);
+
+    let result = futures::executor::block_on(async {
+        feature_service.restore_feature(feature_id).await
+    });
+
+    match result {
+        Ok(()) => CollectionResponse::NoContent,
+        Err(err) => service_exception(err)
+    }
+}
+
+/// Takes the collection out of the trash with its features and stored filters.
+#[post("/trash/collections/<id>/restore")]
+pub fn restore_collection(storage: &State<Storage>, id: i64, editor: Editor) -> CollectionResponse {
+
+    let mut feature_service = create_editing_service(storage, editor);
+
+    let result = futures::executor::block_on(async {
+        feature_service.restore_collection(id).await
+    });
+
     match result {
         Ok(collection) => CollectionResponse::Ok(collection.to_geo_json()),
-        Err(err) => CollectionResponse::SystemError(err.message)
+        Err(err) => service_exception(err)
+    }
+}
+
+/// Deletes for good what has been in the trash longer than the retention period, admins only.
+#[post("/trash/purge")]
+pub fn purge_trash(storage: &State<Storage>, policy: &State<TrashPolicy>, token: AdminToken, editor: Editor) -> CollectionResponse {
+
+    match (&policy.admin_token, &token.0) {
+        (None, _) => return CollectionResponse::Forbidden(exception("Forbidden",
+            "Purging is disabled, set admin_token in the [trash] section of Config.toml")),
+        (Some(admin_token), Some(token)) if admin_token == token => (),
+        _ => return CollectionResponse::Forbidden(exception("Forbidden", "Purging requires the admin token"))
+    }
+
+    let mut feature_service = create_editing_service(storage, editor);
+
+    let result = futures::executor::block_on(async {
+        feature_service.purge_trash(policy.retention_days).await
+    });
+
+    match result {
+        Ok(purged) => CollectionResponse::Ok(purged.to_json()),
+        Err(err) => service_exception(err)
     }
 }
 
-#[post("/collections/<id>/filter/items", data = "<body>", format = "json")]
-pub fn filter_feature(pg_pool: &State<PgPool>, id: i64, body: String) -> CollectionResponse {
+#[post("/collections/<id>/filter/items?<page>&<size>&<count>", data = "<body>", format = "json")]
+pub fn filter_feature(storage: &State<Storage>, origin: &Origin<'_>, id: i64, size: i64, page: i64, count: Option<&str>, body: String) -> CollectionResponse {
 
-    let mut feature_service = create_features_service(pg_pool);
+    let mut feature_service = create_features_service(storage);
     
-    let filter = Filter::from(Json::new(body));
+    let mut filter = match Filter::try_from(Json::new(body)) {
+        Ok(filter) => filter,
+        Err(err) => return filter_exception(&err)
+    };
+
+    if let Err(err) = filter.bind(&HashMap::new()) {
+        return filter_exception(&err);
+    }
 
     let result = futures::executor::block_on(async {
-        feature_service.filter_features_in_collection(id, &filter).await
+        feature_service.filter_features_in_collection(id, &filter, page, size, is_estimated(count)).await
     });
     
     match result {
-        Ok(collection) => CollectionResponse::Created(collection.to_geo_json()),
-        Err(err) => CollectionResponse::SystemError(err.message)
+        Ok(mut collection) => {
+            add_links(&mut collection, origin, id, Some(page), size, is_estimated(count));
+            CollectionResponse::Ok(collection.to_geo_json())
+        },
+        Err(err) => service_exception(err)
+    }
+}
+
+#[put("/collections/<id>/filters/<name>", data = "<body>", format = "json")]
+pub fn put_filter(storage: &State<Storage>, id: i64, name: &str, body: String) -> CollectionResponse {
+
+    let mut feature_service = create_features_service(storage);
+
+    let mut value: serde_json::Value = match serde_json::from_str(&body) {
+        Ok(value) => value,
+        Err(err) => return filter_exception(&FilterError::new("".to_string(), format!("the filter is not valid JSON: {err}")))
+    };
+
+    //the collection is the one in the path
+    match value.as_object_mut() {
+        Some(object) => object.insert("collection_id".to_string(), serde_json::json!(id)),
+        None => return filter_exception(&FilterError::new("".to_string(), "the filter must be a JSON object".to_string()))
+    };
+
+    let filter = match Filter::try_from(&value) {
+        Ok(filter) => filter,
+        Err(err) => return filter_exception(&err)
+    };
+
+    let result = futures::executor::block_on(async {
+        feature_service.save_filter(id, name, &filter).await
+    });
+
+    match result {
+        Ok(stored) => CollectionResponse::Created(stored.to_json()),
+        Err(err) => service_exception(err)
+    }
+}
+
+#[get("/collections/<id>/filters")]
+pub fn get_filters(storage: &State<Storage>, id: i64) -> CollectionResponse {
+
+    let mut feature_service = create_features_service(storage);
+
+    let result = futures::executor::block_on(async {
+        feature_service.get_filters(id).await
+    });
+
+    match result {
+        Ok(filters) => CollectionResponse::Ok(filters.to_json()),
+        Err(err) => service_exception(err)
+    }
+}
+
+#[get("/collections/<id>/filters/<name>")]
+pub fn get_filter(storage: &State<Storage>, id: i64, name: &str) -> CollectionResponse {
+
+    let mut feature_service = create_features_service(storage);
+
+    let result = futures::executor::block_on(async {
+        feature_service.get_filter(id, name).await
+    });
+
+    match result {
+        Ok(stored) => CollectionResponse::Ok(stored.to_json()),
+        Err(err) => service_exception(err)
+    }
+}
+
+#[delete("/collections/<id>/filters/<name>")]
+pub fn delete_filter(storage: &State<Storage>, id: i64, name: &str) -> CollectionResponse {
+
+    let mut feature_service = create_features_service(storage);
+
+    let result = futures::executor::block_on(async {
+        feature_service.delete_filter(id, name).await
+    });
+
+    match result {
+        Ok(()) => CollectionResponse::NoContent,
+        Err(err) => service_exception(err)
+    }
+}
+
+/// Exports the collection and its features as a GeoPackage file.
+#[get("/collections/<id>/geopackage")]
+pub fn get_collection_geopackage(storage: &State<Storage>, id: i64) -> CollectionResponse {
+
+    let mut feature_service = create_features_service(storage);
+    let path = std::env::temp_dir().join(format!("geoxidated-{}-{id}-{}.gpkg", std::process::id(),
+        std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap_or_default().as_nanos()));
+
+    let result = futures::executor::block_on(async {
+        let pool = geopackage_repository::open_geopackage(&path.to_string_lossy()).await
+            .map_err(|err| CollectionResponse::SystemError(exception("ServerError", &err.message)))?;
+        let mut target = GeoPackageFeatureRepository::new(pool.clone());
+
+        let exported = feature_service.export_collection(id, &mut target).await;
+        pool.close().await;
+
+        exported.map_err(service_exception)?;
+        std::fs::read(&path).map_err(|err| CollectionResponse::SystemError(exception("ServerError", &err.to_string())))
+    });
+
+    let _ = std::fs::remove_file(&path);
+
+    match result {
+        Ok(data) => CollectionResponse::GeoPackage(data),
+        Err(response) => response
+    }
+}
+
+fn parse_collection_query(collection_query: CollectionQuery) -> Result<CollectionSearch, CollectionResponse> {
+    let filter_query = FilterQuery {
+        filter: collection_query.filter,
+        filter_lang: collection_query.filter_lang,
+        filter_name: None,
+        q: None,
+        params: HashMap::new(),
+        count: None,
+        as_of: None
+    };
+
+    let filter = match parse_filter_query(0, &filter_query)? {
+        Some(mut filter) => {
+            filter.bind(&filter_query.params).map_err(|err| filter_exception(&err))?;
+            Some(filter)
+        },
+        None => None
+    };
+
+    //collections have no geometry of their own, their extent is searched with bbox
+    let expression = match filter {
+        Some(filter) if filter.expressions.is_spatial() => {
+            return Err(CollectionResponse::BadRequest(exception("InvalidParameterValue",
+                "Spatial operators are not supported on collections, use bbox instead")));
+        },
+        Some(filter) => Some(filter.expressions),
+        None => None
+    };
+
+    let bbox = match collection_query.bbox {
+        Some(text) => {
+            let values: Vec<f64> = text.split(',').filter_map(|value| value.trim().parse().ok()).collect();
+
+            if values.len() != 4 || text.split(',').count() != 4 {
+                return Err(CollectionResponse::BadRequest(exception("InvalidParameterValue",
+                    "The bbox must have 4 comma separated numbers")));
+            }
+
+            Some(Bbox::new(values[0], values[1], values[2], values[3]))
+        },
+        None => None
+    };
+
+    Ok(CollectionSearch::new(collection_query.q, expression, bbox))
+}
+
+fn parse_filter_query(collection_id: i64, filter_query: &FilterQuery) -> Result<Option<Filter>, CollectionResponse> {
+    let text = match &filter_query.filter {
+        Some(text) => text,
+        None => return Ok(None)
+    };
+
+    let expression = match filter_query.filter_lang.as_deref() {
+        None | Some("cql2-text") => cql2_text::parse(text).map_err(|err| {
+            CollectionResponse::BadRequest(serde_json::json!({
+                "code": "InvalidParameterValue",
+                "description": format!("Invalid filter: {err}"),
+                "position": err.position,
+                "reason": err.message
+            }).to_string())
+        })?,
+        Some("cql2-json") => {
+            let value: serde_json::Value = serde_json::from_str(text)
+                .map_err(|err| filter_exception(&FilterError::new("".to_string(), format!("the filter is not valid JSON: {err}"))))?;
+            cql2_json::parse(&value).map_err(|err| filter_exception(&err))?
+        },
+        Some(other) => return Err(CollectionResponse::BadRequest(exception("InvalidParameterValue", &format!("Unsupported filter-lang {other}"))))
+    };
+
+    Ok(Some(Filter::new(collection_id, expression)))
+}
+
+fn filter_exception(err: &FilterError) -> CollectionResponse {
+    CollectionResponse::BadRequest(serde_json::json!({
+        "code": "InvalidParameterValue",
+        "description": err.to_string(),
+        "pointer": err.pointer,
+        "reason": err.reason
+    }).to_string())
+}
+
+fn is_estimated(count: Option<&str>) -> bool {
+    count == Some("estimated")
+}
+
+//self and collection links, prev and next where there are such pages; `page` is none on keyset pages
+fn add_links(collection: &mut FeatureCollection, origin: &Origin<'_>, collection_id: i64, page: Option<i64>, size: i64, estimated: bool) {
+    let Some(paging) = collection.paging.as_mut() else {
+        return;
+    };
+
+    paging.links.push(Link::new(origin.to_string(), "self".to_string()));
+    paging.links.push(Link::new(format!("/collections/{collection_id}"), "collection".to_string()));
+
+    let full = collection.features.len() as i64 >= size;
+
+    match page {
+        Some(page) => {
+            if page > 0 {
+                paging.links.push(Link::new(with_page(origin, "page", &(page - 1).to_string()), "prev".to_string()));
+            }
+
+            //an estimate may be off, so a full page is taken as a sign of more
+            if full && (estimated || (page + 1) * size < paging.number_matched) {
+                paging.links.push(Link::new(with_page(origin, "page", &(page + 1).to_string()), "next".to_string()));
+            }
+        },
+        None => if let Some(next) = &collection.next {
+            paging.links.push(Link::new(with_page(origin, "cursor", &next.to_token()), "next".to_string()));
+        }
+    }
+}
+
+//the requested URI with `page` and `cursor` replaced by `name=value`
+fn with_page(origin: &Origin<'_>, name: &str, value: &str) -> String {
+    let mut query: Vec<String> = origin.query().map(|query| query.as_str().split('&')
+        .filter(|pair| !pair.is_empty() && !matches!(pair.split('=').next(), Some("page") | Some("cursor")))
+        .map(|pair| pair.to_string())
+        .collect())
+        .unwrap_or_default();
+
+    query.push(format!("{name}={value}"));
+
+    format!("{}?{}", origin.path(), query.join("&"))
+}
+
+//a missing cursor starts from the first page
+fn parse_cursor(cursor: Option<&str>) -> Result<PageCursor, CollectionResponse> {
+    match cursor {
+        Some(token) => PageCursor::try_from(token)
+            .map_err(|err| CollectionResponse::BadRequest(exception("InvalidParameterValue", &err))),
+        None => Ok(PageCursor::start())
+    }
+}
+
+//bulk bodies may be far over the default string limit, `limits.bulk` overrides the 32 MiB default
+async fn read_bulk(data: Data<'_>, limits: &Limits) -> Result<Value, CollectionResponse> {
+    let limit = limits.get("bulk").unwrap_or(32.mebibytes());
+
+    let body = match data.open(limit).into_string().await {
+        Ok(body) if body.is_complete() => body.into_inner(),
+        Ok(_) => return Err(CollectionResponse::BadRequest(exception("InvalidParameterValue", &format!("The body is larger than {limit}")))),
+        Err(err) => return Err(CollectionResponse::SystemError(exception("ServerError", &err.to_string())))
+    };
+
+    serde_json::from_str(&body)
+        .map_err(|err| CollectionResponse::BadRequest(exception("InvalidParameterValue", &format!("The body is not valid JSON: {err}"))))
+}
+
+//every feature is checked before anything is written, so all the invalid ones are reported at once
+fn check_features(value: &Value) -> Result<(), CollectionResponse> {
+    let errors: Vec<Value> = value["features"].as_array().into_iter().flatten()
+        .enumerate()
+        .filter_map(|(index, feature)| Feature::check(feature).err()
+            .map(|message| serde_json::json!({"index": index, "message": message})))
+        .collect();
+
+    if errors.is_empty() {
+        return Ok(());
+    }
+
+    Err(CollectionResponse::BadRequest(serde_json::json!({"code": "InvalidParameterValue",
+        "description": "Invalid features, nothing was inserted", "errors": errors}).to_string()))
+}
+
+//reads newline delimited GeoJSON features, the record separators of RFC 8142 are skipped
+fn read_features(data: Data<'_>, limit: ByteUnit) -> FeatureStream<'_> {
+    let reader = BufReader::new(data.open(limit));
+
+    futures::stream::unfold(Some((reader, 0, 0)), move |state| async move {
+        let (mut reader, index, mut read) = state?;
+        let failure = |index, message: String| Some((Err(BulkInsertError { index: Some(index), message }), None));
+
+        let mut line = String::new();
+        let text = loop {
+            line.clear();
+            let length = match reader.read_line(&mut line).await {
+                Ok(length) => length as u64,
+                Err(err) => return failure(index, err.to_string())
+            };
+
+            if length == 0 {
+                if read >= limit.as_u64() {
+                    return failure(index, format!("The body is larger than {limit}"));
+                }
+                return None;
+            }
+            read += length;
+
+            let text = line.trim_matches(|current: char| current.is_whitespace() || current == '\u{1e}');
+            if !text.is_empty() {
+                break text;
+            }
+        };
+
+        let value: Value = match serde_json::from_str(text) {
+            Ok(value) => value,
+            Err(err) => return failure(index, err.to_string())
+        };
+
+        match Feature::check(&value) {
+            Ok(()) => Some((Ok(Feature::from(&value)), Some((reader, index + 1, read)))),
+            Err(message) => failure(index, message)
+        }
+    }).boxed()
+}
+
+//a failure tied to a feature is reported like the invalid ones, anything else is a system error,
+//the backends stop at the first feature they refuse so there is only one
+fn bulk_exception(err: FeatureServiceError) -> CollectionResponse {
+    match err.index {
+        Some(index) => CollectionResponse::BadRequest(serde_json::json!({"code": "InvalidParameterValue",
+            "description": "The first feature that could not be inserted, nothing was inserted",
+            "errors": [{"index": index, "message": err.message}]}).to_string()),
+        None => service_exception(err)
+    }
+}
+
+//missing features, collections and filters are a 404, writes based on an outdated version a 412,
+//writes breaking a rule of the data a 409, what the backend can not do a 501, anything else a 500
+fn service_exception(err: FeatureServiceError) -> CollectionResponse {
+    if err.not_found {
+        return CollectionResponse::NotFound(exception("NotFound", &err.message));
+    }
+
+    if err.conflict {
+        return CollectionResponse::PreconditionFailed(exception("PreconditionFailed", &err.message));
+    }
+
+    if err.constraint {
+        return CollectionResponse::Conflict(exception("Conflict", &err.message));
+    }
+
+    if err.unsupported {
+        return CollectionResponse::NotImplemented(exception("NotImplemented", &err.message));
+    }
+
+    CollectionResponse::SystemError(exception("ServerError", &err.message))
+}
+
+fn etag(version: i64) -> String {
+    format!("\"{version}\"")
+}
+
+//the version a write is based on, `*` allows any version; tags this server did not issue never match
+fn expected_version(if_match: &IfMatch) -> Result<Option<i64>, CollectionResponse> {
+    match if_match.0.as_deref() {
+        None => Err(CollectionResponse::PreconditionRequired(exception("PreconditionRequired",
+            "Send the ETag of the feature in If-Match, or * to overwrite any version"))),
+        Some("*") => Ok(None),
+        Some(tag) => tag.strip_prefix('"').and_then(|tag| tag.strip_suffix('"'))
+            .and_then(|version| version.parse::<i64>().ok())
+            .map(Some)
+            .ok_or_else(|| CollectionResponse::PreconditionFailed(exception("PreconditionFailed",
+                &format!("If-Match {tag} is not the version of the feature"))))
     }
 }
 
-fn create_features_service(pool_state: &State<PgPool>) -> FeatureService {
-    let pool = pool_state.inner().clone();
-    FeatureService::new(FeatureRepository::new(pool))
-}
\ No newline at end of file
+//OGC API exception body
+fn exception(code: &str, description: &str) -> String {
+    serde_json::json!({"code": code, "description": description}).to_string()
+}
+
+fn create_features_service(storage: &State<Storage>) -> FeatureService {
+    FeatureService::new(storage.repository())
+}
+
+fn create_editing_service(storage: &State<Storage>, editor: Editor) -> FeatureService {
+    let mut feature_service = create_features_service(storage);
+    feature_service.set_editor(editor.0);
+    feature_service
+}
+
+#[cfg(test)]
+mod tests {
+    use rocket::http::{ContentType, Header, RawStr, Status};
+    use rocket::local::blocking::Client;
+    use serde_json::{json, Value};
+
+    use crate::config::TrashPolicy;
+    use crate::repository::{storage::Storage, memory_repository::MemoryStore, geopackage_repository};
+
+    fn client(admin_token: Option<&str>) -> Client {
+        let policy = TrashPolicy { retention_days: 0, admin_token: admin_token.map(str::to_string) };
+        Client::tracked(crate::server(Storage::Memory(MemoryStore::default()), policy)).unwrap()
+    }
+
+    fn body(response: rocket::local::blocking::LocalResponse<'_>) -> Value {
+        serde_json::from_str(&response.into_string().unwrap()).unwrap()
+    }
+
+    //a collection of Salvador and São Paulo, returns its id and the ids of the features
+    fn create_cities(client: &Client) -> (i64, Vec<i64>) {
+        let response = client.post("/collections").header(ContentType::JSON).body(json!({"label": "cities", "features": [
+            {"type": "Feature", "geometry": {"type": "Point", "coordinates": [-38.5, -12.9]}, "properties": {"name": "Salvador", "population": 2900000}},
+            {"type": "Feature", "geometry": {"type": "Point", "coordinates": [-46.6, -23.5]}, "properties": {"name": "Sao Paulo", "population": 12300000}}
+        ]}).to_string()).dispatch();
+
+        assert_eq!(response.status(), Status::Ok);
+        let created = body(response);
+        let ids = created["features"].as_array().unwrap().iter().map(|feature| feature["id"].as_i64().unwrap()).collect();
+
+        (created["id"].as_i64().unwrap(), ids)
+    }
+
+    fn feature(id: i64, name: &str) -> String {
+        json!({"id": id, "type": "Feature", "geometry": {"type": "Point", "coordinates": [-38.5, -12.9]}, "properties": {"name": name}}).to_string()
+    }
+
+    #[test]
+    fn declares_the_filter_conformance() {
+        let client = client(None);
+
+        let conformance = body(client.get("/conformance").dispatch());
+
+        assert!(conformance["conformsTo"].as_array().unwrap().contains(&json!("http://www.opengis.net/spec/cql2/1.0/conf/cql2-text")));
+        assert!(!conformance["conformsTo"].as_array().unwrap().contains(&json!("http://www.opengis.net/spec/cql2/1.0/conf/basic-spatial-functions-plus")));
+        assert_eq!(conformance["filterLanguages"], json!(["cql2-text", "cql2-json"]));
+    }
+
+    #[test]
+    fn lists_and_filters_items() {
+        let client = client(None);
+        let (id, _) = create_cities(&client);
+
+        let all = body(client.get(format!("/collections/{id}/items?size=10")).dispatch());
+        let large = body(client.get(format!("/collections/{id}/items?size=10&page=0&filter=population%20%3E%205000000")).dispatch());
+
+        assert_eq!(all["numberMatched"], 2);
+        assert_eq!(all["features"].as_array().unwrap().len(), 2);
+        assert_eq!(large["numberMatched"], 1);
+        assert_eq!(large["features"][0]["properties"]["name"], "Sao Paulo");
+    }
+
+    #[test]
+    fn lists_items_in_a_bbox() {
+        let client = client(None);
+        let (id, ids) = create_cities(&client);
+
+        let keyset = body(client.get(format!("/collections/{id}/items/-39/-13/-38/-12?size=10")).dispatch());
+        let paged = body(client.get(format!("/collections/{id}/items/-39/-13/-38/-12?size=10&page=0&count=estimated")).dispatch());
+        let both = client.get(format!("/collections/{id}/items/-39/-13/-38/-12?size=10&page=0&cursor=abc")).dispatch();
+
+        assert_eq!(keyset["features"].as_array().unwrap().len(), 1);
+        assert_eq!(paged["features"][0]["properties"]["name"], "Salvador");
+        assert_eq!(both.status(), Status::BadRequest);
+
+        //the feature routes still come first
+        let feature = client.get(format!("/collections/{id}/items/{}", ids[0])).dispatch();
+        let history = client.get(format!("/collections/{id}/items/{}/history?size=10", ids[0])).dispatch();
+        let short = client.get(format!("/collections/{id}/items/-39/-13/-38?size=10")).dispatch();
+
+        assert_eq!(feature.status(), Status::Ok);
+        assert_eq!(history.status(), Status::Ok);
+        assert_eq!(short.status(), Status::UnprocessableEntity);
+    }
+
+    #[test]
+    fn rejects_invalid_filters_with_the_position() {
+        let client = client(None);
+        let (id, _) = create_cities(&client);
+
+        let response = client.get(format!("/collections/{id}/items?size=10&filter=population%20%3E")).dispatch();
+
+        assert_eq!(response.status(), Status::BadRequest);
+        let error = body(response);
+        assert_eq!(error["code"], "InvalidParameterValue");
+        assert_eq!(error["position"], 12);
+    }
+
+    #[test]
+    fn unbound_parameters_are_rejected_by_name() {
+        let client = client(None);
+        let (id, _) = create_cities(&client);
+        let stored = json!({"where": {"op": ">", "args": [{"property": "population"}, {"$param": "min"}]}}).to_string();
+
+        let saved = client.put(format!("/collections/{id}/filters/large")).header(ContentType::JSON).body(stored).dispatch();
+        let unbound = client.get(format!("/collections/{id}/items?size=10&page=0&filter-name=large")).dispatch();
+        let bound = body(client.get(format!("/collections/{id}/items?size=10&page=0&filter-name=large&params[min]=5000000")).dispatch());
+
+        assert_eq!(saved.status(), Status::Ok);
+        assert_eq!(unbound.status(), Status::BadRequest);
+        assert_eq!(body(unbound)["reason"], "the parameter min has no value");
+        assert_eq!(bound["numberMatched"], 1);
+
+        let filter = RawStr::new(r#"{"op": "=", "args": [{"property": "kind"}, {"$param": "kind"}]}"#).percent_encode().to_string();
+        let collections = client.get(format!("/collections?size=10&page=0&filter-lang=cql2-json&filter={filter}")).dispatch();
+
+        assert_eq!(collections.status(), Status::BadRequest);
+        assert_eq!(body(collections)["reason"], "the parameter kind has no value");
+    }
+
+    #[test]
+    fn stored_filters_must_be_objects() {
+        let client = client(None);
+        let (id, _) = create_cities(&client);
+
+        let response = client.put(format!("/collections/{id}/filters/large")).header(ContentType::JSON).body("[]").dispatch();
+
+        assert_eq!(response.status(), Status::BadRequest);
+        assert_eq!(body(response)["reason"], "the filter must be a JSON object");
+    }
+
+    #[test]
+    fn updates_require_the_current_etag() {
+        let client = client(None);
+        let (id, ids) = create_cities(&client);
+
+        let response = client.get(format!("/collections/{id}/items/{}", ids[0])).dispatch();
+        let etag = response.headers().get_one("ETag").unwrap().to_string();
+
+        let unversioned = client.put(format!("/collections/{id}/item")).header(ContentType::JSON)
+            .body(feature(ids[0], "Salvador da Bahia")).dispatch();
+        let updated = client.put(format!("/collections/{id}/item")).header(ContentType::JSON)
+            .header(Header::new("If-Match", etag.clone())).body(feature(ids[0], "Salvador da Bahia")).dispatch();
+        let new_etag = updated.headers().get_one("ETag").map(str::to_string);
+        let stale = client.put(format!("/collections/{id}/item")).header(ContentType::JSON)
+            .header(Header::new("If-Match", etag.clone())).body(feature(ids[0], "Salvador")).dispatch();
+
+        assert_eq!(unversioned.status(), Status::PreconditionRequired);
+        assert_eq!(updated.status(), Status::Ok);
+        assert_ne!(new_etag, Some(etag));
+        assert_eq!(stale.status(), Status::PreconditionFailed);
+    }
+
+    #[test]
+    fn deleted_features_are_not_found() {
+        let client = client(None);
+        let (id, ids) = create_cities(&client);
+
+        let deleted = client.delete(format!("/collections/{id}/items/{}", ids[1])).header(Header::new("If-Match", "*")).dispatch();
+        let response = client.get(format!("/collections/{id}/items/{}", ids[1])).dispatch();
+
+        assert_eq!(deleted.status(), Status::NoContent);
+        assert_eq!(response.status(), Status::NotFound);
+        assert_eq!(body(response)["code"], "NotFound");
+    }
+
+    #[test]
+    fn features_keep_at_least_one_collection() {
+        let client = client(None);
+        let (id, ids) = create_cities(&client);
+        let other = body(client.post("/collections").header(ContentType::JSON).body(json!({"label": "capitals"}).to_string()).dispatch());
+        let other = other["id"].as_i64().unwrap();
+
+        let attached = body(client.put(format!("/features/{}/collections/{other}", ids[0])).dispatch());
+        let detached = client.delete(format!("/features/{}/collections/{id}", ids[0])).dispatch().status();
+        let last = client.delete(format!("/features/{}/collections/{other}", ids[0])).dispatch();
+
+        assert_eq!(attached.as_array().unwrap().len(), 2);
+        assert_eq!(detached, Status::NoContent);
+        assert_eq!(last.status(), Status::Conflict);
+        assert_eq!(body(last)["code"], "Conflict");
+        assert_eq!(body(client.get(format!("/features/{}/collections", ids[0])).dispatch())[0]["id"], other);
+    }
+
+    #[test]
+    fn unsupported_operations_are_not_implemented() {
+        let path = std::env::temp_dir().join(format!("geoxidated-routes-{}.gpkg", std::process::id()));
+        let runtime = rocket::tokio::runtime::Runtime::new().unwrap();
+        let pool = runtime.block_on(geopackage_repository::open_geopackage(&path.to_string_lossy())).unwrap();
+        let client = Client::tracked(crate::server(Storage::GeoPackage(pool), TrashPolicy { retention_days: 0, admin_token: None })).unwrap();
+        let (id, ids) = create_cities(&client);
+
+        let response = client.get(format!("/collections/{id}/items/{}/history", ids[0])).dispatch();
+
+        assert_eq!(response.status(), Status::NotImplemented);
+        assert_eq!(body(response)["code"], "NotImplemented");
+        drop(client);
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn unknown_paths_answer_an_exception() {
+        let client = client(None);
+
+        let response = client.get("/nothing/here").dispatch();
+
+        assert_eq!(response.status(), Status::NotFound);
+        assert_eq!(body(response)["code"], "NotFound");
+    }
+
+    #[test]
+    fn purging_requires_the_admin_token() {
+        let disabled = client(None).post("/trash/purge").header(Header::new("Authorization", "Bearer s3cret")).dispatch().status();
+
+        let client = client(Some("s3cret"));
+        let (id, ids) = create_cities(&client);
+        client.delete(format!("/collections/{id}/items/{}", ids[0])).header(Header::new("If-Match", "*")).dispatch();
+
+        let anonymous = client.post("/trash/purge").dispatch().status();
+        let purged = body(client.post("/trash/purge").header(Header::new("Authorization", "Bearer s3cret")).dispatch());
+
+        assert_eq!(disabled, Status::Forbidden);
+        assert_eq!(anonymous, Status::Forbidden);
+        assert_eq!(purged["purgedFeatures"], 1);
+    }
+}
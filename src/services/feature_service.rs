@@ -30,12 +30,30 @@ use std::{fmt::Display, error::Error};
 
 use derive_new::new;
 
-use crate::{repository::features_repository::{FeatureRepository, FeatureRepositoryError}, model::{feature_collection::{FeatureCollectionList, FeatureCollection}, feature::{self, Feature}, bbox::Bbox, filter::Filter}};
+use crate::{repository::features_repository::{FeatureRepository, FeatureRepositoryError, BulkInsertError, FeatureStream}, model::{feature_collection::{FeatureCollectionList, FeatureCollection}, feature::{self, Feature}, bbox::Bbox, filter::Filter,
+    stored_filter::{StoredFilter, StoredFilterList}, collection_search::CollectionSearch,
+    search_hit::SearchResult, cursor::Cursor, paging::Paging, feature_revision::FeatureHistory, instant::Instant,
+    trash::{Trash, PurgedTrash}}};
 
+static EXPORT_PAGE_SIZE: i64 = 500;
 
 #[derive(new, Debug)]
 pub struct FeatureServiceError {
-    pub message: String
+    pub message: String,
+    /// The position of the rejected feature in a bulk insert.
+    #[new(default)]
+    pub index: Option<usize>,
+    #[new(default)]
+    pub not_found: bool,
+    /// The feature was changed since the version the write was based on.
+    #[new(default)]
+    pub conflict: bool,
+    /// The write would break a rule of the data, like leaving a feature without a collection.
+    #[new(default)]
+    pub constraint: bool,
+    /// The backend can not do what was asked.
+    #[new(default)]
+    pub unsupported: bool
 }
 
 impl Display for FeatureServiceError {
@@ -46,29 +64,81 @@ impl Display for FeatureServiceError {
 
 impl Error for FeatureServiceError {}
 
+impl From<BulkInsertError> for FeatureServiceError {
+    fn from(err: BulkInsertError) -> Self {
+        FeatureServiceError { message: err.message, index: err.index, not_found: false, conflict: false, constraint: false,
+            unsupported: false }
+    }
+}
+
+impl From<FeatureRepositoryError> for FeatureServiceError {
+    fn from(err: FeatureRepositoryError) -> Self {
+        FeatureServiceError { message: err.message, index: None, not_found: err.not_found, conflict: err.conflict,
+            constraint: err.constraint, unsupported: err.unsupported }
+    }
+}
+
 #[derive(new)]
 pub struct FeatureService {
-    repository: FeatureRepository
+    repository: Box<dyn FeatureRepository>
 }
 
 impl FeatureService {
 
+    /// Records the following writes in the feature history as made by `editor`.
+    pub fn set_editor(&mut self, editor: Option<String>) {
+        self.repository.set_editor(editor);
+    }
+
     pub async fn get_collections(&mut self, page: i64, size: i64) -> Result<FeatureCollectionList, FeatureServiceError> {
         
         let result = self.repository.get_collections(offset(page, size), size).await;
         
         match result {
             Ok(collections) => Ok(FeatureCollectionList::new(collections)),
-            Err(err) => Err(FeatureServiceError::new(err.message))
+            Err(err) => Err(FeatureServiceError::from(err))
+        }
+    }
+
+    pub async fn get_collections_after(&mut self, cursor: &Cursor, size: i64) -> Result<FeatureCollectionList, FeatureServiceError> {
+
+        let result = self.repository.get_collections_after(cursor.after, size).await;
+
+        match result {
+            Ok(collections) => Ok(FeatureCollectionList::new(collections)),
+            Err(err) => Err(FeatureServiceError::from(err))
+        }
+    }
+
+    pub async fn search_collections(&mut self, search: &CollectionSearch, page: i64, size: i64) -> Result<FeatureCollectionList, FeatureServiceError> {
+        
+        let result = self.repository.search_collections(search, offset(page, size), size).await;
+        
+        match result {
+            Ok(collections) => Ok(FeatureCollectionList::new(collections)),
+            Err(err) => Err(FeatureServiceError::from(err))
+        }
+    }
+
+    pub async fn get_collection(&mut self, id: i64) -> Result<FeatureCollection, FeatureServiceError> {
+        let result = self.repository.get_collection_by_id(id).await;
+
+        match result {
+            Ok(collection) => Ok(collection),
+            Err(err) => Err(FeatureServiceError::from(err))
         }
     }
 
     pub async fn create_collection(&mut self, collection: &FeatureCollection) -> Result<FeatureCollection, FeatureServiceError> {
+        if !collection.features.is_empty() {
+            return Ok(self.repository.create_collection_with_features(collection).await?);
+        }
+
         let result = self.repository.create_collection(collection).await;
 
         match result {
             Ok(collection) => Ok(collection),
-            Err(err) => Err(FeatureServiceError::new(err.message))
+            Err(err) => Err(FeatureServiceError::from(err))
         }
     } 
 
@@ -77,51 +147,120 @@ impl FeatureService {
 
         match result {
             Ok(collection) => Ok(collection),
-            Err(err) => Err(FeatureServiceError::new(err.message))
+            Err(err) => Err(FeatureServiceError::from(err))
         }
     } 
 
-    pub async fn get_features_in_collection(&mut self, id: i64, page: i64, size: i64) -> Result<FeatureCollection, FeatureServiceError> {
+    pub async fn get_features_in_collection(&mut self, id: i64, page: i64, size: i64, estimated: bool) -> Result<FeatureCollection, FeatureServiceError> {
         let features_result = self.repository.get_features_in_collection(id, offset(page, size), size).await;
         let feature_collection_result = self.repository.get_collection_by_id(id).await;
 
-        match feature_collection_result {
+        let collection = match feature_collection_result {
             Ok(collection) => wrap_into_collection(collection, features_result),
-            Err(err) => Err(FeatureServiceError::new(err.message))
-        }
+            Err(err) => Err(FeatureServiceError::from(err))
+        };
+
+        with_paging(collection, self.repository.count_features_in_collection(id, estimated).await)
     } 
 
+    /// The collection as it was at `as_of`, features removed since are included and later ones left out.
+    pub async fn get_features_in_collection_as_of(&mut self, id: i64, as_of: &Instant, page: i64, size: i64) -> Result<FeatureCollection, FeatureServiceError> {
+        let feature_collection_result = self.repository.get_collection_by_id(id).await;
+
+        match feature_collection_result {
+            Ok(collection) => wrap_into_collection(collection,
+                self.repository.get_features_in_collection_as_of(id, as_of, offset(page, size), size).await),
+            Err(err) => Err(FeatureServiceError::from(err))
+        }
+    }
+
+    pub async fn get_feature_history(&mut self, collection_id: i64, feature_id: i64) -> Result<FeatureHistory, FeatureServiceError> {
+        let result = self.repository.get_feature_history(collection_id, feature_id).await;
+
+        match result {
+            Ok(revisions) => Ok(FeatureHistory::new(feature_id, revisions)),
+            Err(err) => Err(FeatureServiceError::from(err))
+        }
+    }
+
+    pub async fn get_features_in_collection_after(&mut self, id: i64, cursor: &Cursor, size: i64, estimated: bool) -> Result<FeatureCollection, FeatureServiceError> {
+        let features_result = self.repository.get_features_in_collection_after(id, cursor.after, size).await;
+        let feature_collection_result = self.repository.get_collection_by_id(id).await;
+
+        let collection = match feature_collection_result {
+            Ok(collection) => wrap_into_page(collection, features_result, size),
+            Err(err) => Err(FeatureServiceError::from(err))
+        };
+
+        with_paging(collection, self.repository.count_features_in_collection(id, estimated).await)
+    }
+
+    pub async fn get_features_in_collection_by_bbox_after(&mut self, id: i64,
+         bbox: &Bbox, cursor: &Cursor, size: i64, estimated: bool) -> Result<FeatureCollection, FeatureServiceError> {
+        let features_result = self.repository.get_features_in_collection_by_bbox_after(id, bbox, cursor.after, size).await;
+        let feature_collection_result = self.repository.get_collection_by_id(id).await;
+
+        let collection = match feature_collection_result {
+            Ok(collection) => wrap_into_page(collection, features_result, size),
+            Err(err) => Err(FeatureServiceError::from(err))
+        };
+
+        with_paging(collection, self.repository.count_features_in_collection_by_bbox(id, bbox, estimated).await)
+    }
+
     pub async fn get_features_in_collection_by_bbox(&mut self, id: i64,
-         bbox: &Bbox, page: i64, size: i64) -> Result<FeatureCollection, FeatureServiceError> {
+         bbox: &Bbox, page: i64, size: i64, estimated: bool) -> Result<FeatureCollection, FeatureServiceError> {
         let features_result = self.repository.get_features_in_collection_by_bbox(id, bbox, offset(page, size), size).await;
         let feature_collection_result = self.repository.get_collection_by_id(id).await;
 
-        match feature_collection_result {
+        let collection = match feature_collection_result {
             Ok(collection) => wrap_into_collection(collection, features_result),
-            Err(err) => Err(FeatureServiceError::new(err.message))
-        }
+            Err(err) => Err(FeatureServiceError::from(err))
+        };
+
+        with_paging(collection, self.repository.count_features_in_collection_by_bbox(id, bbox, estimated).await)
     } 
 
     pub async fn get_features_in_collection_by_id(&mut self, collection_id: i64,
         feature_id: i64) -> Result<FeatureCollection, FeatureServiceError> {
-       let features_result = self.repository.get_feature_by_id(feature_id).await;
+       let features_result = self.repository.get_feature_by_id(collection_id, feature_id).await;
        let feature_collection_result = self.repository.get_collection_by_id(collection_id).await;
 
        match feature_collection_result {
            Ok(collection) => wrap_feature_into_collection(collection, features_result),
-           Err(err) => Err(FeatureServiceError::new(err.message))
+           Err(err) => Err(FeatureServiceError::from(err))
        }
    } 
 
+    //the collection is looked up first, nothing is added to a trashed one
     pub async fn create_feature(&mut self, collection_id: i64, feature: &Feature) -> Result<FeatureCollection, FeatureServiceError> {
+        let collection = match self.repository.get_collection_by_id(collection_id).await {
+            Ok(collection) => collection,
+            Err(err) => return Err(FeatureServiceError::from(err))
+        };
+
         let features_result = self.repository.create_feature(collection_id, feature).await;
-        let feature_collection_result = self.repository.get_collection_by_id(collection_id).await;
+        wrap_feature_into_collection(collection, features_result)
+    }     
 
-        match feature_collection_result {
-            Ok(collection) => wrap_feature_into_collection(collection, features_result),
-            Err(err) => Err(FeatureServiceError::new(err.message))
+    /// Inserts all the features or none of them.
+    pub async fn create_features(&mut self, collection_id: i64, features: &[Feature]) -> Result<FeatureCollection, FeatureServiceError> {
+        let mut collection = match self.repository.get_collection_by_id(collection_id).await {
+            Ok(collection) => collection,
+            Err(err) => return Err(FeatureServiceError::from(err))
+        };
+
+        collection.features = self.repository.create_features(collection_id, features).await?;
+        Ok(collection)
+    }
+
+    pub async fn import_features(&mut self, collection_id: i64, features: FeatureStream<'_>) -> Result<u64, FeatureServiceError> {
+        if let Err(err) = self.repository.get_collection_by_id(collection_id).await {
+            return Err(FeatureServiceError::from(err));
         }
-    }     
+
+        Ok(self.repository.import_features(collection_id, features).await?)
+    }
 
     pub async fn update_feature(&mut self, collection_id: i64, feature: &Feature) -> Result<FeatureCollection, FeatureServiceError> {
         let feature_collection_result = self.repository.get_collection_by_id(collection_id).await;
@@ -129,21 +268,189 @@ impl FeatureService {
 
         match feature_collection_result {
             Ok(collection) => wrap_feature_into_collection(collection, features_result),
-            Err(err) => Err(FeatureServiceError::new(err.message))
+            Err(err) => Err(FeatureServiceError::from(err))
         }
     }  
 
     pub async fn filter_features_in_collection(&mut self, collection_id: i64,
-        filter: &Filter) -> Result<FeatureCollection, FeatureServiceError> {
-            todo!()
-    //    let features_result = self.repository.get_feature_by_id(feature_id).await;
-    //    let feature_collection_result = self.repository.get_collection_by_id(collection_id).await;
-
-    //    match feature_collection_result {
-    //        Ok(collection) => wrap_feature_into_collection(collection, features_result),
-    //        Err(err) => Err(FeatureServiceError::new(err.message))
-    //    }
-   }     
+        filter: &Filter, page: i64, size: i64, estimated: bool) -> Result<FeatureCollection, FeatureServiceError> {
+        let features_result = self.repository.get_features_in_collection_by_filter(collection_id, filter, offset(page, size), size).await;
+        let feature_collection_result = self.repository.get_collection_by_id(collection_id).await;
+
+        let collection = match feature_collection_result {
+            Ok(collection) => wrap_into_collection(collection, features_result),
+            Err(err) => Err(FeatureServiceError::from(err))
+        };
+
+        with_paging(collection, self.repository.count_features_in_collection_by_filter(collection_id, filter, estimated).await)
+    }
+
+    pub async fn search_features_in_collection(&mut self, collection_id: i64, text: &str,
+        filter: Option<&Filter>, page: i64, size: i64) -> Result<SearchResult, FeatureServiceError> {
+        let hits_result = self.repository.search_features_in_collection(collection_id, text, filter, offset(page, size), size).await;
+        let feature_collection_result = self.repository.get_collection_by_id(collection_id).await;
+
+        match (feature_collection_result, hits_result) {
+            (Ok(collection), Ok(hits)) => Ok(SearchResult::new(collection, hits)),
+            (Err(err), _) | (_, Err(err)) => Err(FeatureServiceError::from(err))
+        }
+    }
+
+    pub async fn delete_feature(&mut self, collection_id: i64, feature_id: i64, version: Option<i64>) -> Result<(), FeatureServiceError> {
+        let result = self.repository.delete_feature(collection_id, feature_id, version).await;
+
+        match result {
+            Ok(()) => Ok(()),
+            Err(err) => Err(FeatureServiceError::from(err))
+        }
+    }
+
+    pub async fn get_collections_of_feature(&mut self, feature_id: i64) -> Result<FeatureCollectionList, FeatureServiceError> {
+        let result = self.repository.get_collections_of_feature(feature_id).await;
+
+        match result {
+            Ok(collections) => Ok(FeatureCollectionList::new(collections)),
+            Err(err) => Err(FeatureServiceError::from(err))
+        }
+    }
+
+    /// Adds the feature to the collection and returns every collection now holding it.
+    pub async fn attach_feature(&mut self, collection_id: i64, feature_id: i64) -> Result<FeatureCollectionList, FeatureServiceError> {
+        if let Err(err) = self.repository.attach_feature(collection_id, feature_id).await {
+            return Err(FeatureServiceError::from(err));
+        }
+
+        self.get_collections_of_feature(feature_id).await
+    }
+
+    pub async fn detach_feature(&mut self, collection_id: i64, feature_id: i64) -> Result<(), FeatureServiceError> {
+        let result = self.repository.detach_feature(collection_id, feature_id).await;
+
+        match result {
+            Ok(()) => Ok(()),
+            Err(err) => Err(FeatureServiceError::from(err))
+        }
+    }
+
+    pub async fn delete_collection(&mut self, id: i64) -> Result<(), FeatureServiceError> {
+        let result = self.repository.delete_collection(id).await;
+
+        match result {
+            Ok(()) => Ok(()),
+            Err(err) => Err(FeatureServiceError::from(err))
+        }
+    }
+
+    pub async fn get_trash(&mut self, page: i64, size: i64) -> Result<Trash, FeatureServiceError> {
+        let result = self.repository.get_trash(offset(page, size), size).await;
+
+        match result {
+            Ok(items) => Ok(Trash::new(items)),
+            Err(err) => Err(FeatureServiceError::from(err))
+        }
+    }
+
+    pub async fn restore_feature(&mut self, feature_id: i64) -> Result<(), FeatureServiceError> {
+        let result = self.repository.restore_feature(feature_id).await;
+
+        match result {
+            Ok(()) => Ok(()),
+            Err(err) => Err(FeatureServiceError::from(err))
+        }
+    }
+
+    /// Takes the collection out of the trash and returns it.
+    pub async fn restore_collection(&mut self, id: i64) -> Result<FeatureCollection, FeatureServiceError> {
+        if let Err(err) = self.repository.restore_collection(id).await {
+            return Err(FeatureServiceError::from(err));
+        }
+
+        self.get_collection(id).await
+    }
+
+    /// Deletes for good what has been in the trash for more than `retention_days`.
+    pub async fn purge_trash(&mut self, retention_days: i64) -> Result<PurgedTrash, FeatureServiceError> {
+        let result = self.repository.purge_trash(&Instant::days_ago(retention_days)).await;
+
+        match result {
+            Ok(purged) => Ok(purged),
+            Err(err) => Err(FeatureServiceError::from(err))
+        }
+    }
+
+    //filters of a trashed collection can neither be read nor changed
+    pub async fn save_filter(&mut self, collection_id: i64, name: &str, filter: &Filter) -> Result<StoredFilter, FeatureServiceError> {
+        if let Err(err) = self.repository.get_collection_by_id(collection_id).await {
+            return Err(FeatureServiceError::from(err));
+        }
+
+        let result = self.repository.save_filter(collection_id, name, filter).await;
+
+        match result {
+            Ok(filter) => Ok(filter),
+            Err(err) => Err(FeatureServiceError::from(err))
+        }
+    }
+
+    pub async fn get_filter(&mut self, collection_id: i64, name: &str) -> Result<StoredFilter, FeatureServiceError> {
+        let result = self.repository.get_filter(collection_id, name).await;
+
+        match result {
+            Ok(filter) => Ok(filter),
+            Err(err) => Err(FeatureServiceError::from(err))
+        }
+    }
+
+    pub async fn get_filters(&mut self, collection_id: i64) -> Result<StoredFilterList, FeatureServiceError> {
+        if let Err(err) = self.repository.get_collection_by_id(collection_id).await {
+            return Err(FeatureServiceError::from(err));
+        }
+
+        let result = self.repository.get_filters(collection_id).await;
+
+        match result {
+            Ok(filters) => Ok(StoredFilterList::new(filters)),
+            Err(err) => Err(FeatureServiceError::from(err))
+        }
+    }
+
+    /// Copies the collection and all of its features into `target`, returning the copied collection.
+    pub async fn export_collection(&mut self, collection_id: i64, target: &mut dyn FeatureRepository) -> Result<FeatureCollection, FeatureServiceError> {
+        let collection = self.repository.get_collection_by_id(collection_id).await?;
+        let exported = target.create_collection(&collection).await?;
+        let exported_id: Option<i64> = (&exported.id).into();
+        let exported_id = exported_id.unwrap_or_default();
+
+        //keyset pages keep the order stable, each page is inserted in one call
+        let mut after = Cursor::start().after;
+        loop {
+            let features = self.repository.get_features_in_collection_after(collection_id, after, EXPORT_PAGE_SIZE).await?;
+
+            if !features.is_empty() {
+                target.create_features(exported_id, &features).await?;
+            }
+
+            match features.last().and_then(|feature| Option::<i64>::from(&feature.id)) {
+                Some(last_id) if features.len() as i64 == EXPORT_PAGE_SIZE => after = last_id,
+                _ => break
+            }
+        }
+
+        Ok(exported)
+    }
+
+    pub async fn delete_filter(&mut self, collection_id: i64, name: &str) -> Result<(), FeatureServiceError> {
+        if let Err(err) = self.repository.get_collection_by_id(collection_id).await {
+            return Err(FeatureServiceError::from(err));
+        }
+
+        let result = self.repository.delete_filter(collection_id, name).await;
+
+        match result {
+            Ok(()) => Ok(()),
+            Err(err) => Err(FeatureServiceError::from(err))
+        }
+    }
 }
 
 
@@ -158,7 +465,32 @@ fn wrap_into_collection(mut collection: FeatureCollection,
             collection.features = features;
             Ok(collection)
         }
-        Err(err) => Err(FeatureServiceError::new(err.message))
+        Err(err) => Err(FeatureServiceError::from(err))
+    }
+}
+
+//a full page may be followed by another one
+fn wrap_into_page(collection: FeatureCollection,
+    features_result: Result<Vec<Feature>, FeatureRepositoryError>, size: i64) -> Result<FeatureCollection, FeatureServiceError> {
+    let mut collection = wrap_into_collection(collection, features_result)?;
+
+    let last_id = collection.features.last().and_then(|feature| Option::<i64>::from(&feature.id));
+    collection.next = Cursor::following(last_id, collection.features.len(), size);
+
+    Ok(collection)
+}
+
+//numberMatched and timeStamp of an item response
+fn with_paging(collection_result: Result<FeatureCollection, FeatureServiceError>,
+    count_result: Result<i64, FeatureRepositoryError>) -> Result<FeatureCollection, FeatureServiceError> {
+    let mut collection = collection_result?;
+
+    match count_result {
+        Ok(count) => {
+            collection.paging = Some(Paging::new(count));
+            Ok(collection)
+        },
+        Err(err) => Err(FeatureServiceError::from(err))
     }
 }
 
@@ -169,6 +501,238 @@ fn wrap_feature_into_collection(mut collection: FeatureCollection,
             collection.features = vec![features];
             Ok(collection)
         }
-        Err(err) => Err(FeatureServiceError::new(err.message))
+        Err(err) => Err(FeatureServiceError::from(err))
+    }
+}
+#[cfg(test)]
+mod tests {
+    use futures::executor::block_on;
+    use serde_json::{json, Value};
+
+    use crate::model::{feature::Feature, feature_collection::FeatureCollection, bbox::Bbox, cursor::Cursor,
+        filter::{Filter, cql2_text}};
+    use crate::repository::memory_repository::{MemoryFeatureRepository, MemoryStore};
+    use crate::repository::geopackage_repository::{GeoPackageFeatureRepository, open_geopackage};
+
+    use super::FeatureService;
+
+    fn service(store: &MemoryStore) -> FeatureService {
+        FeatureService::new(Box::new(MemoryFeatureRepository::new(store.clone())))
     }
-}
\ No newline at end of file
+
+    fn point(name: &str, x: f64, y: f64, population: i64) -> Value {
+        json!({"type": "Feature", "geometry": {"type": "Point", "coordinates": [x, y]},
+            "properties": {"name": name, "population": population}})
+    }
+
+    //a collection of Salvador, Recife and São Paulo, returns its id and the ids of the features
+    fn create_cities(service: &mut FeatureService) -> (i64, Vec<i64>) {
+        let collection = FeatureCollection::from(&json!({"label": "cities", "features": [
+            point("Salvador", -38.5, -12.9, 2900000),
+            point("Recife", -34.9, -8.0, 1600000),
+            point("Sao Paulo", -46.6, -23.5, 12300000)
+        ]}));
+
+        let created = block_on(service.create_collection(&collection)).unwrap();
+        let ids = created.features.iter().map(|feature| Option::<i64>::from(&feature.id).unwrap()).collect();
+
+        (Option::<i64>::from(&created.id).unwrap(), ids)
+    }
+
+    fn names(collection: &FeatureCollection) -> Vec<String> {
+        let value: Value = serde_json::from_str(&collection.to_geo_json()).unwrap();
+        value["features"].as_array().into_iter().flatten()
+            .map(|feature| feature["properties"]["name"].as_str().unwrap().to_string())
+            .collect()
+    }
+
+    fn filter(collection_id: i64, text: &str) -> Filter {
+        Filter::new(collection_id, cql2_text::parse(text).unwrap())
+    }
+
+    #[test]
+    fn pages_features_by_offset_with_the_total() {
+        let store = MemoryStore::default();
+        let mut service = service(&store);
+        let (id, _) = create_cities(&mut service);
+
+        let first = block_on(service.get_features_in_collection(id, 0, 2, false)).unwrap();
+        let second = block_on(service.get_features_in_collection(id, 1, 2, false)).unwrap();
+
+        assert_eq!(names(&first), ["Salvador", "Recife"]);
+        assert_eq!(names(&second), ["Sao Paulo"]);
+        assert_eq!(first.paging.unwrap().number_matched, 3);
+    }
+
+    #[test]
+    fn pages_features_by_keyset() {
+        let store = MemoryStore::default();
+        let mut service = service(&store);
+        let (id, ids) = create_cities(&mut service);
+
+        let first = block_on(service.get_features_in_collection_after(id, &Cursor::start(), 2, false)).unwrap();
+        let next = first.next.clone().unwrap();
+        let second = block_on(service.get_features_in_collection_after(id, &next, 2, false)).unwrap();
+
+        assert_eq!(next.after, ids[1]);
+        assert_eq!(names(&second), ["Sao Paulo"]);
+        assert!(second.next.is_none());
+    }
+
+    #[test]
+    fn finds_features_in_a_bbox() {
+        let store = MemoryStore::default();
+        let mut service = service(&store);
+        let (id, _) = create_cities(&mut service);
+
+        let northeast = Bbox::new(-40.0, -14.0, -34.0, -7.0);
+        let collection = block_on(service.get_features_in_collection_by_bbox(id, &northeast, 0, 10, false)).unwrap();
+
+        assert_eq!(names(&collection), ["Salvador", "Recife"]);
+        assert_eq!(collection.paging.unwrap().number_matched, 2);
+    }
+
+    #[test]
+    fn filters_features() {
+        let store = MemoryStore::default();
+        let mut service = service(&store);
+        let (id, _) = create_cities(&mut service);
+
+        let large = block_on(service.filter_features_in_collection(id, &filter(id, "population > 2000000"), 0, 10, false)).unwrap();
+        let named = block_on(service.filter_features_in_collection(id, &filter(id, "name LIKE 'S%' AND NOT population > 5000000"), 0, 10, false)).unwrap();
+
+        assert_eq!(names(&large), ["Salvador", "Sao Paulo"]);
+        assert_eq!(names(&named), ["Salvador"]);
+    }
+
+    #[test]
+    fn updates_only_the_current_version() {
+        let store = MemoryStore::default();
+        let mut service = service(&store);
+        let (id, ids) = create_cities(&mut service);
+
+        let current = block_on(service.get_features_in_collection_by_id(id, ids[0])).unwrap();
+        let version = current.features[0].version.unwrap();
+
+        let mut feature = Feature::from(&json!({"id": ids[0], "geometry": {"type": "Point", "coordinates": [-38.5, -12.9]},
+            "properties": {"name": "Salvador da Bahia"}}));
+        feature.version = Some(version);
+        let updated = block_on(service.update_feature(id, &feature)).unwrap();
+
+        let stale = block_on(service.update_feature(id, &feature)).err().unwrap();
+
+        assert_eq!(names(&updated), ["Salvador da Bahia"]);
+        assert!(updated.features[0].version.unwrap() > version);
+        assert!(stale.conflict);
+    }
+
+    #[test]
+    fn records_the_editor_in_the_history() {
+        let store = MemoryStore::default();
+        let mut service = service(&store);
+        service.set_editor(Some("editor@example.org".to_string()));
+        let (id, ids) = create_cities(&mut service);
+
+        let feature = Feature::from(&json!({"id": ids[1], "geometry": {"type": "Point", "coordinates": [-34.9, -8.0]},
+            "properties": {"name": "Recife", "population": 1700000}}));
+        block_on(service.update_feature(id, &feature)).unwrap();
+
+        let history: Value = serde_json::from_str(&block_on(service.get_feature_history(id, ids[1])).unwrap().to_json()).unwrap();
+        let operations: Vec<&str> = history["revisions"].as_array().unwrap().iter()
+            .map(|revision| revision["operation"].as_str().unwrap())
+            .collect();
+
+        assert_eq!(operations, ["create", "attach", "update"]);
+        assert_eq!(history["revisions"][2]["changedBy"], "editor@example.org");
+        assert_eq!(history["revisions"][2]["properties"]["population"], 1700000);
+    }
+
+    #[test]
+    fn deleted_features_can_be_restored() {
+        let store = MemoryStore::default();
+        let mut service = service(&store);
+        let (id, ids) = create_cities(&mut service);
+
+        block_on(service.delete_feature(id, ids[2], None)).unwrap();
+        let missing = block_on(service.get_features_in_collection_by_id(id, ids[2])).err().unwrap();
+        let trash: Value = serde_json::from_str(&block_on(service.get_trash(0, 10)).unwrap().to_json()).unwrap();
+
+        block_on(service.restore_feature(ids[2])).unwrap();
+        let restored = block_on(service.get_features_in_collection_by_id(id, ids[2])).unwrap();
+
+        assert!(missing.not_found);
+        assert_eq!(trash["items"][0]["type"], "feature");
+        assert_eq!(trash["items"][0]["id"], ids[2]);
+        assert_eq!(names(&restored), ["Sao Paulo"]);
+    }
+
+    #[test]
+    fn stores_named_filters() {
+        let store = MemoryStore::default();
+        let mut service = service(&store);
+        let (id, _) = create_cities(&mut service);
+
+        block_on(service.save_filter(id, "large", &filter(id, "population >= 2900000"))).unwrap();
+        let stored = block_on(service.get_filter(id, "large")).unwrap();
+        let large = block_on(service.filter_features_in_collection(id, &stored.filter, 0, 10, false)).unwrap();
+
+        block_on(service.delete_filter(id, "large")).unwrap();
+        let deleted = block_on(service.get_filter(id, "large")).err().unwrap();
+
+        assert_eq!(names(&large), ["Salvador", "Sao Paulo"]);
+        assert!(deleted.not_found);
+    }
+
+    #[test]
+    fn missing_collections_are_not_found() {
+        let store = MemoryStore::default();
+        let mut service = service(&store);
+
+        let feature = Feature::from(&point("Natal", -35.2, -5.8, 890000));
+
+        assert!(block_on(service.get_collection(42)).err().unwrap().not_found);
+        assert!(block_on(service.get_features_in_collection(42, 0, 10, false)).err().unwrap().not_found);
+        assert!(block_on(service.create_feature(42, &feature)).err().unwrap().not_found);
+    }
+
+    #[test]
+    fn exports_every_page_in_order() {
+        let (source, target) = (MemoryStore::default(), MemoryStore::default());
+        let mut exporter = service(&source);
+        let points: Vec<Value> = (0..1001).map(|index| point(&format!("city {index}"), 0.0, 0.0, index)).collect();
+        let collection = FeatureCollection::from(&json!({"label": "cities", "features": points}));
+        let id = Option::<i64>::from(&block_on(exporter.create_collection(&collection)).unwrap().id).unwrap();
+
+        let exported = block_on(exporter.export_collection(id, &mut MemoryFeatureRepository::new(target.clone()))).unwrap();
+        let exported_id = Option::<i64>::from(&exported.id).unwrap();
+        let copy = block_on(service(&target).get_features_in_collection(exported_id, 0, 2000, false)).unwrap();
+
+        let expected: Vec<String> = (0..1001).map(|index| format!("city {index}")).collect();
+        assert_eq!(names(&copy), expected);
+    }
+
+    #[test]
+    fn exports_into_a_geopackage_that_reopens() {
+        let path = std::env::temp_dir().join(format!("geoxidated-export-{}.gpkg", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let store = MemoryStore::default();
+        let mut exporter = service(&store);
+        let (id, _) = create_cities(&mut exporter);
+
+        let pool = block_on(open_geopackage(&path.to_string_lossy())).unwrap();
+        let exported = block_on(exporter.export_collection(id, &mut GeoPackageFeatureRepository::new(pool.clone()))).unwrap();
+        block_on(pool.close());
+
+        let reopened = block_on(open_geopackage(&path.to_string_lossy())).unwrap();
+        let mut reader = FeatureService::new(Box::new(GeoPackageFeatureRepository::new(reopened.clone())));
+        let exported_id = Option::<i64>::from(&exported.id).unwrap();
+        let copy = block_on(reader.get_features_in_collection(exported_id, 0, 10, false)).unwrap();
+        let value: Value = serde_json::from_str(&copy.to_geo_json()).unwrap();
+
+        assert_eq!(names(&copy), ["Salvador", "Recife", "Sao Paulo"]);
+        assert_eq!(value["features"][0]["geometry"], json!({"type": "Point", "coordinates": [-38.5, -12.9]}));
+        assert_eq!(value["features"][2]["properties"]["population"], 12300000);
+        block_on(reopened.close());
+        let _ = std::fs::remove_file(&path);
+    }
+}